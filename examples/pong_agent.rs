@@ -0,0 +1,71 @@
+// Plays eight games of Pong (1 player) at once, with an agent that moves the
+// left paddle towards the ball, and prints the reward of every episode.
+//
+//     cargo run --release --example pong_agent
+use chip8_rs::environment::{ActionSet, Env, Observation, VecEnv};
+use chip8_rs::reward;
+
+use std::fs;
+
+const ROM: &str = "roms/games/Pong (1 player).ch8";
+const ENVS: usize = 8;
+const THREADS: usize = 4;
+const EPISODES: usize = 16;
+
+const NOOP: usize = 0;
+const UP: usize = 1;
+const DOWN: usize = 2;
+
+// The left paddle is drawn in column 2; the ball is the only other thing
+// lit in the left half of the screen.
+fn policy(observation: &Observation) -> usize {
+    let paddle: Vec<usize> = (0..observation.len())
+        .filter(|&y| observation[y][2])
+        .collect();
+    let ball = (0..observation.len()).find(|&y| observation[y][3..32].iter().any(|&lit| lit));
+
+    match (paddle.first(), ball) {
+        (Some(&top), Some(ball)) if ball < top + 1 => UP,
+        (Some(&top), Some(ball)) if ball > top + 4 => DOWN,
+        _ => NOOP,
+    }
+}
+
+fn main() {
+    let rom = fs::read(ROM).expect("run from the repository root");
+
+    let envs = (0..ENVS)
+        .map(|i| {
+            let mut env = Env::new(&rom).unwrap();
+            env.seed(i as u64);
+            env.set_actions(ActionSet::keys(&[0x1, 0x4]).unwrap());
+            env.set_reward(reward::for_rom("Pong (1 player)").unwrap());
+            env.set_max_frames(Some(60 * 60 * 5));
+            env
+        })
+        .collect();
+    let mut envs = VecEnv::new(envs, THREADS);
+
+    let mut observations = envs.reset();
+    let mut returns = [0.0; ENVS];
+    let mut episodes = 0;
+
+    while episodes < EPISODES {
+        let actions: Vec<usize> = observations.iter().map(policy).collect();
+
+        observations = envs
+            .step(&actions)
+            .into_iter()
+            .enumerate()
+            .map(|(i, (observation, reward, done))| {
+                returns[i] += reward;
+                if done {
+                    println!("environment {}: episode reward {}", i, returns[i]);
+                    returns[i] = 0.0;
+                    episodes += 1;
+                }
+                observation
+            })
+            .collect();
+    }
+}
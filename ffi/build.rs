@@ -0,0 +1,23 @@
+// Generates the C header from src/lib.rs into OUT_DIR, where a test checks it
+// against the checked-in include/chip8.h. That one is only rewritten with
+// CHIP8_UPDATE_HEADER=1, so builds leave the source tree alone.
+use std::env;
+use std::path::PathBuf;
+
+fn main() {
+    let crate_dir = env::var("CARGO_MANIFEST_DIR").unwrap();
+    let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
+    let config = cbindgen::Config::from_file("cbindgen.toml").expect("unreadable cbindgen.toml");
+
+    let bindings = cbindgen::generate_with_config(&crate_dir, config)
+        .expect("unable to generate the C header");
+    bindings.write_to_file(out_dir.join("chip8.h"));
+
+    if env::var_os("CHIP8_UPDATE_HEADER").is_some_and(|value| value == "1") {
+        bindings.write_to_file("include/chip8.h");
+    }
+
+    println!("cargo:rerun-if-changed=src/lib.rs");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+    println!("cargo:rerun-if-env-changed=CHIP8_UPDATE_HEADER");
+}
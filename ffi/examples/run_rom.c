@@ -0,0 +1,95 @@
+/*
+ * Runs a ROM for a number of frames and prints the screen and registers.
+ *
+ *     cargo build --release -p chip8-rs-ffi
+ *     cc -Iffi/include ffi/examples/run_rom.c target/release/libchip8_rs_ffi.a \
+ *         -lpthread -ldl -lm -o run_rom
+ *     ./run_rom "roms/games/Pong (alt).ch8" 120
+ */
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+#include "chip8.h"
+
+#define INSTRUCTIONS_PER_FRAME 10
+
+static long read_file(const char *path, uint8_t *buffer, size_t size) {
+    FILE *file = fopen(path, "rb");
+    if (!file) {
+        return -1;
+    }
+
+    size_t len = fread(buffer, 1, size, file);
+    fclose(file);
+    return (long)len;
+}
+
+int main(int argc, char **argv) {
+    if (argc < 2) {
+        fprintf(stderr, "usage: %s ROM [FRAMES]\n", argv[0]);
+        return 2;
+    }
+
+    int frames = argc > 2 ? atoi(argv[2]) : 60;
+
+    static uint8_t rom[CHIP8_MEMORY_SIZE];
+    long len = read_file(argv[1], rom, sizeof rom);
+    if (len < 0) {
+        perror(argv[1]);
+        return 1;
+    }
+
+    Chip8 *chip8 = chip8_new();
+    chip8_seed(chip8, 0);
+
+    if (chip8_load_rom(chip8, rom, (size_t)len) != CHIP8_STATUS_OK) {
+        fprintf(stderr, "%s: not a ROM that fits in memory\n", argv[1]);
+        chip8_free(chip8);
+        return 1;
+    }
+
+    for (int frame = 0; frame < frames; frame++) {
+        if (chip8_step(chip8, INSTRUCTIONS_PER_FRAME) != CHIP8_STATUS_OK) {
+            fprintf(stderr, "crashed in frame %d\n", frame);
+            chip8_free(chip8);
+            return 1;
+        }
+        chip8_tick_timers(chip8);
+    }
+
+    /* A save state taken now restores to the same screen and registers. */
+    static uint8_t state[CHIP8_STATE_SIZE];
+    chip8_save_state(chip8, state, sizeof state);
+    chip8_step(chip8, INSTRUCTIONS_PER_FRAME);
+    if (chip8_load_state(chip8, state, sizeof state) != CHIP8_STATUS_OK) {
+        fprintf(stderr, "save state didn't load\n");
+        chip8_free(chip8);
+        return 1;
+    }
+
+    uint8_t screen[CHIP8_WIDTH * CHIP8_HEIGHT];
+    chip8_framebuffer(chip8, screen, sizeof screen);
+    for (int y = 0; y < CHIP8_HEIGHT; y++) {
+        char row[CHIP8_WIDTH + 1];
+        for (int x = 0; x < CHIP8_WIDTH; x++) {
+            row[x] = screen[y * CHIP8_WIDTH + x] ? '#' : '.';
+        }
+        row[CHIP8_WIDTH] = '\0';
+        puts(row);
+    }
+
+    Chip8Registers registers;
+    chip8_registers(chip8, &registers);
+    printf("PC=%04X I=%04X SP=%u DT=%u ST=%u\n", registers.pc, registers.i, registers.sp,
+           registers.delay_timer, registers.sound_timer);
+    for (int i = 0; i < CHIP8_REGISTERS; i++) {
+        printf("V%X=%02X%c", i, registers.v[i], i == CHIP8_REGISTERS - 1 ? '\n' : ' ');
+    }
+
+    const uint8_t *memory = chip8_memory(chip8);
+    printf("ROM starts with %02X%02X\n", memory[0x200], memory[0x201]);
+
+    chip8_free(chip8);
+    return 0;
+}
@@ -0,0 +1,106 @@
+/* Generated by cbindgen from src/lib.rs. Do not edit. */
+
+#ifndef CHIP8_H
+#define CHIP8_H
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+#define CHIP8_WIDTH 64
+
+#define CHIP8_HEIGHT 32
+
+#define CHIP8_MEMORY_SIZE 4096
+
+#define CHIP8_KEYPAD_SIZE 16
+
+#define CHIP8_REGISTERS 16
+
+#define CHIP8_STATE_SIZE 6263
+
+typedef enum Chip8Status {
+  CHIP8_STATUS_OK = 0,
+  CHIP8_STATUS_NULL_POINTER,
+  // The ROM doesn't fit in memory.
+  CHIP8_STATUS_INVALID_ROM,
+  // The buffer isn't a save state from this version of the emulator.
+  CHIP8_STATUS_INVALID_STATE,
+  CHIP8_STATUS_INVALID_QUIRKS,
+  CHIP8_STATUS_BUFFER_TOO_SMALL,
+  // The program ran into an opcode the emulator doesn't know, or returned
+  // from a subroutine it never called. The emulator stays stopped where it
+  // crashed until a ROM or state is loaded.
+  CHIP8_STATUS_CRASHED,
+} Chip8Status;
+
+// Opaque to C.
+typedef struct Chip8 Chip8;
+
+typedef struct Chip8Registers {
+  uint8_t v[CHIP8_REGISTERS];
+  uint16_t i;
+  uint16_t pc;
+  // How many subroutine calls deep the program is.
+  uint16_t sp;
+  uint8_t delay_timer;
+  uint8_t sound_timer;
+} Chip8Registers;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+struct Chip8 *chip8_new(void);
+
+void chip8_free(struct Chip8 *chip8);
+
+// Makes CXNN deterministic, for tests and replays.
+void chip8_seed(struct Chip8 *chip8, uint64_t seed);
+
+// Takes the same list as --quirks, e.g. "vip" or "shift-vy,clip".
+enum Chip8Status chip8_set_quirks(struct Chip8 *chip8, const char *list);
+
+// Copies the ROM into memory and starts it from the beginning.
+enum Chip8Status chip8_load_rom(struct Chip8 *chip8, const uint8_t *rom, size_t len);
+
+// Runs `count` instructions. Timers are left alone; call chip8_tick_timers
+// once every 1/60 s. The vip-timing quirk has no effect here, it only paces
+// whole frames in the Rust and Python APIs.
+enum Chip8Status chip8_step(struct Chip8 *chip8, uint32_t count);
+
+void chip8_tick_timers(struct Chip8 *chip8);
+
+// Keys 0x0 to 0xF stay down until they are set up again.
+void chip8_set_key(struct Chip8 *chip8, uint8_t key, bool down);
+
+bool chip8_is_beeping(const struct Chip8 *chip8);
+
+// Whether the screen changed since the last call.
+bool chip8_take_draw_flag(struct Chip8 *chip8);
+
+// Whether the program has stopped on its own, by jumping to itself.
+bool chip8_is_done(const struct Chip8 *chip8);
+
+// Copies the screen into `out`, one byte per pixel, row by row: 1 for lit,
+// 0 for dark. `len` must be at least CHIP8_WIDTH * CHIP8_HEIGHT.
+enum Chip8Status chip8_framebuffer(const struct Chip8 *chip8, uint8_t *out, size_t len);
+
+enum Chip8Status chip8_registers(const struct Chip8 *chip8, struct Chip8Registers *out);
+
+// The CHIP8_MEMORY_SIZE bytes of memory, valid until the emulator is freed.
+// Writing through the pointer changes the running program.
+uint8_t *chip8_memory(struct Chip8 *chip8);
+
+// Writes CHIP8_STATE_SIZE bytes to `out`.
+enum Chip8Status chip8_save_state(const struct Chip8 *chip8, uint8_t *out, size_t len);
+
+enum Chip8Status chip8_load_state(struct Chip8 *chip8, const uint8_t *state, size_t len);
+
+#ifdef __cplusplus
+} // extern "C"
+#endif // __cplusplus
+
+#endif /* CHIP8_H */
@@ -0,0 +1,303 @@
+// A C API around the emulator core, for embedding it in programs written in
+// other languages. include/chip8.h is generated from this file by build.rs,
+// with CHIP8_UPDATE_HEADER=1.
+//
+// Every function takes the emulator as its first argument and does nothing,
+// or returns CHIP8_STATUS_NULL_POINTER, when it or any other pointer is null.
+#![allow(clippy::missing_safety_doc)]
+
+use chip8_rs::cpu::{self, Cpu};
+use chip8_rs::quirks::Quirks;
+
+use std::ffi::CStr;
+use std::os::raw::c_char;
+use std::panic::{self, AssertUnwindSafe};
+use std::{ptr, slice};
+
+// Spelled out so that they end up in the header as numbers.
+pub const CHIP8_WIDTH: usize = 64;
+pub const CHIP8_HEIGHT: usize = 32;
+pub const CHIP8_MEMORY_SIZE: usize = 4096;
+pub const CHIP8_KEYPAD_SIZE: usize = 16;
+pub const CHIP8_REGISTERS: usize = 16;
+pub const CHIP8_STATE_SIZE: usize = 6263;
+
+const _: () = assert!(
+    CHIP8_WIDTH == cpu::GRAPHICS_WIDTH
+        && CHIP8_HEIGHT == cpu::GRAPHICS_HEIGHT
+        && CHIP8_MEMORY_SIZE == cpu::MEMORY_SIZE
+        && CHIP8_KEYPAD_SIZE == cpu::KEYPAD_SIZE
+        && CHIP8_REGISTERS == cpu::REGISTERS
+        && CHIP8_STATE_SIZE == cpu::STATE_SIZE
+);
+
+#[repr(C)]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Chip8Status {
+    Ok = 0,
+    NullPointer,
+    /// The ROM doesn't fit in memory.
+    InvalidRom,
+    /// The buffer isn't a save state from this version of the emulator.
+    InvalidState,
+    InvalidQuirks,
+    BufferTooSmall,
+    /// The program ran into an opcode the emulator doesn't know, or returned
+    /// from a subroutine it never called. The emulator stays stopped where it
+    /// crashed until a ROM or state is loaded.
+    Crashed,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Chip8Registers {
+    pub v: [u8; CHIP8_REGISTERS],
+    pub i: u16,
+    pub pc: u16,
+    /// How many subroutine calls deep the program is.
+    pub sp: u16,
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+}
+
+/// Opaque to C.
+pub struct Chip8 {
+    cpu: Cpu,
+    crashed: bool,
+}
+
+#[no_mangle]
+pub extern "C" fn chip8_new() -> *mut Chip8 {
+    Box::into_raw(Box::new(Chip8 {
+        cpu: Cpu::new(),
+        crashed: false,
+    }))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn chip8_free(chip8: *mut Chip8) {
+    if !chip8.is_null() {
+        drop(Box::from_raw(chip8));
+    }
+}
+
+/// Makes CXNN deterministic, for tests and replays.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_seed(chip8: *mut Chip8, seed: u64) {
+    if let Some(chip8) = chip8.as_mut() {
+        chip8.cpu.seed(seed);
+    }
+}
+
+/// Takes the same list as --quirks, e.g. "vip" or "shift-vy,clip".
+#[no_mangle]
+pub unsafe extern "C" fn chip8_set_quirks(chip8: *mut Chip8, list: *const c_char) -> Chip8Status {
+    let chip8 = match chip8.as_mut() {
+        Some(chip8) if !list.is_null() => chip8,
+        _ => return Chip8Status::NullPointer,
+    };
+
+    match CStr::from_ptr(list)
+        .to_str()
+        .map_err(|e| e.to_string())
+        .and_then(Quirks::parse)
+    {
+        Ok(quirks) => {
+            chip8.cpu.set_quirks(quirks);
+            Chip8Status::Ok
+        }
+        Err(_) => Chip8Status::InvalidQuirks,
+    }
+}
+
+/// Copies the ROM into memory and starts it from the beginning.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_load_rom(
+    chip8: *mut Chip8,
+    rom: *const u8,
+    len: usize,
+) -> Chip8Status {
+    let chip8 = match chip8.as_mut() {
+        Some(chip8) if !rom.is_null() || len == 0 => chip8,
+        _ => return Chip8Status::NullPointer,
+    };
+
+    match chip8.cpu.load_rom(bytes(rom, len)) {
+        Ok(()) => {
+            chip8.crashed = false;
+            Chip8Status::Ok
+        }
+        Err(_) => Chip8Status::InvalidRom,
+    }
+}
+
+/// Runs `count` instructions. Timers are left alone; call chip8_tick_timers
+/// once every 1/60 s. The vip-timing quirk has no effect here, it only paces
+/// whole frames in the Rust and Python APIs.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_step(chip8: *mut Chip8, count: u32) -> Chip8Status {
+    let chip8 = match chip8.as_mut() {
+        Some(chip8) => chip8,
+        None => return Chip8Status::NullPointer,
+    };
+
+    if chip8.crashed {
+        return Chip8Status::Crashed;
+    }
+
+    let cpu = &mut chip8.cpu;
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        for _ in 0..count {
+            cpu.step();
+        }
+    }));
+
+    if result.is_err() {
+        chip8.crashed = true;
+        return Chip8Status::Crashed;
+    }
+
+    Chip8Status::Ok
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn chip8_tick_timers(chip8: *mut Chip8) {
+    if let Some(chip8) = chip8.as_mut() {
+        chip8.cpu.tick_timers();
+    }
+}
+
+/// Keys 0x0 to 0xF stay down until they are set up again.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_set_key(chip8: *mut Chip8, key: u8, down: bool) {
+    if let Some(chip8) = chip8.as_mut() {
+        if (key as usize) < CHIP8_KEYPAD_SIZE {
+            chip8.cpu.set_key(key, down);
+        }
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn chip8_is_beeping(chip8: *const Chip8) -> bool {
+    chip8.as_ref().is_some_and(|chip8| chip8.cpu.is_beeping())
+}
+
+/// Whether the screen changed since the last call.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_take_draw_flag(chip8: *mut Chip8) -> bool {
+    chip8
+        .as_mut()
+        .is_some_and(|chip8| chip8.cpu.take_draw_flag())
+}
+
+/// Whether the program has stopped on its own, by jumping to itself.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_is_done(chip8: *const Chip8) -> bool {
+    chip8.as_ref().is_some_and(|chip8| chip8.cpu.is_done())
+}
+
+/// Copies the screen into `out`, one byte per pixel, row by row: 1 for lit,
+/// 0 for dark. `len` must be at least CHIP8_WIDTH * CHIP8_HEIGHT.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_framebuffer(
+    chip8: *const Chip8,
+    out: *mut u8,
+    len: usize,
+) -> Chip8Status {
+    let chip8 = match chip8.as_ref() {
+        Some(chip8) if !out.is_null() => chip8,
+        _ => return Chip8Status::NullPointer,
+    };
+
+    if len < CHIP8_WIDTH * CHIP8_HEIGHT {
+        return Chip8Status::BufferTooSmall;
+    }
+
+    let out = slice::from_raw_parts_mut(out, len);
+    for (byte, &lit) in out.iter_mut().zip(chip8.cpu.graphics().iter().flatten()) {
+        *byte = lit as u8;
+    }
+
+    Chip8Status::Ok
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn chip8_registers(
+    chip8: *const Chip8,
+    out: *mut Chip8Registers,
+) -> Chip8Status {
+    let chip8 = match chip8.as_ref() {
+        Some(chip8) if !out.is_null() => chip8,
+        _ => return Chip8Status::NullPointer,
+    };
+
+    let registers = chip8.cpu.registers();
+    *out = Chip8Registers {
+        v: registers.v,
+        i: registers.index_register,
+        pc: registers.program_counter,
+        sp: registers.stack_pointer,
+        delay_timer: registers.delay_timer,
+        sound_timer: registers.sound_timer,
+    };
+
+    Chip8Status::Ok
+}
+
+/// The CHIP8_MEMORY_SIZE bytes of memory, valid until the emulator is freed.
+/// Writing through the pointer changes the running program.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_memory(chip8: *mut Chip8) -> *mut u8 {
+    chip8
+        .as_mut()
+        .map_or(ptr::null_mut(), |chip8| chip8.cpu.memory_mut().as_mut_ptr())
+}
+
+/// Writes CHIP8_STATE_SIZE bytes to `out`.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_save_state(
+    chip8: *const Chip8,
+    out: *mut u8,
+    len: usize,
+) -> Chip8Status {
+    let chip8 = match chip8.as_ref() {
+        Some(chip8) if !out.is_null() => chip8,
+        _ => return Chip8Status::NullPointer,
+    };
+
+    if len < CHIP8_STATE_SIZE {
+        return Chip8Status::BufferTooSmall;
+    }
+
+    let state = chip8.cpu.save_state();
+    slice::from_raw_parts_mut(out, state.len()).copy_from_slice(&state);
+    Chip8Status::Ok
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn chip8_load_state(
+    chip8: *mut Chip8,
+    state: *const u8,
+    len: usize,
+) -> Chip8Status {
+    let chip8 = match chip8.as_mut() {
+        Some(chip8) if !state.is_null() => chip8,
+        _ => return Chip8Status::NullPointer,
+    };
+
+    match chip8.cpu.load_state(bytes(state, len)) {
+        Ok(()) => {
+            chip8.crashed = false;
+            Chip8Status::Ok
+        }
+        Err(_) => Chip8Status::InvalidState,
+    }
+}
+
+unsafe fn bytes<'a>(data: *const u8, len: usize) -> &'a [u8] {
+    if len == 0 {
+        &[]
+    } else {
+        slice::from_raw_parts(data, len)
+    }
+}
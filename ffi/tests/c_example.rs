@@ -0,0 +1,51 @@
+// Builds examples/run_rom.c against the static library and the generated
+// header, and runs it on a small ROM.
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+// Clears the screen, draws the font's 0 in the top left corner, sets VA to
+// 0x2A and loops.
+const DRAW_ZERO: [u8; 10] = [0x00, 0xe0, 0xa0, 0x00, 0xd0, 0x05, 0x6a, 0x2a, 0x12, 0x08];
+
+// Test executables live in target/<profile>/deps, next to which cargo puts
+// the libraries.
+fn profile_dir() -> PathBuf {
+    let exe = env::current_exe().unwrap();
+    exe.parent().unwrap().parent().unwrap().to_path_buf()
+}
+
+#[test]
+fn c_example_runs_a_rom() {
+    let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let out_dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR"));
+    let example = out_dir.join("run_rom");
+    let rom = out_dir.join("draw_zero.ch8");
+    fs::write(&rom, DRAW_ZERO).unwrap();
+
+    let status = Command::new(env::var("CC").unwrap_or_else(|_| String::from("cc")))
+        .arg("-Wall")
+        .arg("-Werror")
+        .arg("-I")
+        .arg(manifest_dir.join("include"))
+        .arg(manifest_dir.join("examples").join("run_rom.c"))
+        .arg(profile_dir().join("libchip8_rs_ffi.a"))
+        .args(["-lpthread", "-ldl", "-lm", "-o"])
+        .arg(&example)
+        .status()
+        .expect("a C compiler is needed to build the example");
+    assert!(status.success());
+
+    let output = Command::new(&example).arg(&rom).arg("3").output().unwrap();
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert!(lines[0].starts_with("####."));
+    assert!(lines[1].starts_with("#..#."));
+    assert!(lines[5].starts_with("....."));
+    assert!(stdout.contains("PC=0208"));
+    assert!(stdout.contains("VA=2A"));
+    assert!(stdout.contains("ROM starts with 00E0"));
+}
@@ -0,0 +1,16 @@
+// The checked-in header has to match what build.rs generates from src/lib.rs.
+use std::fs;
+use std::path::PathBuf;
+
+#[test]
+fn checked_in_header_is_current() {
+    let generated = include_str!(concat!(env!("OUT_DIR"), "/chip8.h"));
+    let checked_in =
+        fs::read_to_string(PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("include/chip8.h"))
+            .unwrap();
+
+    assert!(
+        generated == checked_in,
+        "include/chip8.h is out of date, rebuild with CHIP8_UPDATE_HEADER=1"
+    );
+}
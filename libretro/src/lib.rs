@@ -0,0 +1,748 @@
+// A libretro core around the emulator, for RetroArch and other libretro
+// frontends. Only the parts of the API a CHIP-8 needs are implemented.
+
+// Everything exported is called by the frontend, with the guarantees about
+// pointers that libretro.h spells out.
+#![allow(clippy::missing_safety_doc)]
+
+use chip8_rs::cpu::{Cpu, Graphics, GRAPHICS_HEIGHT, GRAPHICS_WIDTH, KEYPAD_SIZE, STATE_SIZE};
+use chip8_rs::host::{self, Audio, Host, Keypad};
+use chip8_rs::palette::Palette;
+use chip8_rs::quirks::Quirks;
+
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_uint, c_void};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::slice;
+use std::sync::Mutex;
+
+const RETRO_API_VERSION: c_uint = 1;
+
+const RETRO_ENVIRONMENT_GET_CAN_DUPE: c_uint = 3;
+const RETRO_ENVIRONMENT_SET_MESSAGE: c_uint = 6;
+const RETRO_ENVIRONMENT_SET_PIXEL_FORMAT: c_uint = 10;
+const RETRO_ENVIRONMENT_SET_INPUT_DESCRIPTORS: c_uint = 11;
+const RETRO_ENVIRONMENT_GET_VARIABLE: c_uint = 15;
+const RETRO_ENVIRONMENT_SET_VARIABLES: c_uint = 16;
+const RETRO_ENVIRONMENT_GET_VARIABLE_UPDATE: c_uint = 17;
+
+const RETRO_PIXEL_FORMAT_XRGB8888: c_uint = 1;
+
+const RETRO_REGION_NTSC: c_uint = 0;
+
+const RETRO_MEMORY_SYSTEM_RAM: c_uint = 2;
+
+const RETRO_DEVICE_JOYPAD: c_uint = 1;
+const RETRO_DEVICE_KEYBOARD: c_uint = 3;
+
+const RETRO_DEVICE_ID_JOYPAD_B: c_uint = 0;
+const RETRO_DEVICE_ID_JOYPAD_Y: c_uint = 1;
+const RETRO_DEVICE_ID_JOYPAD_SELECT: c_uint = 2;
+const RETRO_DEVICE_ID_JOYPAD_START: c_uint = 3;
+const RETRO_DEVICE_ID_JOYPAD_UP: c_uint = 4;
+const RETRO_DEVICE_ID_JOYPAD_DOWN: c_uint = 5;
+const RETRO_DEVICE_ID_JOYPAD_LEFT: c_uint = 6;
+const RETRO_DEVICE_ID_JOYPAD_RIGHT: c_uint = 7;
+const RETRO_DEVICE_ID_JOYPAD_A: c_uint = 8;
+const RETRO_DEVICE_ID_JOYPAD_X: c_uint = 9;
+const RETRO_DEVICE_ID_JOYPAD_L: c_uint = 10;
+const RETRO_DEVICE_ID_JOYPAD_R: c_uint = 11;
+
+// The D-pad steers with the hex keypad's arrows, 2, 4, 6 and 8, and the face
+// buttons cover the keys games most often use besides those.
+const JOYPAD_BINDINGS: [(c_uint, u8, &[u8]); 12] = [
+    (RETRO_DEVICE_ID_JOYPAD_UP, 0x2, b"Up\0"),
+    (RETRO_DEVICE_ID_JOYPAD_DOWN, 0x8, b"Down\0"),
+    (RETRO_DEVICE_ID_JOYPAD_LEFT, 0x4, b"Left\0"),
+    (RETRO_DEVICE_ID_JOYPAD_RIGHT, 0x6, b"Right\0"),
+    (RETRO_DEVICE_ID_JOYPAD_B, 0x5, b"5\0"),
+    (RETRO_DEVICE_ID_JOYPAD_A, 0x0, b"0\0"),
+    (RETRO_DEVICE_ID_JOYPAD_Y, 0x1, b"1\0"),
+    (RETRO_DEVICE_ID_JOYPAD_X, 0x3, b"3\0"),
+    (RETRO_DEVICE_ID_JOYPAD_L, 0x7, b"7\0"),
+    (RETRO_DEVICE_ID_JOYPAD_R, 0x9, b"9\0"),
+    (RETRO_DEVICE_ID_JOYPAD_SELECT, 0xE, b"E\0"),
+    (RETRO_DEVICE_ID_JOYPAD_START, 0xF, b"F\0"),
+];
+
+// The keyboard gets the usual QWERTY layout, in keypad order 0 to F. RETROK
+// codes for letters and digits are their lowercase ASCII values.
+const KEYBOARD_BINDINGS: &[u8; KEYPAD_SIZE] = b"x123qweasdzc4rfv";
+
+const SAMPLE_RATE: f64 = 44100.0;
+const FRAMES_PER_SECOND: f64 = 60.0;
+const SAMPLES_PER_FRAME: usize = (SAMPLE_RATE / FRAMES_PER_SECOND) as usize;
+const TONE_FREQUENCY: usize = 440;
+const TONE_VOLUME: i16 = 4000;
+
+const DEFAULT_SPEED: u8 = 10;
+const MAX_SPEED: u8 = 40;
+
+const SPEED_VARIABLE: &[u8] = b"chip8_speed\0";
+const QUIRKS_VARIABLE: &[u8] = b"chip8_quirks\0";
+const PALETTE_VARIABLE: &[u8] = b"chip8_palette\0";
+
+const CRASH_MESSAGE: &[u8] = b"The game crashed. Reset or load a state to carry on.\0";
+// Three seconds.
+const CRASH_MESSAGE_FRAMES: c_uint = 180;
+
+#[repr(C)]
+pub struct RetroSystemInfo {
+    library_name: *const c_char,
+    library_version: *const c_char,
+    valid_extensions: *const c_char,
+    need_fullpath: bool,
+    block_extract: bool,
+}
+
+#[repr(C)]
+pub struct RetroGameGeometry {
+    base_width: c_uint,
+    base_height: c_uint,
+    max_width: c_uint,
+    max_height: c_uint,
+    aspect_ratio: f32,
+}
+
+#[repr(C)]
+pub struct RetroSystemTiming {
+    fps: f64,
+    sample_rate: f64,
+}
+
+#[repr(C)]
+pub struct RetroSystemAvInfo {
+    geometry: RetroGameGeometry,
+    timing: RetroSystemTiming,
+}
+
+#[repr(C)]
+pub struct RetroGameInfo {
+    pub path: *const c_char,
+    pub data: *const c_void,
+    pub size: usize,
+    pub meta: *const c_char,
+}
+
+#[repr(C)]
+struct RetroVariable {
+    key: *const c_char,
+    value: *const c_char,
+}
+
+#[repr(C)]
+struct RetroMessage {
+    msg: *const c_char,
+    frames: c_uint,
+}
+
+#[repr(C)]
+struct RetroInputDescriptor {
+    port: c_uint,
+    device: c_uint,
+    index: c_uint,
+    id: c_uint,
+    description: *const c_char,
+}
+
+type EnvironmentFn = unsafe extern "C" fn(cmd: c_uint, data: *mut c_void) -> bool;
+type VideoRefreshFn =
+    unsafe extern "C" fn(data: *const c_void, width: c_uint, height: c_uint, pitch: usize);
+type AudioSampleFn = unsafe extern "C" fn(left: i16, right: i16);
+type AudioSampleBatchFn = unsafe extern "C" fn(data: *const i16, frames: usize) -> usize;
+type InputPollFn = unsafe extern "C" fn();
+type InputStateFn =
+    unsafe extern "C" fn(port: c_uint, device: c_uint, index: c_uint, id: c_uint) -> i16;
+
+#[derive(Clone, Copy)]
+struct Callbacks {
+    environment: Option<EnvironmentFn>,
+    video_refresh: Option<VideoRefreshFn>,
+    audio_sample_batch: Option<AudioSampleBatchFn>,
+    input_poll: Option<InputPollFn>,
+    input_state: Option<InputStateFn>,
+}
+
+struct Core {
+    cpu: Cpu,
+    rom: Vec<u8>,
+    speed: u8,
+    palette: Palette,
+    framebuffer: Framebuffer,
+    tone: Tone,
+    // Whether the frontend shows the last frame again when given none.
+    can_dupe: bool,
+    // Set when the emulator panicked, on an opcode it doesn't know or a
+    // return without a call. Nothing runs after that until the game is reset,
+    // a state is loaded or another game is loaded.
+    crashed: bool,
+}
+
+// The core options, read from the frontend before the core is locked.
+struct Options {
+    speed: u8,
+    quirks: Quirks,
+    palette: Palette,
+}
+
+// What a frame sends the frontend, copied out of the core so that the
+// callbacks run with it unlocked and can call back into it.
+struct Frame {
+    // None to show the last frame again.
+    pixels: Option<Vec<u32>>,
+    samples: Vec<i16>,
+    // Whether the game crashed during this frame.
+    crashed: bool,
+}
+
+static CALLBACKS: Mutex<Callbacks> = Mutex::new(Callbacks {
+    environment: None,
+    video_refresh: None,
+    audio_sample_batch: None,
+    input_poll: None,
+    input_state: None,
+});
+static CORE: Mutex<Option<Core>> = Mutex::new(None);
+
+// The screen as XRGB8888, one u32 per CHIP-8 pixel.
+struct Framebuffer {
+    pixels: Vec<u32>,
+    palette: Palette,
+}
+
+impl host::Display for Framebuffer {
+    fn draw(&mut self, graphics: &Graphics) {
+        for (y, row) in graphics.iter().enumerate() {
+            for (x, &pixel) in row.iter().enumerate() {
+                self.pixels[y * GRAPHICS_WIDTH + x] = if pixel {
+                    self.palette.on()
+                } else {
+                    self.palette.off()
+                };
+            }
+        }
+    }
+}
+
+// A square wave, played a frame's worth of samples at a time.
+struct Tone {
+    beeping: bool,
+    phase: usize,
+    samples: Vec<i16>,
+}
+
+impl Audio for Tone {
+    fn set_beeping(&mut self, beeping: bool) {
+        self.beeping = beeping;
+    }
+}
+
+impl Tone {
+    fn fill(&mut self) {
+        let period = SAMPLE_RATE as usize / TONE_FREQUENCY;
+        self.samples.clear();
+
+        for _ in 0..SAMPLES_PER_FRAME {
+            let sample = match self.beeping {
+                true if self.phase < period / 2 => TONE_VOLUME,
+                true => -TONE_VOLUME,
+                false => 0,
+            };
+            self.samples.push(sample);
+            self.samples.push(sample);
+            self.phase = (self.phase + 1) % period;
+        }
+    }
+}
+
+// The keypad as read through the frontend's input callbacks.
+struct RetroKeypad {
+    input_state: Option<InputStateFn>,
+}
+
+impl Keypad for RetroKeypad {
+    fn keys(&mut self) -> [bool; KEYPAD_SIZE] {
+        let mut keys = [false; KEYPAD_SIZE];
+        let input_state = match self.input_state {
+            Some(input_state) => input_state,
+            None => return keys,
+        };
+
+        for &(id, key, _) in JOYPAD_BINDINGS.iter() {
+            if unsafe { input_state(0, RETRO_DEVICE_JOYPAD, 0, id) } != 0 {
+                keys[key as usize] = true;
+            }
+        }
+
+        for (key, &code) in KEYBOARD_BINDINGS.iter().enumerate() {
+            if unsafe { input_state(0, RETRO_DEVICE_KEYBOARD, 0, code as c_uint) } != 0 {
+                keys[key] = true;
+            }
+        }
+
+        keys
+    }
+}
+
+// Calls `f` with the loaded game. A panic marks it crashed instead of
+// unwinding into the frontend, which would abort it. `f` mustn't call the
+// frontend, which may call back into the core while it's locked.
+fn with_core<T, F: FnOnce(&mut Core) -> T>(f: F) -> Option<T> {
+    let mut guard = CORE.lock().unwrap();
+    let core = guard.as_mut()?;
+
+    match panic::catch_unwind(AssertUnwindSafe(|| f(core))) {
+        Ok(result) => Some(result),
+        Err(_) => {
+            core.crashed = true;
+            None
+        }
+    }
+}
+
+// Like with_core, for a game that hasn't crashed.
+fn with_running_core<T, F: FnOnce(&mut Core) -> T>(f: F) -> Option<T> {
+    with_core(|core| if core.crashed { None } else { Some(f(core)) }).flatten()
+}
+
+fn environment(cmd: c_uint, data: *mut c_void) -> bool {
+    let environment = CALLBACKS.lock().unwrap().environment;
+
+    match environment {
+        Some(environment) => unsafe { environment(cmd, data) },
+        None => false,
+    }
+}
+
+fn variable(key: &[u8]) -> Option<String> {
+    let mut variable = RetroVariable {
+        key: key.as_ptr() as *const c_char,
+        value: ptr::null(),
+    };
+
+    if !environment(
+        RETRO_ENVIRONMENT_GET_VARIABLE,
+        &mut variable as *mut RetroVariable as *mut c_void,
+    ) || variable.value.is_null()
+    {
+        return None;
+    }
+
+    let value = unsafe { CStr::from_ptr(variable.value) };
+    Some(value.to_string_lossy().into_owned())
+}
+
+// Core options, in the frontend's `Description; first|second|...` format,
+// where the first value is the default.
+fn declare_variables() {
+    let speeds: Vec<String> = std::iter::once(DEFAULT_SPEED)
+        .chain((1..=MAX_SPEED).filter(|&speed| speed != DEFAULT_SPEED))
+        .map(|speed| speed.to_string())
+        .collect();
+
+    let definitions = [
+        (
+            SPEED_VARIABLE,
+            format!("Instructions per frame; {}", speeds.join("|")),
+        ),
+        (
+            QUIRKS_VARIABLE,
+            String::from("Quirks; none|vip|schip|vip,vip-timing"),
+        ),
+        (
+            PALETTE_VARIABLE,
+            format!("Palette; {}", Palette::names().join("|")),
+        ),
+    ];
+
+    // The frontend copies what it needs before returning, the strings only
+    // have to outlive the call.
+    let values: Vec<CString> = definitions
+        .iter()
+        .map(|(_, value)| CString::new(value.as_str()).unwrap())
+        .collect();
+    let mut variables: Vec<RetroVariable> = definitions
+        .iter()
+        .zip(values.iter())
+        .map(|((key, _), value)| RetroVariable {
+            key: key.as_ptr() as *const c_char,
+            value: value.as_ptr(),
+        })
+        .collect();
+    variables.push(RetroVariable {
+        key: ptr::null(),
+        value: ptr::null(),
+    });
+
+    environment(
+        RETRO_ENVIRONMENT_SET_VARIABLES,
+        variables.as_mut_ptr() as *mut c_void,
+    );
+}
+
+fn declare_input_descriptors() {
+    let mut descriptors: Vec<RetroInputDescriptor> = JOYPAD_BINDINGS
+        .iter()
+        .map(|&(id, _, description)| RetroInputDescriptor {
+            port: 0,
+            device: RETRO_DEVICE_JOYPAD,
+            index: 0,
+            id,
+            description: description.as_ptr() as *const c_char,
+        })
+        .collect();
+    descriptors.push(RetroInputDescriptor {
+        port: 0,
+        device: 0,
+        index: 0,
+        id: 0,
+        description: ptr::null(),
+    });
+
+    environment(
+        RETRO_ENVIRONMENT_SET_INPUT_DESCRIPTORS,
+        descriptors.as_mut_ptr() as *mut c_void,
+    );
+}
+
+impl Options {
+    fn read() -> Options {
+        Options {
+            speed: variable(SPEED_VARIABLE)
+                .and_then(|value| value.parse().ok())
+                .unwrap_or(DEFAULT_SPEED),
+            quirks: variable(QUIRKS_VARIABLE)
+                .and_then(|value| Quirks::parse(&value).ok())
+                .unwrap_or_default(),
+            palette: variable(PALETTE_VARIABLE)
+                .and_then(|value| Palette::named(&value))
+                .unwrap_or_default(),
+        }
+    }
+}
+
+impl Core {
+    fn apply(&mut self, options: &Options) {
+        self.speed = options.speed;
+        self.cpu.set_quirks(options.quirks);
+        self.palette = options.palette;
+        self.framebuffer.palette = self.palette;
+        self.cpu.redraw();
+    }
+
+    // Runs a frame, unless the game crashed. A crashed game keeps showing
+    // its last frame, in silence.
+    fn run(&mut self, keys: &mut [bool; KEYPAD_SIZE]) -> Frame {
+        let mut crashed = false;
+        let redrawn = !self.crashed
+            && panic::catch_unwind(AssertUnwindSafe(|| {
+                Host {
+                    display: &mut self.framebuffer,
+                    audio: &mut self.tone,
+                    keypad: keys,
+                    clock: &mut (),
+                }
+                .run_frame(&mut self.cpu, self.speed)
+            }))
+            .unwrap_or_else(|_| {
+                self.crashed = true;
+                crashed = true;
+                false
+            });
+
+        if self.crashed {
+            self.tone.beeping = false;
+        }
+        self.tone.fill();
+
+        Frame {
+            // A null frame tells the frontend to show the last one again,
+            // when it can.
+            pixels: (redrawn || !self.can_dupe).then(|| self.framebuffer.pixels.clone()),
+            samples: self.tone.samples.clone(),
+            crashed,
+        }
+    }
+
+    // Starts the ROM over. Reloading clears memory the ROM may have written
+    // over.
+    fn reset(&mut self) {
+        let rom = self.rom.clone();
+        let _ = self.cpu.load_rom(&rom);
+        self.crashed = false;
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn retro_api_version() -> c_uint {
+    RETRO_API_VERSION
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn retro_get_system_info(info: *mut RetroSystemInfo) {
+    *info = RetroSystemInfo {
+        library_name: b"chip8-rs\0".as_ptr() as *const c_char,
+        library_version: concat!(env!("CARGO_PKG_VERSION"), "\0").as_ptr() as *const c_char,
+        valid_extensions: b"ch8\0".as_ptr() as *const c_char,
+        need_fullpath: false,
+        block_extract: false,
+    };
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn retro_get_system_av_info(info: *mut RetroSystemAvInfo) {
+    *info = RetroSystemAvInfo {
+        geometry: RetroGameGeometry {
+            base_width: GRAPHICS_WIDTH as c_uint,
+            base_height: GRAPHICS_HEIGHT as c_uint,
+            max_width: GRAPHICS_WIDTH as c_uint,
+            max_height: GRAPHICS_HEIGHT as c_uint,
+            aspect_ratio: GRAPHICS_WIDTH as f32 / GRAPHICS_HEIGHT as f32,
+        },
+        timing: RetroSystemTiming {
+            fps: FRAMES_PER_SECOND,
+            sample_rate: SAMPLE_RATE,
+        },
+    };
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_environment(callback: EnvironmentFn) {
+    CALLBACKS.lock().unwrap().environment = Some(callback);
+    declare_variables();
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_video_refresh(callback: VideoRefreshFn) {
+    CALLBACKS.lock().unwrap().video_refresh = Some(callback);
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_audio_sample(_: AudioSampleFn) {}
+
+#[no_mangle]
+pub extern "C" fn retro_set_audio_sample_batch(callback: AudioSampleBatchFn) {
+    CALLBACKS.lock().unwrap().audio_sample_batch = Some(callback);
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_input_poll(callback: InputPollFn) {
+    CALLBACKS.lock().unwrap().input_poll = Some(callback);
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_input_state(callback: InputStateFn) {
+    CALLBACKS.lock().unwrap().input_state = Some(callback);
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_controller_port_device(_: c_uint, _: c_uint) {}
+
+#[no_mangle]
+pub extern "C" fn retro_init() {}
+
+#[no_mangle]
+pub extern "C" fn retro_deinit() {
+    *CORE.lock().unwrap() = None;
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn retro_load_game(game: *const RetroGameInfo) -> bool {
+    if game.is_null() || (*game).data.is_null() {
+        return false;
+    }
+
+    let mut format = RETRO_PIXEL_FORMAT_XRGB8888;
+    if !environment(
+        RETRO_ENVIRONMENT_SET_PIXEL_FORMAT,
+        &mut format as *mut c_uint as *mut c_void,
+    ) {
+        return false;
+    }
+    declare_input_descriptors();
+
+    let mut can_dupe = false;
+    let can_dupe = environment(
+        RETRO_ENVIRONMENT_GET_CAN_DUPE,
+        &mut can_dupe as *mut bool as *mut c_void,
+    ) && can_dupe;
+
+    let rom = slice::from_raw_parts((*game).data as *const u8, (*game).size).to_vec();
+    let options = Options::read();
+    let core = panic::catch_unwind(|| {
+        let mut cpu = Cpu::new();
+        cpu.load_rom(&rom).ok()?;
+
+        let mut core = Core {
+            cpu,
+            rom,
+            speed: DEFAULT_SPEED,
+            palette: Palette::default(),
+            framebuffer: Framebuffer {
+                pixels: vec![0; GRAPHICS_WIDTH * GRAPHICS_HEIGHT],
+                palette: Palette::default(),
+            },
+            tone: Tone {
+                beeping: false,
+                phase: 0,
+                samples: Vec::with_capacity(SAMPLES_PER_FRAME * 2),
+            },
+            can_dupe,
+            crashed: false,
+        };
+        core.apply(&options);
+        Some(core)
+    });
+
+    match core {
+        Ok(Some(core)) => {
+            *CORE.lock().unwrap() = Some(core);
+            true
+        }
+        _ => false,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn retro_load_game_special(_: c_uint, _: *const RetroGameInfo, _: usize) -> bool {
+    false
+}
+
+#[no_mangle]
+pub extern "C" fn retro_unload_game() {
+    *CORE.lock().unwrap() = None;
+}
+
+#[no_mangle]
+pub extern "C" fn retro_reset() {
+    with_core(Core::reset);
+}
+
+// The frontend is only called with neither CALLBACKS nor CORE locked, so that
+// callbacks calling back into the core don't deadlock.
+#[no_mangle]
+pub extern "C" fn retro_run() {
+    let mut updated = false;
+    if environment(
+        RETRO_ENVIRONMENT_GET_VARIABLE_UPDATE,
+        &mut updated as *mut bool as *mut c_void,
+    ) && updated
+    {
+        let options = Options::read();
+        with_core(|core| core.apply(&options));
+    }
+
+    let callbacks = *CALLBACKS.lock().unwrap();
+    if let Some(input_poll) = callbacks.input_poll {
+        unsafe { input_poll() };
+    }
+    let mut keys = RetroKeypad {
+        input_state: callbacks.input_state,
+    }
+    .keys();
+
+    let frame = match with_core(|core| core.run(&mut keys)) {
+        Some(frame) => frame,
+        None => return,
+    };
+
+    if frame.crashed {
+        let mut message = RetroMessage {
+            msg: CRASH_MESSAGE.as_ptr() as *const c_char,
+            frames: CRASH_MESSAGE_FRAMES,
+        };
+        environment(
+            RETRO_ENVIRONMENT_SET_MESSAGE,
+            &mut message as *mut RetroMessage as *mut c_void,
+        );
+    }
+
+    if let Some(video_refresh) = callbacks.video_refresh {
+        let data = match &frame.pixels {
+            Some(pixels) => pixels.as_ptr() as *const c_void,
+            None => ptr::null(),
+        };
+        unsafe {
+            video_refresh(
+                data,
+                GRAPHICS_WIDTH as c_uint,
+                GRAPHICS_HEIGHT as c_uint,
+                GRAPHICS_WIDTH * 4,
+            )
+        };
+    }
+
+    if let Some(audio_sample_batch) = callbacks.audio_sample_batch {
+        unsafe { audio_sample_batch(frame.samples.as_ptr(), SAMPLES_PER_FRAME) };
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn retro_serialize_size() -> usize {
+    STATE_SIZE
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn retro_serialize(data: *mut c_void, size: usize) -> bool {
+    if data.is_null() || size < STATE_SIZE {
+        return false;
+    }
+
+    with_running_core(|core| {
+        let state = core.cpu.save_state();
+        ptr::copy_nonoverlapping(state.as_ptr(), data as *mut u8, state.len());
+    })
+    .is_some()
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn retro_unserialize(data: *const c_void, size: usize) -> bool {
+    if data.is_null() {
+        return false;
+    }
+
+    let state = slice::from_raw_parts(data as *const u8, size.min(STATE_SIZE));
+    with_core(|core| {
+        let loaded = core.cpu.load_state(state).is_ok();
+        if loaded {
+            // The screen has to be sent again, whatever the state's draw flag.
+            core.cpu.redraw();
+            core.crashed = false;
+        }
+        loaded
+    })
+    .unwrap_or(false)
+}
+
+#[no_mangle]
+pub extern "C" fn retro_cheat_reset() {}
+
+#[no_mangle]
+pub extern "C" fn retro_cheat_set(_: c_uint, _: bool, _: *const c_char) {}
+
+#[no_mangle]
+pub extern "C" fn retro_get_region() -> c_uint {
+    RETRO_REGION_NTSC
+}
+
+// The 4 KB of CHIP-8 memory, for cheats and achievements. The core stays put
+// in CORE until the game is unloaded, and so does the pointer.
+#[no_mangle]
+pub extern "C" fn retro_get_memory_data(id: c_uint) -> *mut c_void {
+    if id != RETRO_MEMORY_SYSTEM_RAM {
+        return ptr::null_mut();
+    }
+
+    with_running_core(|core| core.cpu.memory_mut().as_mut_ptr() as *mut c_void)
+        .unwrap_or(ptr::null_mut())
+}
+
+#[no_mangle]
+pub extern "C" fn retro_get_memory_size(id: c_uint) -> usize {
+    if id != RETRO_MEMORY_SYSTEM_RAM {
+        return 0;
+    }
+
+    with_running_core(|core| core.cpu.memory().len()).unwrap_or(0)
+}
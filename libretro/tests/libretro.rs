@@ -0,0 +1,186 @@
+// Drives the core the way a frontend does, through its exported functions and
+// callbacks that keep what the core sends them.
+use chip8_rs_libretro::*;
+
+use std::ffi::CStr;
+use std::os::raw::{c_char, c_uint, c_void};
+use std::ptr;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+const RETRO_ENVIRONMENT_GET_CAN_DUPE: c_uint = 3;
+const RETRO_ENVIRONMENT_SET_MESSAGE: c_uint = 6;
+const RETRO_ENVIRONMENT_SET_PIXEL_FORMAT: c_uint = 10;
+const RETRO_MEMORY_SYSTEM_RAM: c_uint = 2;
+
+// Clears the screen, draws the font's 0 in the top left corner and loops
+// without drawing again.
+const DRAW_ZERO: [u8; 8] = [0x00, 0xe0, 0xa0, 0x00, 0xd0, 0x05, 0x12, 0x06];
+// Runs into 0000, which the emulator doesn't know.
+const UNKNOWN_OPCODE: [u8; 2] = [0x00, 0x00];
+
+// The core is a global, so tests take turns.
+static SERIAL: Mutex<()> = Mutex::new(());
+static CAN_DUPE: AtomicBool = AtomicBool::new(true);
+// Every frame sent, None for the ones sent as NULL.
+static FRAMES: Mutex<Vec<Option<Vec<u32>>>> = Mutex::new(Vec::new());
+// Whether the audio sent last was all silence.
+static SILENT: AtomicBool = AtomicBool::new(true);
+// The memory size the core reported when called back while sending a frame.
+static MEMORY_SIZE: AtomicUsize = AtomicUsize::new(0);
+static MESSAGES: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+#[repr(C)]
+struct RetroMessage {
+    msg: *const c_char,
+    frames: c_uint,
+}
+
+unsafe extern "C" fn environment(cmd: c_uint, data: *mut c_void) -> bool {
+    match cmd {
+        RETRO_ENVIRONMENT_SET_PIXEL_FORMAT => true,
+        RETRO_ENVIRONMENT_SET_MESSAGE => {
+            let message = &*(data as *const RetroMessage);
+            let text = CStr::from_ptr(message.msg).to_string_lossy().into_owned();
+            MESSAGES.lock().unwrap().push(text);
+            true
+        }
+        RETRO_ENVIRONMENT_GET_CAN_DUPE => {
+            *(data as *mut bool) = CAN_DUPE.load(Ordering::SeqCst);
+            true
+        }
+        _ => false,
+    }
+}
+
+unsafe extern "C" fn video_refresh(data: *const c_void, width: c_uint, height: c_uint, _: usize) {
+    let frame = if data.is_null() {
+        None
+    } else {
+        let len = (width * height) as usize;
+        Some(std::slice::from_raw_parts(data as *const u32, len).to_vec())
+    };
+    FRAMES.lock().unwrap().push(frame);
+    // Frontends may call into the core from their callbacks.
+    MEMORY_SIZE.store(
+        retro_get_memory_size(RETRO_MEMORY_SYSTEM_RAM),
+        Ordering::SeqCst,
+    );
+}
+
+unsafe extern "C" fn audio_sample_batch(data: *const i16, frames: usize) -> usize {
+    let samples = std::slice::from_raw_parts(data, frames * 2);
+    SILENT.store(samples.iter().all(|&sample| sample == 0), Ordering::SeqCst);
+    frames
+}
+
+fn load(rom: &[u8], can_dupe: bool) -> bool {
+    CAN_DUPE.store(can_dupe, Ordering::SeqCst);
+    FRAMES.lock().unwrap().clear();
+    MESSAGES.lock().unwrap().clear();
+    retro_set_environment(environment);
+    retro_set_video_refresh(video_refresh);
+    retro_set_audio_sample_batch(audio_sample_batch);
+
+    let game = RetroGameInfo {
+        path: ptr::null(),
+        data: rom.as_ptr() as *const c_void,
+        size: rom.len(),
+        meta: ptr::null(),
+    };
+    unsafe { retro_load_game(&game) }
+}
+
+fn run() -> Option<Vec<u32>> {
+    retro_run();
+    FRAMES.lock().unwrap().last().cloned().unwrap()
+}
+
+#[test]
+fn states_load_back_with_the_screen() {
+    let _serial = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+    assert!(load(&DRAW_ZERO, true));
+
+    let drawn = run().expect("the first frame draws");
+    assert_eq!(run(), None);
+    assert_eq!(MEMORY_SIZE.load(Ordering::SeqCst), 4096);
+
+    let mut state = vec![0u8; retro_serialize_size()];
+    assert!(unsafe { retro_serialize(state.as_mut_ptr() as *mut c_void, state.len()) });
+
+    assert_eq!(run(), None);
+
+    assert!(unsafe { retro_unserialize(state.as_ptr() as *const c_void, state.len()) });
+    assert_eq!(run(), Some(drawn));
+
+    retro_unload_game();
+}
+
+#[test]
+fn frames_are_sent_again_when_the_frontend_cant_dupe() {
+    let _serial = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+    assert!(load(&DRAW_ZERO, false));
+
+    let drawn = run().expect("the first frame draws");
+    assert_eq!(run(), Some(drawn.clone()));
+    assert_eq!(run(), Some(drawn));
+
+    retro_unload_game();
+}
+
+#[test]
+fn a_crashed_game_stops_without_taking_the_frontend_down() {
+    let _serial = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+    assert!(load(&UNKNOWN_OPCODE, true));
+
+    // Frames keep coming, as dupes, in silence.
+    assert_eq!(run(), None);
+    assert_eq!(run(), None);
+    assert_eq!(FRAMES.lock().unwrap().len(), 2);
+    assert!(SILENT.load(Ordering::SeqCst));
+    assert_eq!(MESSAGES.lock().unwrap().len(), 1);
+    assert_eq!(retro_get_memory_size(RETRO_MEMORY_SYSTEM_RAM), 0);
+
+    let mut state = vec![0u8; retro_serialize_size()];
+    assert!(!unsafe { retro_serialize(state.as_mut_ptr() as *mut c_void, state.len()) });
+
+    // Another game runs as usual.
+    assert!(load(&DRAW_ZERO, true));
+    assert!(run().is_some());
+
+    retro_unload_game();
+}
+
+#[test]
+fn a_crashed_game_without_dupes_keeps_sending_its_last_frame() {
+    let _serial = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+    assert!(load(&UNKNOWN_OPCODE, false));
+
+    let first = run().expect("frames are always sent");
+    assert_eq!(run(), Some(first));
+
+    retro_unload_game();
+}
+
+#[test]
+fn crashed_games_come_back_on_reset_or_an_earlier_state() {
+    let _serial = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+    // Draws, then runs into 0000.
+    let rom = [0x00, 0xe0, 0xa0, 0x00, 0xd0, 0x05, 0x00, 0x00];
+    assert!(load(&rom, true));
+
+    let mut state = vec![0u8; retro_serialize_size()];
+    assert!(unsafe { retro_serialize(state.as_mut_ptr() as *mut c_void, state.len()) });
+
+    run();
+    assert_eq!(retro_get_memory_size(RETRO_MEMORY_SYSTEM_RAM), 0);
+
+    retro_reset();
+    assert_eq!(retro_get_memory_size(RETRO_MEMORY_SYSTEM_RAM), 4096);
+
+    run();
+    assert!(unsafe { retro_unserialize(state.as_ptr() as *const c_void, state.len()) });
+    assert_eq!(retro_get_memory_size(RETRO_MEMORY_SYSTEM_RAM), 4096);
+
+    retro_unload_game();
+}
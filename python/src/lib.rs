@@ -0,0 +1,355 @@
+// The emulator core as a Python extension module:
+//
+//     import chip8, numpy
+//
+//     cpu = chip8.Cpu(seed=0)
+//     cpu.load_rom(open("pong.ch8", "rb").read())
+//     cpu.run_frame(10)
+//     screen = numpy.asarray(cpu.graphics)  # uint8, shape (32, 64)
+
+// Set off by the code #[pymethods] generates around every PyResult.
+#![allow(clippy::useless_conversion)]
+use chip8_rs::cpu::{
+    self, Registers, GRAPHICS_HEIGHT, GRAPHICS_WIDTH, KEYPAD_SIZE, MEMORY_SIZE, REGISTERS,
+};
+use chip8_rs::quirks::Quirks;
+
+use pyo3::exceptions::{PyBufferError, PyIndexError, PyValueError};
+use pyo3::ffi;
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+
+use std::os::raw::{c_char, c_int, c_void};
+use std::ptr;
+
+const BUFFER_FORMAT: &[u8] = b"B\0";
+
+// A copy of the screen taken when `Cpu.graphics` was read, one byte per
+// pixel: 1 for lit, 0 for dark. It exposes the buffer protocol as a read-only
+// 2D array, so numpy.asarray and memoryview work on it without copying again.
+#[pyclass(frozen, module = "chip8")]
+struct Graphics {
+    pixels: Vec<u8>,
+    shape: [ffi::Py_ssize_t; 2],
+    strides: [ffi::Py_ssize_t; 2],
+}
+
+#[pymethods]
+impl Graphics {
+    unsafe fn __getbuffer__(
+        slf: Bound<'_, Self>,
+        view: *mut ffi::Py_buffer,
+        flags: c_int,
+    ) -> PyResult<()> {
+        if view.is_null() {
+            return Err(PyBufferError::new_err("no view to fill in"));
+        }
+        if flags & ffi::PyBUF_WRITABLE == ffi::PyBUF_WRITABLE {
+            return Err(PyBufferError::new_err("graphics are read-only"));
+        }
+
+        let graphics = slf.get();
+        let view = &mut *view;
+        view.buf = graphics.pixels.as_ptr() as *mut c_void;
+        view.len = graphics.pixels.len() as ffi::Py_ssize_t;
+        view.readonly = 1;
+        view.itemsize = 1;
+        view.format = if flags & ffi::PyBUF_FORMAT == ffi::PyBUF_FORMAT {
+            BUFFER_FORMAT.as_ptr() as *mut c_char
+        } else {
+            ptr::null_mut()
+        };
+        view.ndim = 2;
+        view.shape = graphics.shape.as_ptr() as *mut ffi::Py_ssize_t;
+        view.strides = graphics.strides.as_ptr() as *mut ffi::Py_ssize_t;
+        view.suboffsets = ptr::null_mut();
+        view.internal = ptr::null_mut();
+        // Keeps the pixels alive for as long as the view is.
+        view.obj = slf.into_any().into_ptr();
+
+        Ok(())
+    }
+
+    unsafe fn __releasebuffer__(&self, _view: *mut ffi::Py_buffer) {}
+
+    fn __len__(&self) -> usize {
+        GRAPHICS_HEIGHT
+    }
+
+    #[getter]
+    fn shape(&self) -> (usize, usize) {
+        (GRAPHICS_HEIGHT, GRAPHICS_WIDTH)
+    }
+
+    // The pixel at column `x` of row `y`.
+    fn pixel(&self, x: usize, y: usize) -> PyResult<bool> {
+        if x >= GRAPHICS_WIDTH || y >= GRAPHICS_HEIGHT {
+            return Err(PyIndexError::new_err(format!(
+                "({}, {}) is off the {}x{} screen",
+                x, y, GRAPHICS_WIDTH, GRAPHICS_HEIGHT
+            )));
+        }
+
+        Ok(self.pixels[y * GRAPHICS_WIDTH + x] != 0)
+    }
+}
+
+#[pyclass(module = "chip8")]
+struct Cpu {
+    cpu: cpu::Cpu,
+}
+
+#[pymethods]
+impl Cpu {
+    // `quirks` takes the same list as --quirks, e.g. "vip" or "shift-vy,clip".
+    #[new]
+    #[pyo3(signature = (seed = None, quirks = "none"))]
+    fn new(seed: Option<u64>, quirks: &str) -> PyResult<Cpu> {
+        let mut cpu = cpu::Cpu::new();
+        cpu.set_quirks(Quirks::parse(quirks).map_err(PyValueError::new_err)?);
+        if let Some(seed) = seed {
+            cpu.seed(seed);
+        }
+
+        Ok(Cpu { cpu })
+    }
+
+    // Copies the ROM into memory and starts it from the beginning.
+    fn load_rom(&mut self, rom: &[u8]) -> PyResult<()> {
+        self.cpu
+            .load_rom(rom)
+            .map_err(|e| PyValueError::new_err(e.to_string()))
+    }
+
+    fn set_quirks(&mut self, quirks: &str) -> PyResult<()> {
+        self.cpu
+            .set_quirks(Quirks::parse(quirks).map_err(PyValueError::new_err)?);
+        Ok(())
+    }
+
+    fn seed(&mut self, seed: u64) {
+        self.cpu.seed(seed);
+    }
+
+    // Runs `count` instructions without touching the timers. The vip-timing
+    // quirk only applies to run_frame, this runs exactly `count`.
+    #[pyo3(signature = (count = 1))]
+    fn step(&mut self, count: u32) {
+        for _ in 0..count {
+            self.cpu.step();
+        }
+    }
+
+    // Counts the timers down, as happens once every 1/60 s.
+    fn tick_timers(&mut self) {
+        self.cpu.tick_timers();
+    }
+
+    // Runs one 60 Hz frame: `speed` instructions, or as many as the COSMAC VIP
+    // ran with the vip-timing quirk, then a timer tick. Returns whether the
+    // screen changed.
+    #[pyo3(signature = (speed = 10))]
+    fn run_frame(&mut self, speed: u8) -> bool {
+        self.cpu.run_frame(speed, |_| {});
+        self.cpu.tick_timers();
+        self.cpu.take_draw_flag()
+    }
+
+    // Keys 0x0 to 0xF stay down until they are set up again.
+    fn set_key(&mut self, key: u8, down: bool) -> PyResult<()> {
+        if key as usize >= KEYPAD_SIZE {
+            return Err(PyValueError::new_err(format!(
+                "key {:#x} is not on the keypad",
+                key
+            )));
+        }
+
+        self.cpu.set_key(key, down);
+        Ok(())
+    }
+
+    fn set_keys(&mut self, keys: [bool; KEYPAD_SIZE]) {
+        self.cpu.set_keys(&keys);
+    }
+
+    // Whether the screen changed since the last call.
+    fn take_draw_flag(&mut self) -> bool {
+        self.cpu.take_draw_flag()
+    }
+
+    #[getter]
+    fn graphics(&self) -> Graphics {
+        let width = GRAPHICS_WIDTH as ffi::Py_ssize_t;
+        let height = GRAPHICS_HEIGHT as ffi::Py_ssize_t;
+
+        Graphics {
+            pixels: self
+                .cpu
+                .graphics()
+                .iter()
+                .flatten()
+                .map(|&lit| lit as u8)
+                .collect(),
+            shape: [height, width],
+            strides: [width, 1],
+        }
+    }
+
+    #[getter]
+    fn beeping(&self) -> bool {
+        self.cpu.is_beeping()
+    }
+
+    #[getter]
+    fn done(&self) -> bool {
+        self.cpu.is_done()
+    }
+
+    // All of memory, as a copy.
+    #[getter]
+    fn memory<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new_bound(py, self.cpu.memory())
+    }
+
+    fn read_memory<'py>(
+        &self,
+        py: Python<'py>,
+        address: usize,
+        length: usize,
+    ) -> PyResult<Bound<'py, PyBytes>> {
+        check_range(address, length)?;
+        Ok(PyBytes::new_bound(
+            py,
+            &self.cpu.memory()[address..address + length],
+        ))
+    }
+
+    fn write_memory(&mut self, address: usize, data: &[u8]) -> PyResult<()> {
+        check_range(address, data.len())?;
+        self.cpu.memory_mut()[address..address + data.len()].copy_from_slice(data);
+        Ok(())
+    }
+
+    #[getter]
+    fn v(&self) -> [u8; REGISTERS] {
+        self.cpu.registers().v
+    }
+
+    fn set_v(&mut self, x: usize, value: u8) -> PyResult<()> {
+        self.update_registers(|registers| match registers.v.get_mut(x) {
+            Some(register) => {
+                *register = value;
+                Ok(())
+            }
+            None => Err(PyIndexError::new_err(format!("there is no V{}", x))),
+        })
+    }
+
+    #[getter]
+    fn i(&self) -> u16 {
+        self.cpu.registers().index_register
+    }
+
+    #[setter]
+    fn set_i(&mut self, value: u16) -> PyResult<()> {
+        self.update_registers(|registers| {
+            registers.index_register = value;
+            Ok(())
+        })
+    }
+
+    #[getter]
+    fn pc(&self) -> u16 {
+        self.cpu.registers().program_counter
+    }
+
+    #[setter]
+    fn set_pc(&mut self, value: u16) -> PyResult<()> {
+        // The whole opcode has to be in memory.
+        check_range(value as usize, 2)?;
+        self.update_registers(|registers| {
+            registers.program_counter = value;
+            Ok(())
+        })
+    }
+
+    // How many subroutine calls deep the program is.
+    #[getter]
+    fn sp(&self) -> u16 {
+        self.cpu.registers().stack_pointer
+    }
+
+    #[getter]
+    fn delay_timer(&self) -> u8 {
+        self.cpu.registers().delay_timer
+    }
+
+    #[setter]
+    fn set_delay_timer(&mut self, value: u8) -> PyResult<()> {
+        self.update_registers(|registers| {
+            registers.delay_timer = value;
+            Ok(())
+        })
+    }
+
+    #[getter]
+    fn sound_timer(&self) -> u8 {
+        self.cpu.registers().sound_timer
+    }
+
+    #[setter]
+    fn set_sound_timer(&mut self, value: u8) -> PyResult<()> {
+        self.update_registers(|registers| {
+            registers.sound_timer = value;
+            Ok(())
+        })
+    }
+
+    // Everything needed to come back to this point with restore(), except
+    // for the random number generator and the quirks.
+    fn snapshot<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new_bound(py, &self.cpu.save_state())
+    }
+
+    fn restore(&mut self, snapshot: &[u8]) -> PyResult<()> {
+        self.cpu
+            .load_state(snapshot)
+            .map_err(|e| PyValueError::new_err(e.to_string()))
+    }
+}
+
+impl Cpu {
+    fn update_registers<F>(&mut self, update: F) -> PyResult<()>
+    where
+        F: FnOnce(&mut Registers) -> PyResult<()>,
+    {
+        let mut registers = self.cpu.registers();
+        update(&mut registers)?;
+        self.cpu.set_registers(&registers);
+        Ok(())
+    }
+}
+
+fn check_range(address: usize, length: usize) -> PyResult<()> {
+    if address
+        .checked_add(length)
+        .is_none_or(|end| end > MEMORY_SIZE)
+    {
+        return Err(PyIndexError::new_err(format!(
+            "{} bytes at {:#06x} run past the end of memory",
+            length, address
+        )));
+    }
+
+    Ok(())
+}
+
+#[pymodule]
+fn chip8(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add("WIDTH", GRAPHICS_WIDTH)?;
+    m.add("HEIGHT", GRAPHICS_HEIGHT)?;
+    m.add("MEMORY_SIZE", MEMORY_SIZE)?;
+    m.add_class::<Cpu>()?;
+    m.add_class::<Graphics>()?;
+    Ok(())
+}
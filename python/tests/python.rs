@@ -0,0 +1,34 @@
+// Runs test_chip8.py against the freshly built module with the Python that
+// pyo3 built it for: $PYO3_PYTHON, or python3 from the PATH.
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+// Test executables live in target/<profile>/deps, next to which cargo puts
+// the libraries.
+fn profile_dir() -> PathBuf {
+    let exe = env::current_exe().unwrap();
+    exe.parent().unwrap().parent().unwrap().to_path_buf()
+}
+
+#[test]
+fn python_tests_pass() {
+    let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let module_dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("python");
+    fs::create_dir_all(&module_dir).unwrap();
+    fs::copy(
+        profile_dir().join("libchip8.so"),
+        module_dir.join("chip8.so"),
+    )
+    .unwrap();
+
+    let python = env::var("PYO3_PYTHON").unwrap_or_else(|_| String::from("python3"));
+    let status = Command::new(python)
+        .arg(manifest_dir.join("tests").join("test_chip8.py"))
+        .env("PYTHONPATH", &module_dir)
+        .status()
+        .expect("Python is needed to test the module");
+
+    assert!(status.success());
+}
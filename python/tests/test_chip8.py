@@ -0,0 +1,129 @@
+import unittest
+
+import chip8
+
+# Clears the screen, draws the font's 0 in the top left corner, starts the
+# sound timer with VA = 10 and loops.
+DRAW_ZERO = bytes.fromhex("00e0 a000 d005 6a0a fa18 120a")
+
+# Waits for a key and draws its hex digit in the top left corner.
+DRAW_KEY = bytes.fromhex("f00a f029 d115 1206")
+
+
+def loaded(rom):
+    cpu = chip8.Cpu(seed=0)
+    cpu.load_rom(rom)
+    return cpu
+
+
+class GraphicsTest(unittest.TestCase):
+    def test_is_a_2d_byte_buffer(self):
+        cpu = loaded(DRAW_ZERO)
+        cpu.run_frame()
+
+        view = memoryview(cpu.graphics)
+        self.assertEqual(view.shape, (chip8.HEIGHT, chip8.WIDTH))
+        self.assertEqual(view.format, "B")
+        self.assertTrue(view.readonly)
+        self.assertEqual(view.tolist()[0][:5], [1, 1, 1, 1, 0])
+        self.assertEqual(view.tolist()[1][:5], [1, 0, 0, 1, 0])
+
+    def test_works_with_numpy(self):
+        try:
+            import numpy
+        except ImportError:
+            self.skipTest("numpy is not installed")
+
+        cpu = loaded(DRAW_ZERO)
+        cpu.run_frame()
+
+        screen = numpy.asarray(cpu.graphics)
+        self.assertEqual(screen.shape, (32, 64))
+        self.assertEqual(screen.dtype, numpy.uint8)
+        self.assertEqual(screen.sum(), 14)
+
+    def test_pixel(self):
+        cpu = loaded(DRAW_ZERO)
+        cpu.run_frame()
+
+        self.assertTrue(cpu.graphics.pixel(0, 0))
+        self.assertFalse(cpu.graphics.pixel(4, 0))
+        with self.assertRaises(IndexError):
+            cpu.graphics.pixel(64, 0)
+
+
+class CpuTest(unittest.TestCase):
+    def test_step_and_timers(self):
+        cpu = loaded(DRAW_ZERO)
+        cpu.step(5)
+
+        self.assertEqual(cpu.pc, 0x20A)
+        self.assertEqual(cpu.v[0xA], 10)
+        self.assertEqual(cpu.sound_timer, 10)
+        self.assertTrue(cpu.beeping)
+
+        cpu.tick_timers()
+        self.assertEqual(cpu.sound_timer, 9)
+
+    def test_keys(self):
+        cpu = loaded(DRAW_KEY)
+        cpu.run_frame()
+        cpu.set_key(0x8, True)
+        cpu.run_frame()
+
+        self.assertEqual(cpu.v[0], 0x8)
+        with self.assertRaises(ValueError):
+            cpu.set_key(0x10, True)
+
+    def test_memory(self):
+        cpu = loaded(DRAW_ZERO)
+
+        self.assertEqual(cpu.read_memory(0x200, 2), b"\x00\xe0")
+        self.assertEqual(len(cpu.memory), chip8.MEMORY_SIZE)
+
+        # Draw the font's 1 instead.
+        cpu.write_memory(0x202, b"\xa0\x05")
+        cpu.run_frame()
+        self.assertEqual(memoryview(cpu.graphics).tolist()[0][:4], [0, 0, 1, 0])
+
+        with self.assertRaises(IndexError):
+            cpu.write_memory(0xFFF, b"\x00\x00")
+
+    def test_registers(self):
+        cpu = loaded(DRAW_ZERO)
+        cpu.set_v(3, 0x42)
+        cpu.i = 0x300
+        cpu.delay_timer = 5
+        cpu.pc = 0x20A
+
+        self.assertEqual(cpu.v[3], 0x42)
+        self.assertEqual(cpu.i, 0x300)
+        self.assertEqual(cpu.delay_timer, 5)
+        self.assertEqual(cpu.pc, 0x20A)
+        self.assertEqual(cpu.sp, 0)
+
+        with self.assertRaises(IndexError):
+            cpu.set_v(16, 0)
+        with self.assertRaises(IndexError):
+            cpu.pc = 0xFFF
+
+    def test_snapshot_and_restore(self):
+        cpu = loaded(DRAW_ZERO)
+        cpu.step(3)
+        snapshot = cpu.snapshot()
+
+        cpu.run_frame()
+        cpu.restore(snapshot)
+
+        self.assertEqual(cpu.pc, 0x206)
+        self.assertEqual(cpu.sound_timer, 0)
+        with self.assertRaises(ValueError):
+            cpu.restore(b"not a snapshot")
+
+    def test_rejects_unknown_quirks(self):
+        with self.assertRaises(ValueError):
+            chip8.Cpu(quirks="sideways")
+
+
+if __name__ == "__main__":
+    unittest.main()
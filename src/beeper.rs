@@ -0,0 +1,32 @@
+use chip8_rs::host::Audio;
+
+use rodio::source::SineWave;
+use rodio::Sink;
+
+const TONE_FREQUENCY: u32 = 440;
+
+// A 440 Hz tone on the default output device.
+pub struct Beeper {
+    sink: Sink,
+}
+
+impl Beeper {
+    pub fn new() -> Option<Beeper> {
+        let device = rodio::default_output_device()?;
+        let sink = Sink::new(&device);
+        sink.pause();
+        sink.append(SineWave::new(TONE_FREQUENCY));
+
+        Some(Beeper { sink })
+    }
+}
+
+impl Audio for Beeper {
+    fn set_beeping(&mut self, beeping: bool) {
+        if beeping {
+            self.sink.play();
+        } else {
+            self.sink.pause();
+        }
+    }
+}
@@ -0,0 +1,359 @@
+// Cheats: values written into memory or the V registers every frame, and a
+// memory search for finding where a game keeps its lives or score.
+//
+// A cheat code is `TARGET=VALUE`, optionally followed by `?TARGET=VALUE` to
+// apply it only while the condition holds. Targets are a memory address or a
+// register, all in hex: `2F3=09`, `VE=63`, `2F3=09?300=01`.
+use crate::cpu::{Cpu, MEMORY_SIZE, REGISTERS};
+
+use std::fmt;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Target {
+    Memory(u16),
+    Register(u8),
+}
+
+impl Target {
+    pub fn parse(s: &str) -> Result<Target, String> {
+        let invalid = || format!("'{}' is neither an address nor a register", s);
+
+        if let Some(x) = s.strip_prefix('V').or_else(|| s.strip_prefix('v')) {
+            let x = u8::from_str_radix(x, 16).map_err(|_| invalid())?;
+            if x as usize >= REGISTERS {
+                return Err(invalid());
+            }
+            return Ok(Target::Register(x));
+        }
+
+        let address = u16::from_str_radix(s.trim_start_matches("0x"), 16).map_err(|_| invalid())?;
+        if address as usize >= MEMORY_SIZE {
+            return Err(format!("address {} is past the end of memory", s));
+        }
+        Ok(Target::Memory(address))
+    }
+
+    pub fn read(self, cpu: &Cpu) -> u8 {
+        match self {
+            Target::Memory(address) => cpu.memory()[address as usize],
+            Target::Register(x) => cpu.registers().v[x as usize],
+        }
+    }
+
+    pub fn write(self, cpu: &mut Cpu, value: u8) {
+        match self {
+            Target::Memory(address) => cpu.memory_mut()[address as usize] = value,
+            Target::Register(x) => {
+                let mut registers = cpu.registers();
+                registers.v[x as usize] = value;
+                cpu.set_registers(&registers);
+            }
+        }
+    }
+
+    // Every address, then every register.
+    fn all() -> impl Iterator<Item = Target> {
+        (0..MEMORY_SIZE as u16)
+            .map(Target::Memory)
+            .chain((0..REGISTERS as u8).map(Target::Register))
+    }
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Target::Memory(address) => write!(f, "{:03X}", address),
+            Target::Register(x) => write!(f, "V{:X}", x),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Cheat {
+    pub target: Target,
+    pub value: u8,
+    pub condition: Option<(Target, u8)>,
+    pub enabled: bool,
+    pub description: String,
+}
+
+impl Cheat {
+    // Parses a code on its own; the cheat starts enabled, without a
+    // description.
+    pub fn parse(code: &str) -> Result<Cheat, String> {
+        let (assignment, condition) = match code.find('?') {
+            Some(i) => (&code[..i], Some(&code[i + 1..])),
+            None => (code, None),
+        };
+
+        let (target, value) = parse_assignment(assignment)?;
+        let condition = condition.map(parse_assignment).transpose()?;
+
+        Ok(Cheat {
+            target,
+            value,
+            condition,
+            enabled: true,
+            description: String::new(),
+        })
+    }
+
+    pub fn code(&self) -> String {
+        match self.condition {
+            Some((target, value)) => {
+                format!(
+                    "{}={:02X}?{}={:02X}",
+                    self.target, self.value, target, value
+                )
+            }
+            None => format!("{}={:02X}", self.target, self.value),
+        }
+    }
+
+    pub fn apply(&self, cpu: &mut Cpu) {
+        if !self.enabled {
+            return;
+        }
+
+        if let Some((target, value)) = self.condition {
+            if target.read(cpu) != value {
+                return;
+            }
+        }
+
+        self.target.write(cpu, self.value);
+    }
+}
+
+fn parse_assignment(s: &str) -> Result<(Target, u8), String> {
+    let (target, value) = s
+        .split_once('=')
+        .ok_or_else(|| format!("'{}' should look like TARGET=VALUE", s))?;
+    let value = u8::from_str_radix(value.trim_start_matches("0x"), 16)
+        .map_err(|_| format!("'{}' is not a hex byte", value))?;
+
+    Ok((Target::parse(target)?, value))
+}
+
+// The cheats for one ROM. Enabled ones are applied every frame, which keeps
+// their values frozen.
+#[derive(Clone, Default, PartialEq, Debug)]
+pub struct CheatList {
+    pub cheats: Vec<Cheat>,
+}
+
+impl CheatList {
+    pub fn apply(&self, cpu: &mut Cpu) {
+        for cheat in &self.cheats {
+            cheat.apply(cpu);
+        }
+    }
+
+    // One cheat per line: `on` or `off`, the code, and a description.
+    // Lines starting with `#` are comments.
+    pub fn parse(text: &str) -> Result<CheatList, String> {
+        let mut cheats = Vec::new();
+
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (state, rest) = split_word(line);
+            let enabled = match state {
+                "on" => true,
+                "off" => false,
+                _ => return Err(format!("line {}: expected 'on' or 'off'", number + 1)),
+            };
+            let (code, description) = split_word(rest);
+            let mut cheat =
+                Cheat::parse(code).map_err(|e| format!("line {}: {}", number + 1, e))?;
+            cheat.enabled = enabled;
+            cheat.description = description.to_string();
+
+            cheats.push(cheat);
+        }
+
+        Ok(CheatList { cheats })
+    }
+
+    pub fn format(&self) -> String {
+        self.cheats
+            .iter()
+            .map(|cheat| {
+                let state = if cheat.enabled { "on" } else { "off" };
+                format!("{:<3} {} {}\n", state, cheat.code(), cheat.description)
+                    .replace(" \n", "\n")
+            })
+            .collect()
+    }
+}
+
+// The first word of a line and the rest of it, both trimmed.
+fn split_word(line: &str) -> (&str, &str) {
+    match line.split_once(char::is_whitespace) {
+        Some((word, rest)) => (word, rest.trim()),
+        None => (line, ""),
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Comparison {
+    Equal(u8),
+    Changed,
+    Unchanged,
+    Increased,
+    Decreased,
+}
+
+impl Comparison {
+    fn matches(self, before: u8, now: u8) -> bool {
+        match self {
+            Comparison::Equal(value) => now == value,
+            Comparison::Changed => now != before,
+            Comparison::Unchanged => now == before,
+            Comparison::Increased => now > before,
+            Comparison::Decreased => now < before,
+        }
+    }
+}
+
+// Narrows memory and the registers down to the places that behave like the
+// value being looked for: start a search, play until the lives go down,
+// filter by Decreased, and so on until few candidates are left. Every filter
+// compares against the values at the previous one.
+pub struct Search {
+    candidates: Vec<(Target, u8)>,
+}
+
+impl Search {
+    pub fn new(cpu: &Cpu) -> Search {
+        Search {
+            candidates: Target::all()
+                .map(|target| (target, target.read(cpu)))
+                .collect(),
+        }
+    }
+
+    pub fn filter(&mut self, cpu: &Cpu, comparison: Comparison) {
+        self.candidates = self
+            .candidates
+            .iter()
+            .filter_map(|&(target, before)| {
+                let now = target.read(cpu);
+                if comparison.matches(before, now) {
+                    Some((target, now))
+                } else {
+                    None
+                }
+            })
+            .collect();
+    }
+
+    // The places still in the running, with their values at the last filter.
+    pub fn candidates(&self) -> &[(Target, u8)] {
+        &self.candidates
+    }
+}
+
+// CRC-32 as used by zip and most ROM databases, to tell ROMs apart by
+// contents rather than by file name.
+pub fn rom_crc32(rom: &[u8]) -> u32 {
+    let mut crc = !0u32;
+
+    for &byte in rom {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xedb8_8320 & mask);
+        }
+    }
+
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn targets_are_hex_addresses_or_registers() {
+        assert_eq!(Target::parse("2F3"), Ok(Target::Memory(0x2f3)));
+        assert_eq!(Target::parse("0xfff"), Ok(Target::Memory(0xfff)));
+        assert_eq!(Target::parse("VE"), Ok(Target::Register(0xe)));
+        assert_eq!(Target::parse("v0"), Ok(Target::Register(0)));
+
+        for invalid in ["", "V", "V10", "VG", "12G", "1000", "-1"] {
+            assert!(Target::parse(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn codes_take_an_optional_condition() {
+        let cheat = Cheat::parse("2F3=09?vf=0x01").unwrap();
+        assert_eq!(cheat.target, Target::Memory(0x2f3));
+        assert_eq!(cheat.value, 0x09);
+        assert_eq!(cheat.condition, Some((Target::Register(0xf), 0x01)));
+        assert!(cheat.enabled);
+        assert_eq!(cheat.code(), "2F3=09?VF=01");
+
+        assert_eq!(Cheat::parse("VE=63").unwrap().condition, None);
+
+        for invalid in ["", "2F3", "2F3=", "2F3=100", "2F3=09?", "2F3=09?VE", "é=01"] {
+            assert!(Cheat::parse(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn cheat_lists_survive_a_round_trip() {
+        let text = "# Brix\non  2F3=09 Infinite lives\noff VE=63?300=01\n\n";
+        let list = CheatList::parse(text).unwrap();
+
+        assert_eq!(list.cheats.len(), 2);
+        assert_eq!(list.cheats[0].description, "Infinite lives");
+        assert!(!list.cheats[1].enabled);
+        assert_eq!(
+            list.format(),
+            "on  2F3=09 Infinite lives\noff VE=63?300=01\n"
+        );
+        assert_eq!(CheatList::parse(&list.format()), Ok(list));
+
+        assert_eq!(
+            CheatList::parse("on 2F3=09\nmaybe 2F3=09"),
+            Err("line 2: expected 'on' or 'off'".to_string())
+        );
+        assert!(CheatList::parse("off 2F3")
+            .unwrap_err()
+            .starts_with("line 1: "));
+    }
+
+    #[test]
+    fn searches_keep_the_places_that_match() {
+        let mut cpu = Cpu::new();
+        let mut search = Search::new(&cpu);
+        assert_eq!(search.candidates().len(), MEMORY_SIZE + REGISTERS);
+
+        cpu.memory_mut()[0x300] = 3;
+        Target::Register(0xe).write(&mut cpu, 5);
+        search.filter(&cpu, Comparison::Increased);
+        assert_eq!(
+            search.candidates(),
+            [(Target::Memory(0x300), 3), (Target::Register(0xe), 5)]
+        );
+
+        // Compared with the previous filter, not the start of the search.
+        cpu.memory_mut()[0x300] = 2;
+        search.filter(&cpu, Comparison::Decreased);
+        assert_eq!(search.candidates(), [(Target::Memory(0x300), 2)]);
+
+        search.filter(&cpu, Comparison::Equal(7));
+        assert!(search.candidates().is_empty());
+    }
+
+    #[test]
+    fn crc32_matches_the_standard_check_value() {
+        assert_eq!(rom_crc32(b""), 0);
+        assert_eq!(rom_crc32(b"123456789"), 0xcbf4_3926);
+    }
+}
@@ -0,0 +1,235 @@
+use crate::config::Config;
+use chip8_rs::cheat::{rom_crc32, Cheat, CheatList, Comparison, Search};
+use chip8_rs::cpu::Cpu;
+
+use std::fs;
+use std::io::{self, BufRead};
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+const CHEAT_DIRECTORY: &str = "cheats";
+
+// More candidates than this aren't worth reading through yet.
+const MAX_LISTED_CANDIDATES: usize = 20;
+
+pub const COMMANDS: &str = "Cheat console commands:
+  search                  start searching memory and the registers
+  equal <HEX>             keep what now holds HEX
+  changed, unchanged      keep what changed, or didn't, since the last search
+  increased, decreased    keep what went up, or down, since the last search
+  candidates              list what the search has left
+  add <CODE> [NAME]       add a cheat, e.g. add 2F3=09 infinite lives
+  on <N>, off <N>         enable or disable cheat N
+  remove <N>              remove cheat N
+  list                    list the cheats
+  save                    save the cheats for this ROM
+  help                    print this message";
+
+// The cheats for the ROM being played, kept in the config directory under
+// the CRC-32 of the ROM so that renaming it doesn't lose them.
+pub struct Cheats {
+    list: CheatList,
+    path: Option<PathBuf>,
+    search: Option<Search>,
+}
+
+impl Cheats {
+    // Loads the saved cheats for the ROM, then adds the extra ones.
+    pub fn load(filename: &str, extra: &[Cheat]) -> Result<Cheats, String> {
+        let rom = fs::read(filename).map_err(|e| format!("can't read {}: {}", filename, e))?;
+        let path = Config::directory().map(|directory| {
+            directory
+                .join(CHEAT_DIRECTORY)
+                .join(format!("{:08X}.txt", rom_crc32(&rom)))
+        });
+
+        let mut list = match &path {
+            Some(path) if path.exists() => fs::read_to_string(path)
+                .map_err(|e| e.to_string())
+                .and_then(|text| CheatList::parse(&text))
+                .map_err(|e| format!("{}: {}", path.display(), e))?,
+            _ => CheatList::default(),
+        };
+        list.cheats.extend_from_slice(extra);
+
+        Ok(Cheats {
+            list,
+            path,
+            search: None,
+        })
+    }
+
+    // Just these cheats, leaving the saved ones out.
+    pub fn only(cheats: &[Cheat]) -> Cheats {
+        Cheats {
+            list: CheatList {
+                cheats: cheats.to_vec(),
+            },
+            path: None,
+            search: None,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.list.cheats.is_empty()
+    }
+
+    // Called before every frame, keeping frozen values where they are.
+    pub fn apply(&self, cpu: &mut Cpu) {
+        self.list.apply(cpu);
+    }
+
+    // Runs a line typed into the cheat console, returning what to print.
+    pub fn run_command(&mut self, line: &str, cpu: &Cpu) -> Result<String, String> {
+        let line = line.trim();
+        let (command, argument) = match line.split_once(char::is_whitespace) {
+            Some((command, argument)) => (command, argument.trim()),
+            None => (line, ""),
+        };
+
+        let comparison = match command {
+            "equal" => {
+                let value = u8::from_str_radix(argument.trim_start_matches("0x"), 16)
+                    .map_err(|_| format!("equal expects a hex byte, got '{}'", argument))?;
+                Some(Comparison::Equal(value))
+            }
+            "changed" => Some(Comparison::Changed),
+            "unchanged" => Some(Comparison::Unchanged),
+            "increased" => Some(Comparison::Increased),
+            "decreased" => Some(Comparison::Decreased),
+            _ => None,
+        };
+
+        if let Some(comparison) = comparison {
+            let search = self
+                .search
+                .as_mut()
+                .ok_or_else(|| String::from("start a search first"))?;
+            search.filter(cpu, comparison);
+            return Ok(self.candidates());
+        }
+
+        match command {
+            "" => Ok(String::new()),
+            "help" => Ok(String::from(COMMANDS)),
+            "search" => {
+                let search = Search::new(cpu);
+                let count = search.candidates().len();
+                self.search = Some(search);
+                Ok(format!("Searching {} places", count))
+            }
+            "candidates" => Ok(self.candidates()),
+            "add" => {
+                let (code, description) = match argument.split_once(char::is_whitespace) {
+                    Some((code, description)) => (code, description.trim()),
+                    None => (argument, ""),
+                };
+                let mut cheat = Cheat::parse(code)?;
+                cheat.description = description.to_string();
+                self.list.cheats.push(cheat);
+                Ok(self.list())
+            }
+            "on" | "off" => {
+                let i = self.index(argument)?;
+                self.list.cheats[i].enabled = command == "on";
+                Ok(self.list())
+            }
+            "remove" => {
+                let i = self.index(argument)?;
+                self.list.cheats.remove(i);
+                Ok(self.list())
+            }
+            "list" => Ok(self.list()),
+            "save" => self.save(),
+            _ => Err(format!("unknown command '{}', try help", command)),
+        }
+    }
+
+    fn candidates(&self) -> String {
+        let candidates = match &self.search {
+            Some(search) => search.candidates(),
+            None => return String::from("No search running"),
+        };
+
+        let mut text = format!("{} candidates", candidates.len());
+        if candidates.len() <= MAX_LISTED_CANDIDATES {
+            for (target, value) in candidates {
+                text.push_str(&format!("\n  {} = {:02X}", target, value));
+            }
+        }
+        text
+    }
+
+    fn list(&self) -> String {
+        if self.is_empty() {
+            return String::from("No cheats");
+        }
+
+        self.list
+            .format()
+            .lines()
+            .enumerate()
+            .map(|(i, line)| format!("{:>2}. {}", i + 1, line))
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    // Cheats are numbered from 1, as list shows them.
+    fn index(&self, argument: &str) -> Result<usize, String> {
+        argument
+            .parse::<usize>()
+            .ok()
+            .filter(|&n| n >= 1 && n <= self.list.cheats.len())
+            .map(|n| n - 1)
+            .ok_or_else(|| format!("no cheat number '{}'", argument))
+    }
+
+    fn save(&self) -> Result<String, String> {
+        let path = self
+            .path
+            .as_ref()
+            .ok_or_else(|| String::from("no config directory to save cheats in"))?;
+
+        path.parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| fs::write(path, self.list.format()))
+            .map_err(|e| format!("can't save {}: {}", path.display(), e))?;
+
+        Ok(format!("Saved to {}", path.display()))
+    }
+}
+
+// Lines typed on standard input while the window runs, read on a thread of
+// their own so that the emulator never waits for them.
+pub struct Console {
+    lines: Receiver<String>,
+}
+
+impl Console {
+    pub fn open() -> Console {
+        let (sender, lines) = mpsc::channel();
+
+        thread::spawn(move || {
+            for line in io::stdin().lock().lines().map_while(Result::ok) {
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        println!("{}", COMMANDS);
+        Console { lines }
+    }
+
+    // Runs whatever was typed since the last frame.
+    pub fn poll(&self, cheats: &mut Cheats, cpu: &Cpu) {
+        while let Ok(line) = self.lines.try_recv() {
+            match cheats.run_command(&line, cpu) {
+                Ok(output) if output.is_empty() => {}
+                Ok(output) => println!("{}", output),
+                Err(e) => eprintln!("error: {}", e),
+            }
+        }
+    }
+}
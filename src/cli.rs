@@ -0,0 +1,414 @@
+use crate::keymap::Keymap;
+use chip8_rs::cheat::Cheat;
+use chip8_rs::cpu::{DEFAULT_LOAD_ADDRESS, MEMORY_SIZE};
+use chip8_rs::filter::Filter;
+use chip8_rs::phosphor::Persistence;
+use chip8_rs::quirks::Quirks;
+use std::convert::TryFrom;
+use std::path::PathBuf;
+
+pub const USAGE: &str = "Usage: chip8-rs [OPTIONS] [ROM or DIRECTORY]
+
+Runs a CHIP-8 ROM. Without a ROM, or with a directory, opens the ROM launcher
+on that directory (roms/ by default).
+
+Options:
+  --info                 print the ROM's title, author, year and instructions
+  --config <PATH>        read settings from PATH instead of
+                         $XDG_CONFIG_HOME/chip8-rs/config.ini
+  --speed, --ipf <N>     instructions per frame, 1 to 255 (default 10)
+  --quirks <LIST>        comma separated quirks or presets: none, vip, schip,
+                         shift-vy, memory-increment, jump-vx, vf-reset, clip,
+                         key-release, vip-timing (run as fast as the COSMAC
+                         VIP did, whatever the speed)
+  --scale <N>            window size as a multiple of 64x32, 1 to 30
+  --palette <NAME>       color palette: lcd, mono, green, amber, gameboy,
+                         high-contrast, xo-chip, xo-chip-16, one from the
+                         config or a list of colors like 000000,ffffff
+  --flicker <MODE>       flicker reduction: off, or, max[:FRAMES] or
+                         decay[:KEEP]. or keeps pixels lit for a frame after
+                         they go off, max for FRAMES frames (3 by default),
+                         and decay fades them out, keeping KEEP of their
+                         brightness every frame (0.5 by default)
+  --filter <LIST>        picture filters: none, crt, scanlines, grid, bloom,
+                         curvature and smooth, each with an optional
+                         strength from 0 to 1, e.g. scanlines:0.3,bloom
+  --layout <NAME>        keyboard layout for the hex keypad: qwerty, azerty,
+                         dvorak, numpad
+  --terminal             draw in the terminal instead of a window
+  --fullscreen           borderless window filling as much of the screen as fits
+  --mute                 run without sound
+  --no-gamepad           ignore gamepads and joysticks
+  --script <PATH>        run the Rhai script at PATH alongside the ROM
+  --cheat <CODE>         apply a cheat every frame, e.g. 2F3=09 or
+                         VE=63?2F3=00; may be given more than once
+  --cheat-console        search memory and manage cheats by typing commands
+                         into the console while the window runs
+  --seed <N>             seed the random number generator for repeatable runs
+  --debug                print every instruction as it runs
+  --no-dump              don't print the program listing on start
+  --load-address <ADDR>  where to load the ROM, e.g. 0x200 (default)
+  --record <PATH>        record the screen to an animated GIF or APNG,
+                         by PATH's extension: .gif, .png or .apng
+  --record-input <PATH>  save the keys pressed in every frame to a movie
+  --headless             run without a window or sound
+  --movie <PATH>         with --headless, play back a movie's keys
+  --video <PATH>         with --headless, export every frame to a Y4M video
+  --audio <PATH>         with --headless, export the sound to a WAV file
+  --screenshot <PATH>    with --headless, save the last frame as .png, .pbm
+                         or .txt, scaled up by --scale
+  --frames <N>           with --headless, stop after N frames
+  --help                 print this message";
+
+const MIN_SCALE: usize = 1;
+const MAX_SCALE: usize = 30;
+
+// Everything below the load address has to fit the font.
+const MIN_LOAD_ADDRESS: u16 = 0x50;
+
+pub enum Command {
+    Help,
+    Info(String),
+    Run(Box<Options>),
+}
+
+pub struct Options {
+    pub target: Option<String>,
+    pub config: Option<PathBuf>,
+    pub speed: Option<u8>,
+    pub quirks: Option<Quirks>,
+    pub scale: Option<usize>,
+    // A palette's name or its colors, looked up once the config is read.
+    pub palette: Option<String>,
+    pub flicker: Option<Persistence>,
+    pub filter: Option<Filter>,
+    pub layout: Option<Keymap>,
+    pub terminal: bool,
+    pub fullscreen: bool,
+    pub mute: bool,
+    pub gamepad: bool,
+    pub script: Option<PathBuf>,
+    pub cheats: Vec<Cheat>,
+    pub cheat_console: bool,
+    pub seed: Option<u64>,
+    pub debug: bool,
+    pub dump: bool,
+    pub load_address: u16,
+    pub record: Option<PathBuf>,
+    pub record_input: Option<PathBuf>,
+    pub headless: bool,
+    pub movie: Option<PathBuf>,
+    pub video: Option<PathBuf>,
+    pub audio: Option<PathBuf>,
+    pub screenshot: Option<PathBuf>,
+    pub frames: Option<u32>,
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options {
+            target: None,
+            config: None,
+            speed: None,
+            quirks: None,
+            scale: None,
+            palette: None,
+            flicker: None,
+            filter: None,
+            layout: None,
+            terminal: false,
+            fullscreen: false,
+            mute: false,
+            gamepad: true,
+            script: None,
+            cheats: Vec::new(),
+            cheat_console: false,
+            seed: None,
+            debug: false,
+            dump: true,
+            load_address: DEFAULT_LOAD_ADDRESS,
+            record: None,
+            record_input: None,
+            headless: false,
+            movie: None,
+            video: None,
+            audio: None,
+            screenshot: None,
+            frames: None,
+        }
+    }
+}
+
+pub fn parse<I: Iterator<Item = String>>(mut args: I) -> Result<Command, String> {
+    let mut options = Options::default();
+    let mut info = false;
+
+    while let Some(arg) = args.next() {
+        // Accept both `--speed 12` and `--speed=12`.
+        let (flag, inline) = match arg.find('=') {
+            Some(i) if arg.starts_with("--") => {
+                (arg[..i].to_string(), Some(arg[i + 1..].to_string()))
+            }
+            _ => (arg.clone(), None),
+        };
+
+        let mut value = || {
+            inline
+                .clone()
+                .or_else(|| args.next())
+                .ok_or_else(|| format!("{} needs a value", flag))
+        };
+
+        match flag.as_str() {
+            "--help" | "-h" => return Ok(Command::Help),
+            "--info" => info = true,
+            "--speed" | "--ipf" => {
+                options.speed = Some(parse_ranged(&flag, &value()?, 1, u8::MAX)?);
+            }
+            "--config" => options.config = Some(PathBuf::from(value()?)),
+            "--quirks" => options.quirks = Some(Quirks::parse(&value()?)?),
+            "--scale" => {
+                options.scale = Some(parse_ranged(&flag, &value()?, MIN_SCALE, MAX_SCALE)?);
+            }
+            "--palette" => options.palette = Some(value()?),
+            "--flicker" => options.flicker = Some(Persistence::parse(&value()?)?),
+            "--filter" => options.filter = Some(Filter::parse(&value()?)?),
+            "--layout" => {
+                let name = value()?;
+                let layout = Keymap::layout(&name).ok_or_else(|| {
+                    format!(
+                        "unknown layout '{}', expected one of: {}",
+                        name,
+                        Keymap::layout_names().join(", ")
+                    )
+                })?;
+                options.layout = Some(layout);
+            }
+            "--terminal" => options.terminal = true,
+            "--fullscreen" => options.fullscreen = true,
+            "--mute" => options.mute = true,
+            "--no-gamepad" => options.gamepad = false,
+            "--script" => options.script = Some(PathBuf::from(value()?)),
+            "--cheat" => options.cheats.push(Cheat::parse(&value()?)?),
+            "--cheat-console" => options.cheat_console = true,
+            "--seed" => options.seed = Some(parse_number(&flag, &value()?)?),
+            "--debug" => options.debug = true,
+            "--no-dump" => options.dump = false,
+            "--load-address" => {
+                let value = value()?;
+                let address: u16 = parse_number(&flag, &value)?;
+
+                if address < MIN_LOAD_ADDRESS || address as usize > MEMORY_SIZE - 2 {
+                    return Err(format!(
+                        "{} must be between {:#05x} and {:#05x}, got {}",
+                        flag,
+                        MIN_LOAD_ADDRESS,
+                        MEMORY_SIZE - 2,
+                        value
+                    ));
+                }
+
+                options.load_address = address;
+            }
+            "--record" => options.record = Some(PathBuf::from(value()?)),
+            "--record-input" => options.record_input = Some(PathBuf::from(value()?)),
+            "--headless" => options.headless = true,
+            "--movie" => options.movie = Some(PathBuf::from(value()?)),
+            "--video" => options.video = Some(PathBuf::from(value()?)),
+            "--audio" => options.audio = Some(PathBuf::from(value()?)),
+            "--screenshot" => options.screenshot = Some(PathBuf::from(value()?)),
+            "--frames" => options.frames = Some(parse_number(&flag, &value()?)?),
+            _ if flag.starts_with('-') => return Err(format!("unknown option {}", flag)),
+            _ if options.target.is_some() => {
+                return Err(format!("unexpected argument {}", arg));
+            }
+            _ => options.target = Some(arg),
+        }
+    }
+
+    if info {
+        return options
+            .target
+            .map(Command::Info)
+            .ok_or_else(|| String::from("--info needs a ROM"));
+    }
+
+    if options.headless && options.target.is_none() {
+        return Err(String::from("--headless needs a ROM"));
+    }
+
+    if options.record.is_some() && options.target.is_none() {
+        return Err(String::from("--record needs a ROM"));
+    }
+
+    if options.terminal && options.target.is_none() {
+        return Err(String::from("--terminal needs a ROM"));
+    }
+
+    if options.terminal && (options.headless || options.fullscreen || options.scale.is_some()) {
+        return Err(String::from(
+            "--terminal can't be used with --headless, --fullscreen or --scale",
+        ));
+    }
+
+    if options.terminal && options.script.is_some() {
+        return Err(String::from("--script can't be used with --terminal"));
+    }
+
+    if options.cheat_console && (options.terminal || options.headless) {
+        return Err(String::from(
+            "--cheat-console can't be used with --terminal or --headless",
+        ));
+    }
+
+    if options.frames.is_some() && !options.headless {
+        return Err(String::from("--frames only works with --headless"));
+    }
+
+    if (options.movie.is_some()
+        || options.video.is_some()
+        || options.audio.is_some()
+        || options.screenshot.is_some())
+        && !options.headless
+    {
+        return Err(String::from(
+            "--movie, --video, --audio and --screenshot only work with --headless",
+        ));
+    }
+
+    if options.record_input.is_some() && (options.headless || options.target.is_none()) {
+        return Err(String::from(
+            "--record-input needs a ROM and can't be used with --headless",
+        ));
+    }
+
+    if options.fullscreen && options.scale.is_some() {
+        return Err(String::from(
+            "--fullscreen and --scale can't be used together",
+        ));
+    }
+
+    Ok(Command::Run(Box::new(options)))
+}
+
+// Numbers may be given in decimal or, with a 0x prefix, in hex.
+fn parse_number<T: TryFrom<u64>>(flag: &str, value: &str) -> Result<T, String> {
+    let parsed = match value.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => value.parse::<u64>(),
+    };
+
+    parsed
+        .ok()
+        .and_then(|n| T::try_from(n).ok())
+        .ok_or_else(|| format!("{} expects a number, got '{}'", flag, value))
+}
+
+fn parse_ranged<T>(flag: &str, value: &str, min: T, max: T) -> Result<T, String>
+where
+    T: TryFrom<u64> + PartialOrd + std::fmt::Display + Copy,
+{
+    // Parsed wide first, so that numbers too big for T are out of range too.
+    let n: u64 = parse_number(flag, value)?;
+
+    match T::try_from(n) {
+        Ok(n) if n >= min && n <= max => Ok(n),
+        _ => Err(format!(
+            "{} must be between {} and {}, got {}",
+            flag, min, max, value
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_args(args: &[&str]) -> Result<Command, String> {
+        parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    fn options(args: &[&str]) -> Options {
+        match parse_args(args) {
+            Ok(Command::Run(options)) => *options,
+            Ok(_) => panic!("{:?} didn't run a ROM", args),
+            Err(e) => panic!("{:?} failed: {}", args, e),
+        }
+    }
+
+    fn error(args: &[&str]) -> String {
+        match parse_args(args) {
+            Err(e) => e,
+            Ok(_) => panic!("{:?} was accepted", args),
+        }
+    }
+
+    #[test]
+    fn values_follow_their_flag_or_an_equals_sign() {
+        let options = options(&["--speed", "12", "--scale=3", "--seed", "0x10", "rom.ch8"]);
+        assert_eq!(options.speed, Some(12));
+        assert_eq!(options.scale, Some(3));
+        assert_eq!(options.seed, Some(16));
+        assert_eq!(options.target.as_deref(), Some("rom.ch8"));
+    }
+
+    #[test]
+    fn flags_without_their_value_are_errors() {
+        assert_eq!(error(&["--speed"]), "--speed needs a value");
+        assert_eq!(error(&["rom.ch8", "--palette"]), "--palette needs a value");
+    }
+
+    #[test]
+    fn unknown_flags_and_extra_arguments_are_errors() {
+        assert_eq!(error(&["--sped", "12"]), "unknown option --sped");
+        assert_eq!(error(&["-x"]), "unknown option -x");
+        assert_eq!(error(&["a.ch8", "b.ch8"]), "unexpected argument b.ch8");
+    }
+
+    #[test]
+    fn numbers_are_checked_against_their_range() {
+        assert_eq!(
+            error(&["--speed", "0"]),
+            "--speed must be between 1 and 255, got 0"
+        );
+        assert_eq!(
+            error(&["--ipf", "256"]),
+            "--ipf must be between 1 and 255, got 256"
+        );
+        assert_eq!(
+            error(&["--speed", "fast"]),
+            "--speed expects a number, got 'fast'"
+        );
+        assert_eq!(
+            error(&["--scale", "31"]),
+            "--scale must be between 1 and 30, got 31"
+        );
+        assert_eq!(
+            error(&["--load-address", "0x10"]),
+            "--load-address must be between 0x050 and 0xffe, got 0x10"
+        );
+        assert_eq!(options(&["--speed", "255"]).speed, Some(255));
+    }
+
+    #[test]
+    fn every_flicker_mode_in_the_usage_parses() {
+        for mode in ["off", "or", "max", "max:4", "decay", "decay:0.7"] {
+            assert!(options(&["--flicker", mode]).flicker.is_some(), "{}", mode);
+        }
+        assert!(error(&["--flicker", "blur"]).starts_with("unknown flicker reduction"));
+    }
+
+    #[test]
+    fn options_that_need_a_rom_or_each_other_say_so() {
+        assert_eq!(error(&["--headless"]), "--headless needs a ROM");
+        assert_eq!(error(&["--info"]), "--info needs a ROM");
+        assert_eq!(
+            error(&["--frames", "10", "rom.ch8"]),
+            "--frames only works with --headless"
+        );
+        assert!(matches!(
+            parse_args(&["--help", "--nope"]),
+            Ok(Command::Help)
+        ));
+    }
+}
@@ -0,0 +1,422 @@
+use crate::keymap::{Binding, Keymap};
+use crate::keys::{key_name, parse_key};
+use crate::{INITIAL_SPEED, MAX_SPEED, MIN_SPEED};
+use chip8_rs::filter::Filter;
+use chip8_rs::host::TimeScale;
+use chip8_rs::palette::{self, parse_color, Palette};
+use chip8_rs::phosphor::Persistence;
+use chip8_rs::quirks::Quirks;
+
+use minifb::Key;
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const CONFIG_DIRECTORY: &str = "chip8-rs";
+const CONFIG_FILE: &str = "config.ini";
+
+const DEFAULT_SLOW_MOTION: f32 = 0.5;
+
+#[derive(Clone, Copy)]
+pub struct Hotkeys {
+    pub help: Key,
+    pub keypad: Key,
+    pub restart: Key,
+    pub reload: Key,
+    pub debug: Key,
+    pub speed_down: Key,
+    pub speed_up: Key,
+    pub record: Key,
+    pub screenshot: Key,
+    pub palette: Key,
+    pub counters: Key,
+    pub menu: Key,
+    pub pause: Key,
+    pub frame_advance: Key,
+    pub fast_forward: Key,
+    pub slow_motion: Key,
+}
+
+impl Default for Hotkeys {
+    fn default() -> Hotkeys {
+        Hotkeys {
+            help: Key::F1,
+            keypad: Key::F3,
+            restart: Key::F2,
+            reload: Key::F5,
+            debug: Key::F4,
+            speed_down: Key::F11,
+            speed_up: Key::F12,
+            record: Key::F6,
+            screenshot: Key::F7,
+            palette: Key::F8,
+            counters: Key::F9,
+            menu: Key::F10,
+            pause: Key::Space,
+            frame_advance: Key::N,
+            fast_forward: Key::Tab,
+            slow_motion: Key::Backquote,
+        }
+    }
+}
+
+impl Hotkeys {
+    // One line per hotkey. The settings menu is left out where there's no
+    // window to draw it in.
+    pub fn describe(&self, menu: bool) -> String {
+        let menu_line = (self.menu, "pause and open the settings menu");
+
+        [
+            (self.help, "toggle this help (UP/DOWN to scroll)"),
+            (self.keypad, "show which keys make up the hex keypad"),
+            (self.restart, "restart emulation"),
+            (self.reload, "reload configuration"),
+            (self.debug, "toggle debug (see console)"),
+            (self.speed_down, "emulation speed down"),
+            (self.speed_up, "emulation speed up"),
+            (self.record, "start or stop recording a GIF"),
+            (self.screenshot, "save a screenshot"),
+            (self.palette, "switch to the next palette"),
+            (self.counters, "show frames and instructions per second"),
+            menu_line,
+            (self.pause, "pause or resume"),
+            (
+                self.frame_advance,
+                "pause, then advance one frame at a time",
+            ),
+            (self.fast_forward, "fast-forward while held"),
+            (self.slow_motion, "slow motion while held"),
+            (Key::Escape, "exit"),
+        ]
+        .iter()
+        .filter(|&&line| menu || line != menu_line)
+        .map(|(key, action)| format!("{} - {}", key_name(*key), action))
+        .collect::<Vec<String>>()
+        .join("\n")
+    }
+}
+
+#[derive(Clone)]
+pub struct Settings {
+    pub speed: u8,
+    pub min_speed: u8,
+    pub max_speed: u8,
+    pub quirks: Quirks,
+    pub palette: Palette,
+    // The presets, then the config's own palettes, to cycle through.
+    pub palettes: Vec<(String, Palette)>,
+    pub flicker: Persistence,
+    pub filter: Filter,
+    // How fast the game runs while the fast-forward and slow motion hotkeys
+    // are held.
+    pub fast_forward: TimeScale,
+    pub slow_motion: TimeScale,
+    pub keymap: Keymap,
+    pub hotkeys: Hotkeys,
+}
+
+impl Default for Settings {
+    fn default() -> Settings {
+        Settings {
+            speed: INITIAL_SPEED,
+            min_speed: MIN_SPEED,
+            max_speed: MAX_SPEED,
+            quirks: Quirks::default(),
+            palette: Palette::default(),
+            palettes: Palette::presets()
+                .map(|(name, palette)| (name.to_string(), palette))
+                .collect(),
+            flicker: Persistence::default(),
+            filter: Filter::default(),
+            fast_forward: TimeScale::Uncapped,
+            slow_motion: TimeScale::Times(DEFAULT_SLOW_MOTION),
+            keymap: Keymap::default(),
+            hotkeys: Hotkeys::default(),
+        }
+    }
+}
+
+impl Settings {
+    // A palette by name, the config's own included, or a list of colors.
+    pub fn find_palette(&self, value: &str) -> Result<Palette, String> {
+        if palette::is_list(value) {
+            return Palette::parse_colors(value);
+        }
+
+        self.palettes
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(value))
+            .map(|(_, palette)| *palette)
+            .ok_or_else(|| {
+                let names: Vec<&str> = self
+                    .palettes
+                    .iter()
+                    .map(|(name, _)| name.as_str())
+                    .collect();
+                format!(
+                    "unknown palette '{}', expected one of: {}, or a list of colors",
+                    value,
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+struct Entry {
+    key: String,
+    value: String,
+    line: usize,
+}
+
+struct Section {
+    name: String,
+    rom: Option<String>,
+    entries: Vec<Entry>,
+}
+
+// An INI file in the style of git config: `[emulator]`, `[keys]` and
+// `[hotkeys]` hold the defaults, and the same sections with a ROM name, e.g.
+// `[keys "Pong (1 player)"]`, override them for that ROM only. `[palettes]`
+// names palettes of the user's own.
+pub struct Config {
+    path: Option<PathBuf>,
+    explicit: bool,
+    sections: Vec<Section>,
+}
+
+impl Config {
+    // Where the config file and everything else chip8-rs keeps between runs
+    // go, $XDG_CONFIG_HOME/chip8-rs.
+    pub fn directory() -> Option<PathBuf> {
+        let base = env::var_os("XDG_CONFIG_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+
+        Some(base.join(CONFIG_DIRECTORY))
+    }
+
+    pub fn default_path() -> Option<PathBuf> {
+        Config::directory().map(|directory| directory.join(CONFIG_FILE))
+    }
+
+    // An explicitly given path has to exist, the default one is optional.
+    pub fn load(path: Option<&Path>) -> Result<Config, String> {
+        let (path, explicit) = match path {
+            Some(path) => (Some(path.to_path_buf()), true),
+            None => (Config::default_path(), false),
+        };
+
+        let text = match &path {
+            Some(p) if explicit || p.exists() => fs::read_to_string(p)
+                .map_err(|e| format!("can't read config {}: {}", p.display(), e))?,
+            _ => String::new(),
+        };
+
+        let config = Config {
+            sections: parse(&text).map_err(|e| describe(&path, e))?,
+            path,
+            explicit,
+        };
+
+        // Catch bad values now rather than when a ROM happens to use them.
+        config.settings(&[])?;
+        for section in &config.sections {
+            if let Some(rom) = &section.rom {
+                config.settings(&[rom])?;
+            }
+        }
+
+        Ok(config)
+    }
+
+    pub fn reload(&self) -> Result<Config, String> {
+        Config::load(self.path.as_deref().filter(|_| self.explicit))
+    }
+
+    // Settings for a ROM known by any of the given names, usually its file
+    // name and its title. With no names, just the defaults.
+    pub fn settings(&self, names: &[&str]) -> Result<Settings, String> {
+        let mut settings = Settings::default();
+
+        let global = self.sections.iter().filter(|s| s.rom.is_none());
+        let rom = self.sections.iter().filter(|s| match &s.rom {
+            Some(rom) => names.iter().any(|n| n.eq_ignore_ascii_case(rom)),
+            None => false,
+        });
+
+        // Palettes go first, so that they can be used wherever they're defined.
+        let (palettes, others): (Vec<&Section>, Vec<&Section>) =
+            global.chain(rom).partition(|s| s.name == "palettes");
+
+        for section in palettes.into_iter().chain(others) {
+            for entry in &section.entries {
+                apply(&mut settings, &section.name, entry)
+                    .map_err(|e| describe(&self.path, (entry.line, e)))?;
+            }
+        }
+
+        if settings.min_speed > settings.max_speed {
+            return Err(describe(
+                &self.path,
+                (0, String::from("min_speed is above max_speed")),
+            ));
+        }
+
+        Ok(settings)
+    }
+}
+
+fn describe(path: &Option<PathBuf>, (line, message): (usize, String)) -> String {
+    let file = path
+        .as_ref()
+        .map(|p| p.display().to_string())
+        .unwrap_or_else(|| String::from("config"));
+
+    if line == 0 {
+        format!("{}: {}", file, message)
+    } else {
+        format!("{}:{}: {}", file, line, message)
+    }
+}
+
+fn parse(text: &str) -> Result<Vec<Section>, (usize, String)> {
+    let mut sections: Vec<Section> = Vec::new();
+
+    for (i, raw) in text.lines().enumerate() {
+        let line_number = i + 1;
+        let line = raw.trim();
+
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+
+        if line.starts_with('[') {
+            let header = line
+                .strip_prefix('[')
+                .and_then(|l| l.strip_suffix(']'))
+                .ok_or((line_number, String::from("unterminated section header")))?;
+
+            let (name, rom) = match header.find('"') {
+                Some(quote) => {
+                    let rom = header[quote..]
+                        .strip_prefix('"')
+                        .and_then(|r| r.strip_suffix('"'))
+                        .ok_or((line_number, String::from("unterminated ROM name")))?;
+                    (header[..quote].trim(), Some(rom.to_string()))
+                }
+                None => (header.trim(), None),
+            };
+
+            if !["emulator", "keys", "hotkeys", "palettes"].contains(&name) {
+                return Err((line_number, format!("unknown section [{}]", name)));
+            }
+
+            sections.push(Section {
+                name: name.to_string(),
+                rom,
+                entries: Vec::new(),
+            });
+            continue;
+        }
+
+        let mut parts = line.splitn(2, '=');
+        let (key, value) = match (parts.next(), parts.next()) {
+            (Some(key), Some(value)) => (key.trim(), value.trim()),
+            _ => return Err((line_number, String::from("expected key = value"))),
+        };
+
+        let section = sections
+            .last_mut()
+            .ok_or((line_number, String::from("setting outside of a section")))?;
+
+        section.entries.push(Entry {
+            key: key.to_lowercase(),
+            value: value.to_string(),
+            line: line_number,
+        });
+    }
+
+    Ok(sections)
+}
+
+fn apply(settings: &mut Settings, section: &str, entry: &Entry) -> Result<(), String> {
+    let value = entry.value.as_str();
+
+    match (section, entry.key.as_str()) {
+        ("emulator", "speed") => settings.speed = parse_speed(value)?,
+        ("emulator", "min_speed") => settings.min_speed = parse_speed(value)?,
+        ("emulator", "max_speed") => settings.max_speed = parse_speed(value)?,
+        ("emulator", "quirks") => settings.quirks = Quirks::parse(value)?,
+        ("emulator", "palette") => settings.palette = settings.find_palette(value)?,
+        ("emulator", "color_on") => settings.palette.set(1, parse_color(value)?),
+        ("emulator", "color_off") => settings.palette.set(0, parse_color(value)?),
+        ("emulator", "flicker") => settings.flicker = Persistence::parse(value)?,
+        ("emulator", "filter") => settings.filter = Filter::parse(value)?,
+        ("emulator", "fast_forward") => settings.fast_forward = TimeScale::parse(value)?,
+        ("emulator", "slow_motion") => settings.slow_motion = TimeScale::parse(value)?,
+        ("keys", "layout") => {
+            settings.keymap = Keymap::layout(value).ok_or_else(|| {
+                format!(
+                    "unknown layout '{}', expected one of: {}",
+                    value,
+                    Keymap::layout_names().join(", ")
+                )
+            })?;
+        }
+        ("keys", key) => {
+            let chip8_key = u8::from_str_radix(key, 16)
+                .ok()
+                .filter(|&k| k < 16 && key.len() == 1)
+                .ok_or_else(|| format!("'{}' is not a CHIP-8 key, expected 0 to F", key))?;
+            let bindings = value
+                .split(',')
+                .map(binding_from)
+                .collect::<Result<Vec<Binding>, String>>()?;
+            settings.keymap.bind(chip8_key, bindings);
+        }
+        ("hotkeys", "help") => settings.hotkeys.help = key_from(value)?,
+        ("hotkeys", "keypad") => settings.hotkeys.keypad = key_from(value)?,
+        ("hotkeys", "restart") => settings.hotkeys.restart = key_from(value)?,
+        ("hotkeys", "reload") => settings.hotkeys.reload = key_from(value)?,
+        ("hotkeys", "debug") => settings.hotkeys.debug = key_from(value)?,
+        ("hotkeys", "speed_down") => settings.hotkeys.speed_down = key_from(value)?,
+        ("hotkeys", "speed_up") => settings.hotkeys.speed_up = key_from(value)?,
+        ("hotkeys", "record") => settings.hotkeys.record = key_from(value)?,
+        ("hotkeys", "screenshot") => settings.hotkeys.screenshot = key_from(value)?,
+        ("hotkeys", "palette") => settings.hotkeys.palette = key_from(value)?,
+        ("hotkeys", "counters") => settings.hotkeys.counters = key_from(value)?,
+        ("hotkeys", "menu") => settings.hotkeys.menu = key_from(value)?,
+        ("hotkeys", "pause") => settings.hotkeys.pause = key_from(value)?,
+        ("hotkeys", "frame_advance") => settings.hotkeys.frame_advance = key_from(value)?,
+        ("hotkeys", "fast_forward") => settings.hotkeys.fast_forward = key_from(value)?,
+        ("hotkeys", "slow_motion") => settings.hotkeys.slow_motion = key_from(value)?,
+        ("palettes", name) => {
+            let palette = Palette::parse_colors(value)?;
+            match settings.palettes.iter_mut().find(|(n, _)| n == name) {
+                Some((_, existing)) => *existing = palette,
+                None => settings.palettes.push((name.to_string(), palette)),
+            }
+        }
+        (section, key) => return Err(format!("unknown setting '{}' in [{}]", key, section)),
+    }
+
+    Ok(())
+}
+
+fn key_from(name: &str) -> Result<Key, String> {
+    parse_key(name).ok_or_else(|| format!("unknown key '{}'", name))
+}
+
+fn binding_from(name: &str) -> Result<Binding, String> {
+    Binding::parse(name).ok_or_else(|| format!("unknown key or button '{}'", name.trim()))
+}
+
+fn parse_speed(value: &str) -> Result<u8, String> {
+    value
+        .parse::<u8>()
+        .ok()
+        .filter(|&speed| speed > 0)
+        .ok_or_else(|| format!("speed must be between 1 and 255, got '{}'", value))
+}
@@ -1,15 +1,54 @@
 use crate::opcode::Opcode;
-use rand::random;
-use rodio::Sink;
+use crate::quirks::Quirks;
+use crate::timing;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 use std::fs::File;
-use std::io::Read;
-use std::{thread, time::Duration};
+use std::io::{self, Read};
 
 pub const GRAPHICS_WIDTH: usize = 64;
 pub const GRAPHICS_HEIGHT: usize = 32;
 pub const FONTSET_BYTES_PER_CHAR: u16 = 5;
-
-const REGISTERS: usize = 16;
+pub const MEMORY_SIZE: usize = 4096;
+pub const DEFAULT_LOAD_ADDRESS: u16 = 0x200;
+pub const KEYPAD_SIZE: usize = 16;
+
+pub const REGISTERS: usize = 16;
+
+// Save states hold this many levels of subroutine calls, more than the 12 the
+// COSMAC VIP had room for. Only the innermost ones are kept beyond that.
+const STATE_STACK_DEPTH: usize = 16;
+const STATE_MAGIC: &[u8; 4] = b"C8ST";
+const STATE_VERSION: u8 = 2;
+
+// Every save state is the same size, which libretro frontends rely on.
+pub const STATE_SIZE: usize = STATE_MAGIC.len()
+    + 1
+    + 4 * 2
+    + 2
+    + REGISTERS
+    + MEMORY_SIZE
+    + GRAPHICS_WIDTH * GRAPHICS_HEIGHT
+    + 2
+    + KEYPAD_SIZE * 3
+    + 1
+    + 4
+    + 1
+    + STATE_STACK_DEPTH * 2;
+
+pub type Graphics = [[bool; GRAPHICS_WIDTH]; GRAPHICS_HEIGHT];
+
+// A copy of the CPU's registers, for frontends that show or inspect them.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Registers {
+    pub v: [u8; REGISTERS],
+    pub index_register: u16,
+    pub program_counter: u16,
+    // How many subroutine calls deep the program is.
+    pub stack_pointer: u16,
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+}
 
 const FONTSET: [u8; 80] = [
     0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
@@ -34,32 +73,43 @@ pub struct Cpu {
     program_counter: u16,
     index_register: u16,
     stack_pointer: u16,
-    memory: [u8; 4096],
+    memory: [u8; MEMORY_SIZE],
     delay_timer: u8,
     sound_timer: u8,
     register: [u8; REGISTERS],
-    pub graphics: [[bool; GRAPHICS_WIDTH]; GRAPHICS_HEIGHT],
-    pub draw_flag: bool,
+    graphics: Graphics,
+    draw_flag: bool,
     done: bool,
     debug: bool,
     stack: Vec<u16>,
-    keys: [bool; 16],
-    sink: Sink,
+    keys: [bool; KEYPAD_SIZE],
+    // Keys that went down or up since FX0A started waiting.
+    keys_pressed: [bool; KEYPAD_SIZE],
+    keys_released: [bool; KEYPAD_SIZE],
+    waiting_for_key: bool,
+    quirks: Quirks,
+    rng: StdRng,
+    load_address: u16,
+    // With the vip_timing quirk, the machine cycles left in this frame. Below
+    // zero, the last instruction ran over into the next one.
+    cycles: i32,
+    instructions: u64,
+}
+
+impl Default for Cpu {
+    fn default() -> Cpu {
+        Cpu::new()
+    }
 }
 
 impl Cpu {
+    // Screen, sound and input are up to the frontend, see host::Host.
     pub fn new() -> Cpu {
-        let device = rodio::default_output_device().unwrap();
-        let sink = Sink::new(&device);
-        sink.pause();
-        let source = rodio::source::SineWave::new(440);
-        sink.append(source);
-
         Cpu {
-            program_counter: 0x200,
+            program_counter: DEFAULT_LOAD_ADDRESS,
             index_register: 0,
             stack_pointer: 0,
-            memory: [0; 4096],
+            memory: [0; MEMORY_SIZE],
             delay_timer: 0,
             sound_timer: 0,
             register: [0; REGISTERS],
@@ -68,18 +118,49 @@ impl Cpu {
             done: false,
             debug: false,
             stack: Vec::<u16>::new(),
-            keys: [false; 16],
-            sink: sink,
+            keys: [false; KEYPAD_SIZE],
+            keys_pressed: [false; KEYPAD_SIZE],
+            keys_released: [false; KEYPAD_SIZE],
+            waiting_for_key: false,
+            quirks: Quirks::default(),
+            rng: StdRng::from_entropy(),
+            load_address: DEFAULT_LOAD_ADDRESS,
+            cycles: 0,
+            instructions: 0,
         }
     }
 
-    pub fn set_key(&mut self, key: u8) {
-        self.keys[key as usize] = true;
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.quirks = quirks;
     }
 
-    pub fn reset_keys(&mut self) {
-        for i in 0..16 {
-            self.keys[i] = false;
+    pub fn seed(&mut self, seed: u64) {
+        self.rng = StdRng::seed_from_u64(seed);
+    }
+
+    // Takes effect on the next load_program.
+    pub fn set_load_address(&mut self, address: u16) {
+        self.load_address = address;
+    }
+
+    // Keys stay down until they are set up again, so frontends only need to
+    // report changes, or the whole keypad once a frame with set_keys.
+    pub fn set_key(&mut self, key: u8, down: bool) {
+        let key = key as usize;
+
+        if down && !self.keys[key] {
+            self.keys_pressed[key] = true;
+        }
+        if !down && self.keys[key] {
+            self.keys_released[key] = true;
+        }
+
+        self.keys[key] = down;
+    }
+
+    pub fn set_keys(&mut self, keys: &[bool; KEYPAD_SIZE]) {
+        for (key, &down) in keys.iter().enumerate() {
+            self.set_key(key as u8, down);
         }
     }
 
@@ -93,28 +174,134 @@ impl Cpu {
         Opcode::new(opcode)
     }
 
-    pub fn load_program(&mut self, file_name: &str) {
-        let mut file = File::open(file_name).expect("There was an issue reading the file.");
+    pub fn load_program(&mut self, file_name: &str) -> io::Result<()> {
+        let mut file = File::open(file_name)?;
         let mut game_data = Vec::new();
-        file.read_to_end(&mut game_data)
-            .expect("Failure to read file");
+        file.read_to_end(&mut game_data)?;
+
+        self.load_rom(&game_data)
+    }
+
+    pub fn load_rom(&mut self, game_data: &[u8]) -> io::Result<()> {
+        let start = self.load_address as usize;
+        if start + game_data.len() > MEMORY_SIZE {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "program is {} bytes, only {} fit at {:#06x}",
+                    game_data.len(),
+                    MEMORY_SIZE - start,
+                    start
+                ),
+            ));
+        }
+
+        self.memory = [0; MEMORY_SIZE];
+        self.restart();
+
+        self.memory[..FONTSET.len()].copy_from_slice(&FONTSET);
+        self.memory[start..start + game_data.len()].copy_from_slice(game_data);
 
-        for i in 0..80 {
-            self.memory[i as usize] = FONTSET[i as usize];
+        Ok(())
+    }
+
+    // Everything needed to pick up emulation where it was left, except for
+    // the random number generator, and the quirks, which are settings.
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut state = Vec::with_capacity(STATE_SIZE);
+
+        state.extend_from_slice(STATE_MAGIC);
+        state.push(STATE_VERSION);
+        for value in &[
+            self.program_counter,
+            self.index_register,
+            self.stack_pointer,
+            self.load_address,
+        ] {
+            state.extend_from_slice(&value.to_be_bytes());
+        }
+        state.push(self.delay_timer);
+        state.push(self.sound_timer);
+        state.extend_from_slice(&self.register);
+        state.extend_from_slice(&self.memory);
+        for row in self.graphics.iter() {
+            state.extend(row.iter().map(|&pixel| pixel as u8));
+        }
+        state.push(self.draw_flag as u8);
+        state.push(self.done as u8);
+        for keys in &[self.keys, self.keys_pressed, self.keys_released] {
+            state.extend(keys.iter().map(|&key| key as u8));
+        }
+        state.push(self.waiting_for_key as u8);
+        state.extend_from_slice(&self.cycles.to_be_bytes());
+
+        let skipped = self.stack.len().saturating_sub(STATE_STACK_DEPTH);
+        let stack = &self.stack[skipped..];
+        state.push(stack.len() as u8);
+        for i in 0..STATE_STACK_DEPTH {
+            let address = stack.get(i).copied().unwrap_or(0);
+            state.extend_from_slice(&address.to_be_bytes());
         }
 
-        for i in &game_data {
-            self.memory[self.program_counter as usize] = *i as u8;
-            self.program_counter += 1;
+        state
+    }
+
+    pub fn load_state(&mut self, state: &[u8]) -> io::Result<()> {
+        let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message);
+
+        if state.len() != STATE_SIZE || &state[..STATE_MAGIC.len()] != STATE_MAGIC {
+            return Err(invalid("not a save state"));
+        }
+        if state[STATE_MAGIC.len()] != STATE_VERSION {
+            return Err(invalid("save state from an incompatible version"));
+        }
+
+        // The length was checked above, so there's always a next byte.
+        let mut bytes = state[STATE_MAGIC.len() + 1..].iter().copied();
+        let mut next = || bytes.next().unwrap();
+
+        self.program_counter = u16::from_be_bytes([next(), next()]);
+        self.index_register = u16::from_be_bytes([next(), next()]);
+        self.stack_pointer = u16::from_be_bytes([next(), next()]);
+        self.load_address = u16::from_be_bytes([next(), next()]);
+        self.delay_timer = next();
+        self.sound_timer = next();
+        for register in self.register.iter_mut() {
+            *register = next();
         }
+        for cell in self.memory.iter_mut() {
+            *cell = next();
+        }
+        for pixel in self.graphics.iter_mut().flat_map(|row| row.iter_mut()) {
+            *pixel = next() != 0;
+        }
+        self.draw_flag = next() != 0;
+        self.done = next() != 0;
+        for keys in [
+            &mut self.keys,
+            &mut self.keys_pressed,
+            &mut self.keys_released,
+        ] {
+            for key in keys.iter_mut() {
+                *key = next() != 0;
+            }
+        }
+        self.waiting_for_key = next() != 0;
+        self.cycles = i32::from_be_bytes([next(), next(), next(), next()]);
+
+        let depth = (next() as usize).min(STATE_STACK_DEPTH);
+        let stack: Vec<u16> = (0..STATE_STACK_DEPTH)
+            .map(|_| u16::from_be_bytes([next(), next()]))
+            .collect();
+        self.stack = stack[..depth].to_vec();
 
-        self.program_counter = 0x200;
+        Ok(())
     }
 
     pub fn dump_program(&self) {
-        let mut i = 0x200;
+        let mut i = self.load_address as usize;
 
-        while i < 0x1000 {
+        while i < MEMORY_SIZE - 1 {
             let opcode = self.opcode_at(i);
             if opcode.opcode == 0 {
                 break;
@@ -130,6 +317,25 @@ impl Cpu {
         }
     }
 
+    // Runs a frame's worth of instructions, calling `before_step` ahead of
+    // every one: `speed` of them, or with the vip_timing quirk as many as the
+    // COSMAC VIP had time for in 1/60 s. Timers are left alone.
+    pub fn run_frame<F: FnMut(&mut Cpu)>(&mut self, speed: u8, mut before_step: F) {
+        if !self.quirks.vip_timing {
+            for _ in 0..speed {
+                before_step(self);
+                self.step();
+            }
+            return;
+        }
+
+        self.cycles += timing::INTERPRETER_CYCLES;
+        while self.cycles > 0 && !self.done {
+            before_step(self);
+            self.step();
+        }
+    }
+
     pub fn step(&mut self) {
         if self.done {
             return;
@@ -141,17 +347,8 @@ impl Cpu {
             println!("{:#06x}: {}", self.program_counter, opcode);
         }
 
-        if self.delay_timer > 0 {
-            self.delay_timer -= 1;
-        }
-
-        if self.sound_timer > 0 {
-            self.sink.play();
-            thread::sleep(Duration::from_micros(16600) * self.sound_timer as u32);
-            self.sink.pause();
-
-            self.sound_timer = 0;
-        }
+        self.instructions += 1;
+        let (program_counter, vx) = (self.program_counter, self.register[opcode.x() as usize]);
 
         match opcode.t() {
             0x0000 => match opcode.kk() {
@@ -173,9 +370,9 @@ impl Cpu {
                 0x3 => self.xor(opcode.x(), opcode.y()),
                 0x4 => self.addr(opcode.x(), opcode.y()),
                 0x5 => self.sub(opcode.x(), opcode.y()),
-                0x6 => self.shr(opcode.x()),
+                0x6 => self.shr(opcode.x(), opcode.y()),
                 0x7 => self.ssub(opcode.x(), opcode.y()),
-                0xE => self.shl(opcode.x()),
+                0xE => self.shl(opcode.x(), opcode.y()),
                 _ => panic!("Unknown in math: {}", opcode),
             },
             0x9000 => self.skrne(opcode.x(), opcode.y()),
@@ -202,22 +399,99 @@ impl Cpu {
             },
             _ => panic!("Unknown opcode: {}", opcode),
         }
+
+        if self.quirks.vip_timing {
+            let skipped = self.program_counter == program_counter + 4;
+            let cycles = timing::cycles(&opcode, vx, skipped) as i32;
+
+            // The sprite is already on the screen, but the VIP only drew it
+            // once the next frame began: the rest of this frame goes unused
+            // and the drawing's cycles come out of the next.
+            if opcode.t() == 0xD000 {
+                self.cycles = -cycles;
+            } else {
+                self.cycles -= cycles;
+            }
+        }
+    }
+
+    // Counts both timers down, once every 1/60 s.
+    pub fn tick_timers(&mut self) {
+        self.delay_timer = self.delay_timer.saturating_sub(1);
+        self.sound_timer = self.sound_timer.saturating_sub(1);
+    }
+
+    // How many instructions have run since the CPU was made.
+    pub fn instructions(&self) -> u64 {
+        self.instructions
+    }
+
+    pub fn is_beeping(&self) -> bool {
+        self.sound_timer > 0
+    }
+
+    pub fn registers(&self) -> Registers {
+        Registers {
+            v: self.register,
+            index_register: self.index_register,
+            program_counter: self.program_counter,
+            stack_pointer: self.stack.len() as u16,
+            delay_timer: self.delay_timer,
+            sound_timer: self.sound_timer,
+        }
+    }
+
+    // Everything but the stack pointer, which follows the calls and returns
+    // the program makes.
+    pub fn set_registers(&mut self, registers: &Registers) {
+        self.register = registers.v;
+        self.index_register = registers.index_register;
+        self.program_counter = registers.program_counter;
+        self.delay_timer = registers.delay_timer;
+        self.sound_timer = registers.sound_timer;
+    }
+
+    pub fn memory(&self) -> &[u8] {
+        &self.memory
+    }
+
+    pub fn memory_mut(&mut self) -> &mut [u8] {
+        &mut self.memory
+    }
+
+    pub fn graphics(&self) -> &Graphics {
+        &self.graphics
     }
 
-    pub fn draw_done(&mut self) {
+    // Whether the screen changed since the last call.
+    pub fn take_draw_flag(&mut self) -> bool {
+        let draw_flag = self.draw_flag;
         self.draw_flag = false;
+        draw_flag
+    }
+
+    // Makes the next frame redraw the screen, e.g. after an overlay covered it.
+    pub fn redraw(&mut self) {
+        self.draw_flag = true;
     }
 
     pub fn restart(&mut self) {
-        self.program_counter = 0x200;
+        self.program_counter = self.load_address;
         self.index_register = 0;
         self.stack_pointer = 0;
         self.delay_timer = 0;
         self.sound_timer = 0;
         self.register = [0; REGISTERS];
-        self.clr();
-        self.draw_flag = false;
+        self.graphics = [[false; GRAPHICS_WIDTH]; GRAPHICS_HEIGHT];
+        self.stack.clear();
+        self.waiting_for_key = false;
+        self.draw_flag = true;
         self.done = false;
+        self.cycles = 0;
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.done
     }
 
     pub fn toggle_debug(&mut self) {
@@ -230,7 +504,7 @@ impl Cpu {
     }
 
     fn rand(&mut self, x: u8, kk: u8) {
-        self.register[x as usize] = random::<u8>() & kk;
+        self.register[x as usize] = self.rng.gen::<u8>() & kk;
         self.program_counter += 2;
     }
 
@@ -254,8 +528,19 @@ impl Cpu {
             for col in 0..8 {
                 let bit = (byte >> (7 - col)) & 0x1;
 
-                let position_x: usize = (pos_x + col) as usize % GRAPHICS_WIDTH;
-                let position_y: usize = (pos_y + row as u8) as usize % GRAPHICS_HEIGHT;
+                let mut position_x: usize = pos_x as usize + col as usize;
+                let mut position_y: usize = pos_y as usize + row;
+
+                if self.quirks.clip {
+                    position_x = pos_x as usize % GRAPHICS_WIDTH + col as usize;
+                    position_y = pos_y as usize % GRAPHICS_HEIGHT + row;
+                    if position_x >= GRAPHICS_WIDTH || position_y >= GRAPHICS_HEIGHT {
+                        continue;
+                    }
+                }
+
+                position_x %= GRAPHICS_WIDTH;
+                position_y %= GRAPHICS_HEIGHT;
 
                 let pixel = if self.graphics[position_y][position_x] {
                     1
@@ -276,7 +561,7 @@ impl Cpu {
     }
 
     fn add(&mut self, x: u8, kk: u8) {
-        self.register[x as usize] += kk;
+        self.register[x as usize] = self.register[x as usize].wrapping_add(kk);
         self.program_counter += 2;
     }
 
@@ -321,13 +606,14 @@ impl Cpu {
             self.register[0xF] = 1;
         }
 
-        self.register[x as usize] = res as u8 & 0xFF;
+        self.register[x as usize] = res as u8;
 
         self.program_counter += 2;
     }
 
     fn clr(&mut self) {
         self.graphics = [[false; GRAPHICS_WIDTH]; GRAPHICS_HEIGHT];
+        self.draw_flag = true;
         self.program_counter += 2;
     }
 
@@ -364,7 +650,10 @@ impl Cpu {
     }
 
     fn and(&mut self, x: u8, y: u8) {
-        self.register[x as usize] = self.register[x as usize] & self.register[y as usize];
+        self.register[x as usize] &= self.register[y as usize];
+        if self.quirks.vf_reset {
+            self.register[0xF] = 0;
+        }
         self.program_counter += 2;
     }
 
@@ -374,32 +663,41 @@ impl Cpu {
                 self.memory[(self.index_register + offset as u16) as usize];
         }
 
+        if self.quirks.memory_increment {
+            self.index_register += x as u16 + 1;
+        }
+
         self.program_counter += 2;
     }
 
-    fn shl(&mut self, x: u8) {
-        self.register[0xF] = (self.register[x as usize] >> 7) & 0x1;
-        self.register[x as usize] = self.register[x as usize] << 1;
+    fn shl(&mut self, x: u8, y: u8) {
+        let source = if self.quirks.shift_vy { y } else { x };
+        let value = self.register[source as usize];
+
+        self.register[x as usize] = value << 1;
+        self.register[0xF] = (value >> 7) & 0x1;
         self.program_counter += 2;
     }
 
-    fn shr(&mut self, x: u8) {
-        self.register[0xF] = self.register[x as usize] & 0x1;
+    fn shr(&mut self, x: u8, y: u8) {
+        let source = if self.quirks.shift_vy { y } else { x };
+        let value = self.register[source as usize];
 
-        self.register[x as usize] = self.register[x as usize] >> 1;
+        self.register[x as usize] = value >> 1;
+        self.register[0xF] = value & 0x1;
         self.program_counter += 2;
     }
 
     fn sub(&mut self, x: u8, y: u8) {
-        let vx: u8 = self.register[x as usize] as u8;
-        let vy: u8 = self.register[y as usize] as u8;
+        let vx: u8 = self.register[x as usize];
+        let vy: u8 = self.register[y as usize];
 
         self.register[0xF] = 0;
         if vy > vx {
             self.register[0xF] = 1;
         }
 
-        self.register[x as usize] = vx - vy;
+        self.register[x as usize] = vx.wrapping_sub(vy);
         self.program_counter += 2;
     }
 
@@ -409,6 +707,10 @@ impl Cpu {
                 self.register[offset as usize];
         }
 
+        if self.quirks.memory_increment {
+            self.index_register += x as u16 + 1;
+        }
+
         self.program_counter += 2;
     }
 
@@ -426,7 +728,10 @@ impl Cpu {
     }
 
     fn xor(&mut self, x: u8, y: u8) {
-        self.register[x as usize] = self.register[x as usize] ^ self.register[y as usize];
+        self.register[x as usize] ^= self.register[y as usize];
+        if self.quirks.vf_reset {
+            self.register[0xF] = 0;
+        }
         self.program_counter += 2;
     }
 
@@ -439,30 +744,38 @@ impl Cpu {
     }
 
     fn or(&mut self, x: u8, y: u8) {
-        self.register[x as usize] = self.register[x as usize] | self.register[y as usize];
+        self.register[x as usize] |= self.register[y as usize];
+        if self.quirks.vf_reset {
+            self.register[0xF] = 0;
+        }
         self.program_counter += 2;
     }
 
     fn ssub(&mut self, x: u8, y: u8) {
-        let vx: u8 = self.register[x as usize] as u8;
-        let vy: u8 = self.register[y as usize] as u8;
+        let vx: u8 = self.register[x as usize];
+        let vy: u8 = self.register[y as usize];
 
         self.register[0xF] = 0;
         if vx > vy {
             self.register[0xF] = 1;
         }
 
-        self.register[x as usize] = vy - vx;
+        self.register[x as usize] = vy.wrapping_sub(vx);
         self.program_counter += 2;
     }
 
     fn jumpi(&mut self, nnn: u16) {
-        self.program_counter = self.register[0x0] as u16 + nnn;
+        let register = if self.quirks.jump_vx {
+            (nnn >> 8) as usize
+        } else {
+            0x0
+        };
+
+        self.program_counter = self.register[register] as u16 + nnn;
     }
 
     fn skpr(&mut self, x: u8) {
         if self.keys[self.register[x as usize] as usize] {
-            self.reset_keys();
             self.program_counter += 4;
         } else {
             self.program_counter += 2;
@@ -471,7 +784,6 @@ impl Cpu {
 
     fn skup(&mut self, x: u8) {
         if self.keys[self.register[x as usize] as usize] {
-            self.reset_keys();
             self.program_counter += 2;
         } else {
             self.program_counter += 4;
@@ -487,17 +799,157 @@ impl Cpu {
         self.memory[self.index_register as usize] =
             ((self.register[x as usize] as u16 % 1000) / 100) as u8;
         self.memory[self.index_register as usize + 1] = (self.register[x as usize] % 100) / 10;
-        self.memory[self.index_register as usize + 2] = self.register[x as usize] % 100;
+        self.memory[self.index_register as usize + 2] = self.register[x as usize] % 10;
         self.program_counter += 2;
     }
 
+    // Waits for a key to go down after FX0A starts waiting, so a key that was
+    // already held doesn't count. With the key_release quirk it also has to
+    // come back up, like on the COSMAC VIP.
     fn keyd(&mut self, x: u8) {
-        for (i, &item) in self.keys.iter().enumerate() {
-            if item {
-                self.register[x as usize] = i as u8;
-                self.program_counter += 2;
-                return;
+        if !self.waiting_for_key {
+            self.waiting_for_key = true;
+            self.keys_pressed = [false; KEYPAD_SIZE];
+            self.keys_released = [false; KEYPAD_SIZE];
+        }
+
+        let key = (0..KEYPAD_SIZE).find(|&k| {
+            if self.quirks.key_release {
+                self.keys_pressed[k] && self.keys_released[k] && !self.keys[k]
+            } else {
+                self.keys_pressed[k]
             }
+        });
+
+        if let Some(key) = key {
+            self.register[x as usize] = key as u8;
+            self.waiting_for_key = false;
+            self.program_counter += 2;
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Waits for a key into V3, then loops.
+    const WAIT_FOR_KEY: [u8; 4] = [0xf3, 0x0a, 0x12, 0x02];
+
+    fn load(rom: &[u8], quirks: Quirks) -> Cpu {
+        let mut cpu = Cpu::new();
+        cpu.set_quirks(quirks);
+        cpu.load_rom(rom).unwrap();
+        cpu
+    }
+
+    fn is_waiting(cpu: &Cpu) -> bool {
+        cpu.program_counter == 0x200
+    }
+
+    #[test]
+    fn waiting_for_a_key_takes_a_fresh_press() {
+        let mut cpu = load(&WAIT_FOR_KEY, Quirks::default());
+
+        // Held from before the wait, and still held through it.
+        cpu.set_key(0x5, true);
+        cpu.step();
+        cpu.step();
+        assert!(is_waiting(&cpu));
+
+        cpu.set_key(0x5, false);
+        cpu.step();
+        assert!(is_waiting(&cpu));
+
+        cpu.set_key(0x7, true);
+        cpu.step();
+        assert!(!is_waiting(&cpu));
+        assert_eq!(cpu.register[0x3], 0x7);
+    }
+
+    #[test]
+    fn a_held_key_counts_once_it_is_pressed_again() {
+        let mut cpu = load(&WAIT_FOR_KEY, Quirks::default());
+
+        cpu.set_key(0x5, true);
+        cpu.step();
+        cpu.set_key(0x5, false);
+        cpu.set_key(0x5, true);
+        cpu.step();
+        assert_eq!(cpu.register[0x3], 0x5);
+    }
+
+    #[test]
+    fn with_key_release_the_key_has_to_come_back_up() {
+        let quirks = Quirks {
+            key_release: true,
+            ..Quirks::default()
+        };
+        let mut cpu = load(&WAIT_FOR_KEY, quirks);
+
+        cpu.step();
+        cpu.set_key(0x7, true);
+        cpu.step();
+        cpu.step();
+        assert!(is_waiting(&cpu));
+
+        cpu.set_key(0x7, false);
+        cpu.step();
+        assert!(!is_waiting(&cpu));
+        assert_eq!(cpu.register[0x3], 0x7);
+    }
+
+    #[test]
+    fn a_key_released_before_the_wait_does_not_count_on_release() {
+        let quirks = Quirks {
+            key_release: true,
+            ..Quirks::default()
+        };
+        let mut cpu = load(&WAIT_FOR_KEY, quirks);
+
+        // Pressed and let go before FX0A started waiting.
+        cpu.set_key(0x7, true);
+        cpu.set_key(0x7, false);
+        cpu.step();
+        assert!(is_waiting(&cpu));
+    }
+
+    #[test]
+    fn skipping_on_keys_leaves_them_down() {
+        // EX9E twice, then EXA1, on key 0.
+        let rom = [0xe0, 0x9e, 0x00, 0x00, 0xe0, 0x9e, 0x00, 0x00, 0xe0, 0xa1];
+        let mut cpu = load(&rom, Quirks::default());
+
+        cpu.set_key(0x0, true);
+        cpu.step();
+        assert_eq!(cpu.program_counter, 0x204);
+        cpu.step();
+        assert_eq!(cpu.program_counter, 0x208);
+        cpu.step();
+        assert_eq!(cpu.program_counter, 0x20a);
+    }
+
+    #[test]
+    fn states_keep_the_cycles_a_vip_frame_ran_over() {
+        // Draws over and over, which doesn't fit a VIP frame evenly.
+        let rom = [0xd0, 0x05, 0x12, 0x00];
+        let quirks = Quirks {
+            vip_timing: true,
+            ..Quirks::default()
+        };
+
+        let mut cpu = load(&rom, quirks);
+        cpu.run_frame(0, |_| {});
+        let state = cpu.save_state();
+        assert_eq!(state.len(), STATE_SIZE);
+
+        let mut loaded = Cpu::new();
+        loaded.set_quirks(quirks);
+        loaded.load_state(&state).unwrap();
+        assert_eq!(loaded.cycles, cpu.cycles);
+
+        cpu.run_frame(0, |_| {});
+        loaded.run_frame(0, |_| {});
+        assert_eq!(loaded.save_state(), cpu.save_state());
+    }
+}
@@ -0,0 +1,335 @@
+// A reinforcement learning environment in the style of OpenAI Gym: reset()
+// starts an episode, step(action) plays a few frames with the action's keys
+// held and returns what the agent sees, the reward and whether it's over.
+use crate::cpu::{Cpu, Graphics, KEYPAD_SIZE};
+use crate::host::Host;
+use crate::quirks::Quirks;
+use crate::reward::{NoReward, RewardExtractor};
+
+use std::io;
+use std::thread;
+
+const DEFAULT_SPEED: u8 = 10;
+const DEFAULT_FRAME_SKIP: u32 = 4;
+
+pub type Observation = Graphics;
+
+// The keys an agent can choose between. Action 0 is always "no keys".
+#[derive(Clone, PartialEq, Debug)]
+pub struct ActionSet {
+    actions: Vec<[bool; KEYPAD_SIZE]>,
+}
+
+impl ActionSet {
+    // One action per combination of keys, after the implicit no-op. Keys go
+    // from 0x0 to 0xF.
+    pub fn new(combinations: &[&[u8]]) -> Result<ActionSet, String> {
+        let mut actions = vec![[false; KEYPAD_SIZE]];
+
+        for keys in combinations {
+            let mut action = [false; KEYPAD_SIZE];
+            for &key in keys.iter() {
+                match action.get_mut(key as usize) {
+                    Some(down) => *down = true,
+                    None => return Err(format!("key {:#x} is not on the keypad", key)),
+                }
+            }
+            actions.push(action);
+        }
+
+        Ok(ActionSet { actions })
+    }
+
+    // One action per key, e.g. `ActionSet::keys(&[0x1, 0x4])` for up and down.
+    pub fn keys(keys: &[u8]) -> Result<ActionSet, String> {
+        let combinations: Vec<[u8; 1]> = keys.iter().map(|&key| [key]).collect();
+        let slices: Vec<&[u8]> = combinations.iter().map(|keys| &keys[..]).collect();
+        ActionSet::new(&slices)
+    }
+
+    // Every key on its own.
+    pub fn full() -> ActionSet {
+        let keys: Vec<u8> = (0..KEYPAD_SIZE as u8).collect();
+        ActionSet::keys(&keys).expect("every key is on the keypad")
+    }
+
+    pub fn len(&self) -> usize {
+        self.actions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.actions.is_empty()
+    }
+
+    fn keys_for(&self, action: usize) -> [bool; KEYPAD_SIZE] {
+        match self.actions.get(action) {
+            Some(keys) => *keys,
+            None => panic!(
+                "action {} is out of range for {} actions",
+                action,
+                self.actions.len()
+            ),
+        }
+    }
+}
+
+pub struct Env {
+    cpu: Cpu,
+    rom: Vec<u8>,
+    speed: u8,
+    frame_skip: u32,
+    max_frames: Option<u32>,
+    frames: u32,
+    seed: Option<u64>,
+    actions: ActionSet,
+    reward: Box<dyn RewardExtractor>,
+}
+
+impl Env {
+    // Ready to reset(), with every key as an action and no reward.
+    pub fn new(rom: &[u8]) -> io::Result<Env> {
+        let mut cpu = Cpu::new();
+        cpu.load_rom(rom)?;
+
+        Ok(Env {
+            cpu,
+            rom: rom.to_vec(),
+            speed: DEFAULT_SPEED,
+            frame_skip: DEFAULT_FRAME_SKIP,
+            max_frames: None,
+            frames: 0,
+            seed: None,
+            actions: ActionSet::full(),
+            reward: Box::new(NoReward),
+        })
+    }
+
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.cpu.set_quirks(quirks);
+    }
+
+    // Makes episodes repeatable: every reset() after this plays out the same
+    // for the same actions.
+    pub fn seed(&mut self, seed: u64) {
+        self.seed = Some(seed);
+        self.cpu.seed(seed);
+    }
+
+    // Instructions per frame.
+    pub fn set_speed(&mut self, speed: u8) {
+        self.speed = speed;
+    }
+
+    // Frames each action is held for, at least 1.
+    pub fn set_frame_skip(&mut self, frame_skip: u32) {
+        self.frame_skip = frame_skip.max(1);
+    }
+
+    // Cuts episodes off after this many frames.
+    pub fn set_max_frames(&mut self, max_frames: Option<u32>) {
+        self.max_frames = max_frames;
+    }
+
+    pub fn set_actions(&mut self, actions: ActionSet) {
+        self.actions = actions;
+    }
+
+    pub fn set_reward(&mut self, reward: Box<dyn RewardExtractor>) {
+        self.reward = reward;
+    }
+
+    pub fn actions(&self) -> &ActionSet {
+        &self.actions
+    }
+
+    pub fn cpu(&self) -> &Cpu {
+        &self.cpu
+    }
+
+    pub fn reset(&mut self) -> Observation {
+        self.cpu
+            .load_rom(&self.rom)
+            .expect("the ROM fitted when the environment was created");
+        self.cpu.set_keys(&[false; KEYPAD_SIZE]);
+        if let Some(seed) = self.seed {
+            self.cpu.seed(seed);
+        }
+        self.frames = 0;
+        self.reward.reset(self.cpu.memory());
+
+        *self.cpu.graphics()
+    }
+
+    // Holds the action's keys for frame_skip frames. Panics if the action
+    // isn't one of the action set's.
+    pub fn step(&mut self, action: usize) -> (Observation, f32, bool) {
+        let mut keys = self.actions.keys_for(action);
+        let mut reward = 0.0;
+        let mut done = false;
+
+        for _ in 0..self.frame_skip {
+            let mut host = Host {
+                display: &mut (),
+                audio: &mut (),
+                keypad: &mut keys,
+                clock: &mut (),
+            };
+            host.run_frame(&mut self.cpu, self.speed);
+            self.frames += 1;
+
+            reward += self.reward.reward(self.cpu.memory());
+            done = self.reward.is_over(self.cpu.memory())
+                || self.cpu.is_done()
+                || self.max_frames.is_some_and(|max| self.frames >= max);
+
+            if done {
+                break;
+            }
+        }
+
+        (*self.cpu.graphics(), reward, done)
+    }
+}
+
+// Many environments stepped together, split between a number of threads.
+// Environments that finish an episode start the next one on their own: the
+// step that ends an episode returns its last observation, and the next step
+// plays on from a reset.
+pub struct VecEnv {
+    envs: Vec<Env>,
+    finished: Vec<bool>,
+    threads: usize,
+}
+
+impl VecEnv {
+    pub fn new(envs: Vec<Env>, threads: usize) -> VecEnv {
+        VecEnv {
+            finished: vec![false; envs.len()],
+            envs,
+            threads: threads.max(1),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.envs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.envs.is_empty()
+    }
+
+    pub fn envs_mut(&mut self) -> &mut [Env] {
+        &mut self.envs
+    }
+
+    pub fn reset(&mut self) -> Vec<Observation> {
+        self.finished
+            .iter_mut()
+            .for_each(|finished| *finished = false);
+        self.in_parallel(|env, _| env.reset())
+    }
+
+    // One action per environment.
+    pub fn step(&mut self, actions: &[usize]) -> Vec<(Observation, f32, bool)> {
+        assert_eq!(
+            actions.len(),
+            self.envs.len(),
+            "one action is needed per environment"
+        );
+
+        let finished = self.finished.clone();
+        let results = self.in_parallel(|env, i| {
+            if finished[i] {
+                env.reset();
+            }
+            env.step(actions[i])
+        });
+
+        for (finished, (_, _, done)) in self.finished.iter_mut().zip(&results) {
+            *finished = *done;
+        }
+        results
+    }
+
+    fn in_parallel<T, F>(&mut self, f: F) -> Vec<T>
+    where
+        T: Send,
+        F: Fn(&mut Env, usize) -> T + Sync,
+    {
+        let chunk_size = self.envs.len().div_ceil(self.threads).max(1);
+        let f = &f;
+
+        thread::scope(|scope| {
+            let handles: Vec<_> = self
+                .envs
+                .chunks_mut(chunk_size)
+                .enumerate()
+                .map(|(chunk, envs)| {
+                    scope.spawn(move || {
+                        envs.iter_mut()
+                            .enumerate()
+                            .map(|(i, env)| f(env, chunk * chunk_size + i))
+                            .collect::<Vec<T>>()
+                    })
+                })
+                .collect();
+
+            handles
+                .into_iter()
+                .flat_map(|handle| handle.join().unwrap())
+                .collect()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Draws the font's digit for a random number below 16 in the top left
+    // corner, over and over.
+    const RANDOM_DIGITS: [u8; 12] = [
+        0x00, 0xe0, 0xc0, 0x0f, 0xf0, 0x29, 0x61, 0x00, 0xd1, 0x15, 0x12, 0x00,
+    ];
+
+    fn episode(env: &mut Env) -> Vec<Observation> {
+        env.reset();
+        (0..20).map(|frame| env.step(frame % 3).0).collect()
+    }
+
+    #[test]
+    fn seeded_episodes_play_out_the_same() {
+        let mut env = Env::new(&RANDOM_DIGITS).unwrap();
+        env.set_actions(ActionSet::keys(&[0x1, 0x4]).unwrap());
+        env.seed(7);
+
+        let first = episode(&mut env);
+        assert_eq!(episode(&mut env), first);
+        assert!(first.windows(2).any(|frames| frames[0] != frames[1]));
+    }
+
+    #[test]
+    fn seeded_vec_envs_reset_to_the_same_episode() {
+        let envs = (0..2)
+            .map(|_| {
+                let mut env = Env::new(&RANDOM_DIGITS).unwrap();
+                env.seed(7);
+                env
+            })
+            .collect();
+        let mut envs = VecEnv::new(envs, 2);
+
+        envs.reset();
+        let first: Vec<_> = (0..10).map(|_| envs.step(&[0, 0])).collect();
+        envs.reset();
+        let second: Vec<_> = (0..10).map(|_| envs.step(&[0, 0])).collect();
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn action_sets_only_take_keypad_keys() {
+        assert_eq!(ActionSet::new(&[&[0x1, 0xF]]).unwrap().len(), 2);
+        assert!(ActionSet::new(&[&[0x1], &[0x10]]).is_err());
+        assert!(ActionSet::keys(&[16]).is_err());
+    }
+}
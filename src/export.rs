@@ -0,0 +1,143 @@
+use chip8_rs::cpu::{Graphics, GRAPHICS_HEIGHT, GRAPHICS_WIDTH};
+use chip8_rs::palette::Palette;
+
+use std::f32::consts::PI;
+use std::fs::File;
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+use std::path::Path;
+
+// Video is exported at this multiple of 64x32, which keeps the pixels sharp
+// through the chroma subsampling most encoders apply.
+const VIDEO_SCALE: usize = 4;
+const FRAMES_PER_SECOND: u32 = 60;
+
+const SAMPLE_RATE: u32 = 44_100;
+const SAMPLES_PER_FRAME: u32 = SAMPLE_RATE / FRAMES_PER_SECOND;
+// The same tone as the beeper, a little quieter than full scale.
+const TONE_FREQUENCY: f32 = 440.0;
+const AMPLITUDE: f32 = 0.25 * i16::MAX as f32;
+const WAV_HEADER_SIZE: u32 = 44;
+
+// Raw frames for video tools to encode later, as a YUV4MPEG2 stream: a line of
+// header, then every frame's Y, U and V planes at full resolution.
+pub struct VideoExport {
+    out: BufWriter<File>,
+    // The palette's off and on colors as Y, U and V.
+    colors: [[u8; 3]; 2],
+}
+
+impl VideoExport {
+    pub fn create(path: &Path, palette: Palette) -> io::Result<VideoExport> {
+        let mut out = BufWriter::new(File::create(path)?);
+        writeln!(
+            out,
+            "YUV4MPEG2 W{} H{} F{}:1 Ip A1:1 C444",
+            GRAPHICS_WIDTH * VIDEO_SCALE,
+            GRAPHICS_HEIGHT * VIDEO_SCALE,
+            FRAMES_PER_SECOND
+        )?;
+
+        Ok(VideoExport {
+            out,
+            colors: [yuv(palette.off()), yuv(palette.on())],
+        })
+    }
+
+    pub fn write_frame(&mut self, graphics: &Graphics) -> io::Result<()> {
+        self.out.write_all(b"FRAME\n")?;
+
+        for plane in 0..3 {
+            for row in graphics.iter() {
+                let line: Vec<u8> = row
+                    .iter()
+                    .flat_map(|&lit| [self.colors[lit as usize][plane]; VIDEO_SCALE])
+                    .collect();
+                for _ in 0..VIDEO_SCALE {
+                    self.out.write_all(&line)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+// 0x00RRGGBB to limited range BT.601, what Y4M players assume.
+fn yuv(color: u32) -> [u8; 3] {
+    let [_, r, g, b] = color.to_be_bytes();
+    let (r, g, b) = (r as f32, g as f32, b as f32);
+
+    let y = 16.0 + (65.481 * r + 128.553 * g + 24.966 * b) / 255.0;
+    let u = 128.0 + (-37.797 * r - 74.203 * g + 112.0 * b) / 255.0;
+    let v = 128.0 + (112.0 * r - 93.786 * g - 18.214 * b) / 255.0;
+
+    [y.round() as u8, u.round() as u8, v.round() as u8]
+}
+
+// The beeper's tone as 16-bit mono PCM, one frame's worth of samples at a time.
+// The tone carries on across frames, so that it doesn't click between them.
+pub struct AudioExport {
+    out: BufWriter<File>,
+    samples: u64,
+}
+
+impl AudioExport {
+    pub fn create(path: &Path) -> io::Result<AudioExport> {
+        let mut out = BufWriter::new(File::create(path)?);
+        // The sizes are filled in by finish(), once they're known.
+        write_wav_header(&mut out, 0)?;
+
+        Ok(AudioExport { out, samples: 0 })
+    }
+
+    pub fn write_frame(&mut self, beeping: bool) -> io::Result<()> {
+        for _ in 0..SAMPLES_PER_FRAME {
+            let sample = if beeping {
+                // The tone repeats every second, which keeps the time small
+                // enough for an f32 to stay precise.
+                let time = (self.samples % SAMPLE_RATE as u64) as f32 / SAMPLE_RATE as f32;
+                (AMPLITUDE * (2.0 * PI * TONE_FREQUENCY * time).sin()) as i16
+            } else {
+                0
+            };
+
+            self.out.write_all(&sample.to_le_bytes())?;
+            self.samples += 1;
+        }
+
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> io::Result<()> {
+        // Past 4 GB the header can't hold the size, and players read on
+        // to the end of the file anyway.
+        let size = (self.samples * 2).min((u32::MAX - WAV_HEADER_SIZE) as u64) as u32;
+        self.out.seek(SeekFrom::Start(0))?;
+        write_wav_header(&mut self.out, size)?;
+        self.out.flush()
+    }
+}
+
+fn write_wav_header<W: Write>(out: &mut W, data_size: u32) -> io::Result<()> {
+    let channels: u16 = 1;
+    let bits: u16 = 16;
+    let block_align = channels * bits / 8;
+
+    out.write_all(b"RIFF")?;
+    out.write_all(&(WAV_HEADER_SIZE - 8 + data_size).to_le_bytes())?;
+    out.write_all(b"WAVEfmt ")?;
+    out.write_all(&16u32.to_le_bytes())?;
+    // Uncompressed PCM.
+    out.write_all(&1u16.to_le_bytes())?;
+    out.write_all(&channels.to_le_bytes())?;
+    out.write_all(&SAMPLE_RATE.to_le_bytes())?;
+    out.write_all(&(SAMPLE_RATE * block_align as u32).to_le_bytes())?;
+    out.write_all(&block_align.to_le_bytes())?;
+    out.write_all(&bits.to_le_bytes())?;
+    out.write_all(b"data")?;
+    out.write_all(&data_size.to_le_bytes())
+}
@@ -0,0 +1,344 @@
+// Post-processing for the picture, done on the CPU so that it works the same
+// in the window and in headless screenshots: the screen is scaled up to
+// whatever size it's shown at, sharp or smoothed, and then given scanlines, a
+// grid between pixels, a glow around lit pixels and the curve of a CRT.
+//
+// Filters are a comma separated list, each optionally with a strength from 0
+// to 1: `scanlines,bloom:0.6,curvature`. `crt` turns on all but the grid.
+
+const DEFAULT_STRENGTH: f32 = 0.5;
+// How far the corners are pulled in at full curvature, as a fraction of the
+// picture.
+const MAX_CURVATURE: f32 = 0.25;
+// How far the glow spreads, in emulated pixels.
+const BLOOM_RADIUS: usize = 1;
+
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub struct Filter {
+    // Strengths from 0 for off to 1.
+    pub scanlines: f32,
+    pub grid: f32,
+    pub bloom: f32,
+    pub curvature: f32,
+    // Whether to scale up with bilinear filtering instead of nearest neighbor.
+    pub smooth: bool,
+}
+
+pub const FILTER_NAMES: [&str; 7] = [
+    "none",
+    "crt",
+    "scanlines[:STRENGTH]",
+    "grid[:STRENGTH]",
+    "bloom[:STRENGTH]",
+    "curvature[:STRENGTH]",
+    "smooth",
+];
+
+impl Filter {
+    pub fn parse(s: &str) -> Result<Filter, String> {
+        let mut filter = Filter::default();
+
+        for item in s.split(',').map(str::trim) {
+            let (name, strength) = match item.split_once(':') {
+                Some((name, strength)) => (name, Some(strength)),
+                None => (item, None),
+            };
+
+            let strength = match strength {
+                None => DEFAULT_STRENGTH,
+                Some(strength) => match strength.parse::<f32>() {
+                    Ok(strength) if (0.0..=1.0).contains(&strength) => strength,
+                    _ => {
+                        return Err(format!(
+                            "filter strengths go from 0 to 1, got '{}'",
+                            strength
+                        ))
+                    }
+                },
+            };
+
+            match (name, item.contains(':')) {
+                ("none", false) => filter = Filter::default(),
+                ("crt", false) => {
+                    filter = Filter {
+                        scanlines: DEFAULT_STRENGTH,
+                        bloom: DEFAULT_STRENGTH,
+                        curvature: DEFAULT_STRENGTH,
+                        smooth: true,
+                        ..filter
+                    }
+                }
+                ("smooth", false) => filter.smooth = true,
+                ("scanlines", _) => filter.scanlines = strength,
+                ("grid", _) => filter.grid = strength,
+                ("bloom", _) => filter.bloom = strength,
+                ("curvature", _) => filter.curvature = strength,
+                _ => {
+                    return Err(format!(
+                        "unknown filter '{}', expected a list of: {}",
+                        item,
+                        FILTER_NAMES.join(", ")
+                    ))
+                }
+            }
+        }
+
+        Ok(filter)
+    }
+
+    // Draws an image of 0x00RRGGBB pixels into the target, as large as fits
+    // without changing its shape, with black around it. Every emulated pixel
+    // is a cell by cell square of the source, which is where scanlines and
+    // the grid go.
+    pub fn render(
+        &self,
+        source: &[u32],
+        source_width: usize,
+        cell: usize,
+        target: &mut [u32],
+        target_width: usize,
+    ) {
+        let source_height = source.len() / source_width;
+        let target_height = target.len() / target_width;
+        let cell = cell.max(1) as f32;
+
+        // The largest size with the source's shape, centered.
+        let scale = (target_width as f32 / source_width as f32)
+            .min(target_height as f32 / source_height as f32);
+        let (width, height) = (source_width as f32 * scale, source_height as f32 * scale);
+        let (left, top) = (
+            (target_width as f32 - width) / 2.0,
+            (target_height as f32 - height) / 2.0,
+        );
+        // How big an emulated pixel is on the target.
+        let pixel_size = scale * cell;
+
+        let glow = if self.bloom > 0.0 {
+            Some(blur(source, source_width, BLOOM_RADIUS * cell as usize))
+        } else {
+            None
+        };
+
+        for y in 0..target_height {
+            for x in 0..target_width {
+                let u = (x as f32 + 0.5 - left) / width;
+                let v = (y as f32 + 0.5 - top) / height;
+                let (u, v) = self.curve(u, v);
+
+                target[y * target_width + x] = if (0.0..1.0).contains(&u) && (0.0..1.0).contains(&v)
+                {
+                    let (sx, sy) = (u * source_width as f32, v * source_height as f32);
+                    let mut color = if self.smooth {
+                        bilinear(source, source_width, sx, sy)
+                    } else {
+                        nearest(source, source_width, sx, sy)
+                    };
+
+                    // Light spilling over from brighter pixels nearby.
+                    if let Some(glow) = &glow {
+                        let glow = bilinear(glow, source_width, sx, sy);
+                        for (channel, value) in color.iter_mut().enumerate() {
+                            *value += (glow[channel] - *value).max(0.0) * self.bloom;
+                        }
+                    }
+
+                    // Where in its emulated pixel this is, from 0 to 1.
+                    let (cx, cy) = ((sx / cell).fract(), (sy / cell).fract());
+                    let mut brightness = 1.0;
+
+                    if self.scanlines > 0.0 {
+                        // Darkest between lines, full at their middle.
+                        let distance = (cy - 0.5).abs() * 2.0;
+                        brightness *= 1.0 - self.scanlines * distance * distance;
+                    }
+
+                    // A line a target pixel wide along the top and left of
+                    // every emulated pixel, once they're big enough to spare it.
+                    if self.grid > 0.0
+                        && pixel_size >= 3.0
+                        && (cx * pixel_size < 1.0 || cy * pixel_size < 1.0)
+                    {
+                        brightness *= 1.0 - self.grid;
+                    }
+
+                    pack(color, brightness)
+                } else {
+                    0
+                };
+            }
+        }
+    }
+
+    // Bends where a point of the target comes from, as a CRT's glass does.
+    fn curve(&self, u: f32, v: f32) -> (f32, f32) {
+        if self.curvature == 0.0 {
+            return (u, v);
+        }
+
+        let (cu, cv) = (u * 2.0 - 1.0, v * 2.0 - 1.0);
+        let k = self.curvature * MAX_CURVATURE;
+        let (cu, cv) = (cu * (1.0 + k * cv * cv), cv * (1.0 + k * cu * cu));
+
+        ((cu + 1.0) / 2.0, (cv + 1.0) / 2.0)
+    }
+}
+
+fn channels(color: u32) -> [f32; 3] {
+    let [_, r, g, b] = color.to_be_bytes();
+    [r as f32, g as f32, b as f32]
+}
+
+fn pack(color: [f32; 3], brightness: f32) -> u32 {
+    let [r, g, b] = color.map(|channel| (channel * brightness).clamp(0.0, 255.0) as u8);
+    u32::from_be_bytes([0, r, g, b])
+}
+
+fn nearest(source: &[u32], width: usize, x: f32, y: f32) -> [f32; 3] {
+    let height = source.len() / width;
+    let (x, y) = ((x as usize).min(width - 1), (y as usize).min(height - 1));
+    channels(source[y * width + x])
+}
+
+// Blends the four source pixels around a point, with pixel centers at .5.
+fn bilinear(source: &[u32], width: usize, x: f32, y: f32) -> [f32; 3] {
+    let height = source.len() / width;
+    let (x, y) = ((x - 0.5).max(0.0), (y - 0.5).max(0.0));
+    let (x0, y0) = ((x as usize).min(width - 1), (y as usize).min(height - 1));
+    let (x1, y1) = ((x0 + 1).min(width - 1), (y0 + 1).min(height - 1));
+    let (fx, fy) = (x.fract(), y.fract());
+
+    let at = |x: usize, y: usize| channels(source[y * width + x]);
+    let mut color = [0.0; 3];
+    for (channel, value) in color.iter_mut().enumerate() {
+        let top = at(x0, y0)[channel] * (1.0 - fx) + at(x1, y0)[channel] * fx;
+        let bottom = at(x0, y1)[channel] * (1.0 - fx) + at(x1, y1)[channel] * fx;
+        *value = top * (1.0 - fy) + bottom * fy;
+    }
+
+    color
+}
+
+// A box blur of the source, horizontally then vertically.
+fn blur(source: &[u32], width: usize, radius: usize) -> Vec<u32> {
+    let height = source.len() / width;
+    let pixels: Vec<[f32; 3]> = source.iter().map(|&color| channels(color)).collect();
+
+    let pass = |pixels: &[[f32; 3]], step: (usize, usize)| -> Vec<[f32; 3]> {
+        let mut blurred = vec![[0.0; 3]; pixels.len()];
+        for y in 0..height {
+            for x in 0..width {
+                let mut sum = [0.0; 3];
+                let mut count = 0.0;
+                for offset in 0..=radius * 2 {
+                    let (nx, ny) = (
+                        (x + offset * step.0).checked_sub(radius * step.0),
+                        (y + offset * step.1).checked_sub(radius * step.1),
+                    );
+                    if let (Some(nx), Some(ny)) = (nx, ny) {
+                        if nx < width && ny < height {
+                            for (channel, total) in sum.iter_mut().enumerate() {
+                                *total += pixels[ny * width + nx][channel];
+                            }
+                            count += 1.0;
+                        }
+                    }
+                }
+                blurred[y * width + x] = sum.map(|total| total / count);
+            }
+        }
+        blurred
+    };
+
+    let horizontal = pass(&pixels, (1, 0));
+    pass(&horizontal, (0, 1))
+        .into_iter()
+        .map(|color| pack(color, 1.0))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WHITE: u32 = 0x00ffffff;
+
+    // A 4x2 source, lit in a checkerboard.
+    fn checkerboard() -> Vec<u32> {
+        (0..8)
+            .map(|i| if (i % 4 + i / 4) % 2 == 0 { WHITE } else { 0 })
+            .collect()
+    }
+
+    fn render(filter: &str, source: &[u32], scale: usize) -> Vec<u32> {
+        let mut target = vec![0; source.len() * scale * scale];
+        Filter::parse(filter)
+            .unwrap()
+            .render(source, 4, 1, &mut target, 4 * scale);
+        target
+    }
+
+    fn red(color: u32) -> u32 {
+        color >> 16 & 0xff
+    }
+
+    #[test]
+    fn no_filter_and_zero_strengths_only_scale_up() {
+        let source = checkerboard();
+        let scaled = render("none", &source, 3);
+
+        for (i, &color) in scaled.iter().enumerate() {
+            let (x, y) = (i % 12 / 3, i / 12 / 3);
+            assert_eq!(color, source[y * 4 + x], "pixel {}", i);
+        }
+
+        let zero = "scanlines:0,grid:0,bloom:0,curvature:0";
+        assert_eq!(render(zero, &source, 3), scaled);
+    }
+
+    #[test]
+    fn scanlines_darken_the_rows_between_lines() {
+        let target = render("scanlines:1", &[WHITE; 8], 3);
+        let row = |y: usize| red(target[y * 12]);
+
+        for line in 0..2 {
+            assert_eq!(row(line * 3 + 1), 0xff);
+            assert!(row(line * 3) < 0xff);
+            assert!(row(line * 3 + 2) < 0xff);
+        }
+    }
+
+    #[test]
+    fn the_grid_darkens_the_top_and_left_of_every_pixel() {
+        let target = render("grid:0.5", &[WHITE; 8], 4);
+
+        for (i, &color) in target.iter().enumerate() {
+            let (x, y) = (i % 16, i / 16);
+            let expected = if x % 4 == 0 || y % 4 == 0 { 0x7f } else { 0xff };
+            assert_eq!(red(color), expected, "pixel {}, {}", x, y);
+        }
+    }
+
+    #[test]
+    fn bloom_lights_up_around_lit_pixels() {
+        let mut source = [0; 8];
+        source[1] = WHITE;
+        let target = render("bloom:1", &source, 1);
+
+        assert_eq!(target[1], WHITE);
+        assert!(red(target[0]) > 0 && red(target[2]) > 0);
+        assert_eq!(target[7], 0);
+    }
+
+    #[test]
+    fn pictures_keep_their_shape_and_curve_in_at_the_corners() {
+        let mut target = vec![WHITE; 8 * 8];
+        Filter::default().render(&[WHITE; 8], 4, 1, &mut target, 8);
+        assert!(target[..16].iter().all(|&color| color == 0));
+        assert!(target[16..48].iter().all(|&color| color == WHITE));
+        assert!(target[48..].iter().all(|&color| color == 0));
+
+        let curved = render("curvature:1", &[WHITE; 8], 8);
+        assert_eq!(curved[0], 0);
+        assert_eq!(curved[8 * 32 + 16], WHITE);
+    }
+}
@@ -0,0 +1,405 @@
+use std::fmt;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Button {
+    Up,
+    Down,
+    Left,
+    Right,
+    South,
+    East,
+    West,
+    North,
+    LeftShoulder,
+    RightShoulder,
+    Select,
+    Start,
+}
+
+const ALL_BUTTONS: [Button; 12] = [
+    Button::Up,
+    Button::Down,
+    Button::Left,
+    Button::Right,
+    Button::South,
+    Button::East,
+    Button::West,
+    Button::North,
+    Button::LeftShoulder,
+    Button::RightShoulder,
+    Button::Select,
+    Button::Start,
+];
+
+// Buttons are named with a `Pad` prefix in the config, so they can sit next to
+// keyboard keys: `PadUp`, `PadSouth`, `PadStart`...
+const BUTTON_PREFIX: &str = "Pad";
+
+impl Button {
+    pub fn parse(name: &str) -> Option<Button> {
+        // Key names can be anything, so the prefix is only sliced off where
+        // it ends on a character boundary.
+        let name = name.trim();
+        if !name
+            .get(..BUTTON_PREFIX.len())
+            .is_some_and(|prefix| prefix.eq_ignore_ascii_case(BUTTON_PREFIX))
+        {
+            return None;
+        }
+
+        let name = &name[BUTTON_PREFIX.len()..];
+        ALL_BUTTONS
+            .iter()
+            .find(|button| format!("{:?}", button).eq_ignore_ascii_case(name))
+            .copied()
+    }
+}
+
+impl fmt::Display for Button {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{:?}", BUTTON_PREFIX, self)
+    }
+}
+
+// Where gamepad state comes from. Every connected pad counts, so holding a
+// button on any of them holds it for the emulator.
+pub trait GamepadBackend {
+    // Picks up new events and newly plugged in pads. Called once per frame.
+    // Returns messages about pads coming and going, for the OSD.
+    fn poll(&mut self) -> Vec<String>;
+
+    fn is_down(&self, button: Button) -> bool;
+}
+
+// A pad driven by code rather than hardware, for tests. With nothing pressed
+// it also stands in when gamepads are turned off.
+#[derive(Default)]
+pub struct VirtualGamepad {
+    down: Vec<Button>,
+}
+
+#[cfg(test)]
+impl VirtualGamepad {
+    pub fn press(&mut self, button: Button) {
+        if !self.down.contains(&button) {
+            self.down.push(button);
+        }
+    }
+
+    pub fn release(&mut self, button: Button) {
+        self.down.retain(|&b| b != button);
+    }
+}
+
+impl GamepadBackend for VirtualGamepad {
+    fn poll(&mut self) -> Vec<String> {
+        Vec::new()
+    }
+
+    fn is_down(&self, button: Button) -> bool {
+        self.down.contains(&button)
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub fn default_backend() -> Box<dyn GamepadBackend> {
+    Box::new(evdev::Evdev::new())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn default_backend() -> Box<dyn GamepadBackend> {
+    Box::new(VirtualGamepad::default())
+}
+
+#[cfg(target_os = "linux")]
+mod evdev {
+    use super::{Button, GamepadBackend};
+
+    use std::fs::{self, File, OpenOptions};
+    use std::io::{self, Read};
+    use std::mem;
+    use std::os::unix::fs::OpenOptionsExt;
+    use std::path::{Path, PathBuf};
+
+    const INPUT_DIRECTORY: &str = "/dev/input";
+    const SYSFS_DIRECTORY: &str = "/sys/class/input";
+
+    // Scanning for new devices every frame is wasteful, once a second is
+    // plenty for noticing a pad being plugged in.
+    const RESCAN_INTERVAL: u32 = 60;
+
+    const EV_KEY: u16 = 0x01;
+    const EV_ABS: u16 = 0x03;
+
+    const ABS_HAT0X: u16 = 0x10;
+    const ABS_HAT0Y: u16 = 0x11;
+
+    // Any device reporting BTN_GAMEPAD (also known as BTN_SOUTH) is a pad.
+    const BTN_GAMEPAD: usize = 0x130;
+
+    const BUTTON_CODES: [(u16, Button); 12] = [
+        (0x130, Button::South),
+        (0x131, Button::East),
+        (0x133, Button::North),
+        (0x134, Button::West),
+        (0x136, Button::LeftShoulder),
+        (0x137, Button::RightShoulder),
+        (0x13a, Button::Select),
+        (0x13b, Button::Start),
+        (0x220, Button::Up),
+        (0x221, Button::Down),
+        (0x222, Button::Left),
+        (0x223, Button::Right),
+    ];
+
+    struct Device {
+        path: PathBuf,
+        file: File,
+        down: Vec<Button>,
+    }
+
+    pub struct Evdev {
+        devices: Vec<Device>,
+        frames_since_scan: u32,
+        // Connections and disconnections since the last poll.
+        messages: Vec<String>,
+    }
+
+    impl Evdev {
+        pub fn new() -> Evdev {
+            let mut evdev = Evdev {
+                devices: Vec::new(),
+                frames_since_scan: 0,
+                messages: Vec::new(),
+            };
+            evdev.scan();
+            evdev
+        }
+
+        fn scan(&mut self) {
+            let entries = match fs::read_dir(INPUT_DIRECTORY) {
+                Ok(entries) => entries,
+                Err(_) => return,
+            };
+
+            for entry in entries.filter_map(|e| e.ok()) {
+                let path = entry.path();
+                let name = entry.file_name().to_string_lossy().into_owned();
+
+                if !name.starts_with("event")
+                    || self.devices.iter().any(|d| d.path == path)
+                    || !is_gamepad(&name)
+                {
+                    continue;
+                }
+
+                // Pads we can't open (usually for lack of permissions) are
+                // skipped quietly and retried on the next scan.
+                let file = match OpenOptions::new()
+                    .read(true)
+                    .custom_flags(libc::O_NONBLOCK)
+                    .open(&path)
+                {
+                    Ok(file) => file,
+                    Err(_) => continue,
+                };
+
+                self.messages
+                    .push(format!("Gamepad connected: {}", device_name(&name)));
+                self.devices.push(Device {
+                    path,
+                    file,
+                    down: Vec::new(),
+                });
+            }
+        }
+    }
+
+    impl GamepadBackend for Evdev {
+        fn poll(&mut self) -> Vec<String> {
+            self.frames_since_scan += 1;
+            if self.frames_since_scan >= RESCAN_INTERVAL {
+                self.frames_since_scan = 0;
+                self.scan();
+            }
+
+            let messages = &mut self.messages;
+            self.devices
+                .retain_mut(|device| match device.read_events() {
+                    Ok(()) => true,
+                    Err(_) => {
+                        messages.push(format!("Gamepad disconnected: {}", device.path.display()));
+                        false
+                    }
+                });
+
+            mem::take(&mut self.messages)
+        }
+
+        fn is_down(&self, button: Button) -> bool {
+            self.devices.iter().any(|d| d.down.contains(&button))
+        }
+    }
+
+    impl Device {
+        fn read_events(&mut self) -> io::Result<()> {
+            // struct input_event: a timeval, then u16 type, u16 code, i32 value.
+            let offset = mem::size_of::<libc::timeval>();
+            let size = offset + 8;
+            let mut event = vec![0u8; size];
+
+            loop {
+                match self.file.read(&mut event) {
+                    Ok(n) if n == size => {
+                        let kind = u16::from_ne_bytes([event[offset], event[offset + 1]]);
+                        let code = u16::from_ne_bytes([event[offset + 2], event[offset + 3]]);
+                        let value = i32::from_ne_bytes([
+                            event[offset + 4],
+                            event[offset + 5],
+                            event[offset + 6],
+                            event[offset + 7],
+                        ]);
+                        self.handle(kind, code, value);
+                    }
+                    Ok(_) => return Err(io::Error::from(io::ErrorKind::UnexpectedEof)),
+                    Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(()),
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+
+        fn handle(&mut self, kind: u16, code: u16, value: i32) {
+            match (kind, code) {
+                (EV_KEY, _) => {
+                    if let Some(&(_, button)) = BUTTON_CODES.iter().find(|(c, _)| *c == code) {
+                        self.set(button, value != 0);
+                    }
+                }
+                // Most pads report the D-pad as a hat axis rather than buttons.
+                (EV_ABS, ABS_HAT0X) => {
+                    self.set(Button::Left, value < 0);
+                    self.set(Button::Right, value > 0);
+                }
+                (EV_ABS, ABS_HAT0Y) => {
+                    self.set(Button::Up, value < 0);
+                    self.set(Button::Down, value > 0);
+                }
+                _ => (),
+            }
+        }
+
+        fn set(&mut self, button: Button, down: bool) {
+            self.down.retain(|&b| b != button);
+            if down {
+                self.down.push(button);
+            }
+        }
+    }
+
+    fn sysfs(event: &str, file: &str) -> Option<String> {
+        fs::read_to_string(
+            Path::new(SYSFS_DIRECTORY)
+                .join(event)
+                .join("device")
+                .join(file),
+        )
+        .ok()
+    }
+
+    fn device_name(event: &str) -> String {
+        sysfs(event, "name")
+            .map(|name| name.trim().to_string())
+            .unwrap_or_else(|| String::from(event))
+    }
+
+    // The key capabilities are a bitmap written as hex words, most
+    // significant first, each as wide as the kernel's unsigned long.
+    fn is_gamepad(event: &str) -> bool {
+        let capabilities = match sysfs(event, "capabilities/key") {
+            Some(capabilities) => capabilities,
+            None => return false,
+        };
+
+        let word_bits = mem::size_of::<libc::c_ulong>() * 8;
+        let words: Vec<&str> = capabilities.split_whitespace().rev().collect();
+
+        words
+            .get(BTN_GAMEPAD / word_bits)
+            .and_then(|word| u64::from_str_radix(word, 16).ok())
+            .is_some_and(|word| word & (1 << (BTN_GAMEPAD % word_bits)) != 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keymap::{Binding, Keymap};
+
+    fn keypad(keymap: &Keymap, gamepad: &VirtualGamepad) -> Vec<u8> {
+        let pressed = keymap.pressed(|binding| match binding {
+            Binding::Key(_) => false,
+            Binding::Button(button) => gamepad.is_down(button),
+        });
+        (0..16).filter(|&key| pressed[key as usize]).collect()
+    }
+
+    #[test]
+    fn buttons_hold_their_keys_until_released() {
+        let keymap = Keymap::layout("qwerty").unwrap();
+        let mut gamepad = VirtualGamepad::default();
+        assert_eq!(keypad(&keymap, &gamepad), []);
+
+        gamepad.press(Button::Up);
+        gamepad.press(Button::South);
+        assert!(gamepad.poll().is_empty());
+        assert_eq!(keypad(&keymap, &gamepad), [0x2, 0x5]);
+
+        gamepad.release(Button::Up);
+        assert_eq!(keypad(&keymap, &gamepad), [0x5]);
+
+        gamepad.release(Button::South);
+        gamepad.release(Button::South);
+        assert_eq!(keypad(&keymap, &gamepad), []);
+    }
+
+    #[test]
+    fn buttons_parse_by_name_and_anything_else_is_left_alone() {
+        assert_eq!(Button::parse("PadSouth"), Some(Button::South));
+        assert_eq!(
+            Button::parse(" padleftshoulder "),
+            Some(Button::LeftShoulder)
+        );
+        for name in [
+            "Pad",
+            "PadÜ",
+            "Up",
+            "é",
+            "ü",
+            "éé",
+            "Päd",
+            "Pa\u{301}dUp",
+            "",
+        ] {
+            assert_eq!(Button::parse(name), None, "{}", name);
+        }
+    }
+
+    #[test]
+    fn pressing_twice_takes_one_release() {
+        let mut gamepad = VirtualGamepad::default();
+        gamepad.press(Button::Start);
+        gamepad.press(Button::Start);
+        gamepad.release(Button::Start);
+        assert!(!gamepad.is_down(Button::Start));
+    }
+
+    #[test]
+    fn rebound_buttons_follow_the_keymap() {
+        let mut keymap = Keymap::layout("qwerty").unwrap();
+        keymap.bind(0xF, vec![Binding::parse("PadStart").unwrap()]);
+        let mut gamepad = VirtualGamepad::default();
+
+        gamepad.press(Button::Start);
+        assert_eq!(keypad(&keymap, &gamepad), [0xF]);
+    }
+}
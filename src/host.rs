@@ -0,0 +1,207 @@
+use crate::cpu::{Cpu, Graphics, KEYPAD_SIZE};
+
+use std::thread;
+use std::time::{Duration, Instant};
+
+// CHIP-8 timers count down at 60 Hz, and everything else keeps pace with them.
+pub const FRAME_DURATION: Duration = Duration::from_micros(16_667);
+
+// What a frontend provides for the emulator to run on: somewhere to show the
+// screen, something to beep, a hex keypad and a sense of time. `()` stands in
+// for any of them that a frontend doesn't have.
+
+pub trait Display {
+    // Called whenever the screen changed since the last frame.
+    fn draw(&mut self, graphics: &Graphics);
+}
+
+pub trait Audio {
+    // The tone plays for as long as the sound timer is running.
+    fn set_beeping(&mut self, beeping: bool);
+}
+
+pub trait Keypad {
+    // Which of the 16 keys are held, read once a frame.
+    fn keys(&mut self) -> [bool; KEYPAD_SIZE];
+}
+
+pub trait Clock {
+    // Blocks until the next frame is due.
+    fn wait(&mut self);
+}
+
+impl Display for () {
+    fn draw(&mut self, _: &Graphics) {}
+}
+
+impl Audio for () {
+    fn set_beeping(&mut self, _: bool) {}
+}
+
+impl Keypad for () {
+    fn keys(&mut self) -> [bool; KEYPAD_SIZE] {
+        [false; KEYPAD_SIZE]
+    }
+}
+
+// No waiting at all, for running faster than real time.
+impl Clock for () {
+    fn wait(&mut self) {}
+}
+
+// Keys worked out by the frontend ahead of the frame.
+impl Keypad for [bool; KEYPAD_SIZE] {
+    fn keys(&mut self) -> [bool; KEYPAD_SIZE] {
+        *self
+    }
+}
+
+// Keeps frames 1/60 s apart. A frontend that falls behind carries on from
+// where it is instead of rushing to catch up.
+pub struct FrameClock {
+    next: Instant,
+}
+
+impl FrameClock {
+    pub fn new() -> FrameClock {
+        FrameClock {
+            next: Instant::now() + FRAME_DURATION,
+        }
+    }
+}
+
+impl Default for FrameClock {
+    fn default() -> FrameClock {
+        FrameClock::new()
+    }
+}
+
+impl Clock for FrameClock {
+    fn wait(&mut self) {
+        let now = Instant::now();
+
+        if self.next > now {
+            thread::sleep(self.next - now);
+            self.next += FRAME_DURATION;
+        } else {
+            self.next = now + FRAME_DURATION;
+        }
+    }
+}
+
+// How fast the emulated machine runs compared to real time. Everything
+// emulated keeps pace with it, the 60 Hz timers and the sound included.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TimeScale {
+    // This many emulated frames for every frame shown: above 1 fast-forwards,
+    // below 1 is slow motion.
+    Times(f32),
+    // As many emulated frames as fit in the time of one frame shown.
+    Uncapped,
+}
+
+// Past this, fast-forward might as well be uncapped.
+const MAX_TIMES: f32 = 100.0;
+const MIN_TIMES: f32 = 0.01;
+
+// An uncapped frame leaves the rest of its time for drawing the screen.
+const UNCAPPED_BUDGET: Duration = Duration::from_micros(12_500);
+
+impl TimeScale {
+    pub const NORMAL: TimeScale = TimeScale::Times(1.0);
+
+    // Parses `uncapped`, or how many times real time: `4`, `0.5`, `x4`.
+    pub fn parse(s: &str) -> Result<TimeScale, String> {
+        if s == "uncapped" {
+            return Ok(TimeScale::Uncapped);
+        }
+
+        match s.trim_start_matches('x').parse::<f32>() {
+            Ok(times) if (MIN_TIMES..=MAX_TIMES).contains(&times) => Ok(TimeScale::Times(times)),
+            _ => Err(format!(
+                "expected 'uncapped' or {} to {} times real time, got '{}'",
+                MIN_TIMES, MAX_TIMES, s
+            )),
+        }
+    }
+}
+
+impl Default for TimeScale {
+    fn default() -> TimeScale {
+        TimeScale::NORMAL
+    }
+}
+
+// Spreads emulated frames over the frames shown, carrying fractions of a
+// frame over to the next one so that slow motion stays even.
+#[derive(Default)]
+pub struct Pacer {
+    owed: f32,
+}
+
+impl Pacer {
+    // Calls `frame` as many times as the time scale calls for in one frame
+    // shown. Returns how many times that was.
+    pub fn run<F: FnMut()>(&mut self, scale: TimeScale, mut frame: F) -> u32 {
+        let mut ran = 0;
+
+        match scale {
+            TimeScale::Times(times) => {
+                self.owed += times;
+                while self.owed >= 1.0 {
+                    frame();
+                    self.owed -= 1.0;
+                    ran += 1;
+                }
+            }
+            TimeScale::Uncapped => {
+                self.owed = 0.0;
+                let start = Instant::now();
+                while ran == 0 || start.elapsed() < UNCAPPED_BUDGET {
+                    frame();
+                    ran += 1;
+                }
+            }
+        }
+
+        ran
+    }
+}
+
+pub struct Host<'a> {
+    pub display: &'a mut dyn Display,
+    pub audio: &'a mut dyn Audio,
+    pub keypad: &'a mut dyn Keypad,
+    pub clock: &'a mut dyn Clock,
+}
+
+impl Host<'_> {
+    // Runs one frame: `speed` instructions, or a VIP frame's worth with the
+    // vip_timing quirk, then the timers tick once. Returns whether the screen
+    // was redrawn.
+    pub fn run_frame(&mut self, cpu: &mut Cpu, speed: u8) -> bool {
+        self.run_frame_with(cpu, speed, |_| {})
+    }
+
+    // Like run_frame, calling `before_step` ahead of every instruction.
+    pub fn run_frame_with<F: FnMut(&mut Cpu)>(
+        &mut self,
+        cpu: &mut Cpu,
+        speed: u8,
+        before_step: F,
+    ) -> bool {
+        cpu.set_keys(&self.keypad.keys());
+        cpu.run_frame(speed, before_step);
+        cpu.tick_timers();
+
+        self.audio.set_beeping(cpu.is_beeping());
+
+        let redrawn = cpu.take_draw_flag();
+        if redrawn {
+            self.display.draw(cpu.graphics());
+        }
+
+        self.clock.wait();
+        redrawn
+    }
+}
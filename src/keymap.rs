@@ -0,0 +1,265 @@
+use crate::gamepad::Button;
+use crate::keys::{key_name, parse_key};
+use crate::overlay;
+use chip8_rs::cpu::KEYPAD_SIZE;
+use chip8_rs::palette::Palette;
+
+use minifb::Key;
+
+use std::fmt;
+
+// The hex keypad as laid out on the COSMAC VIP. Layout presets put the same
+// shape on the keyboard, so `1` is always top left and `F` bottom right, apart
+// from A and B on the numpad.
+pub const KEYPAD_ROWS: [[u8; 4]; 4] = [
+    [0x1, 0x2, 0x3, 0xC],
+    [0x4, 0x5, 0x6, 0xD],
+    [0x7, 0x8, 0x9, 0xE],
+    [0xA, 0x0, 0xB, 0xF],
+];
+
+const LAYOUTS: [(&str, [[Key; 4]; 4]); 4] = [
+    (
+        "qwerty",
+        [
+            [Key::Key1, Key::Key2, Key::Key3, Key::Key4],
+            [Key::Q, Key::W, Key::E, Key::R],
+            [Key::A, Key::S, Key::D, Key::F],
+            [Key::Z, Key::X, Key::C, Key::V],
+        ],
+    ),
+    (
+        "azerty",
+        [
+            [Key::Key1, Key::Key2, Key::Key3, Key::Key4],
+            [Key::A, Key::Z, Key::E, Key::R],
+            [Key::Q, Key::S, Key::D, Key::F],
+            [Key::W, Key::X, Key::C, Key::V],
+        ],
+    ),
+    (
+        "dvorak",
+        [
+            [Key::Key1, Key::Key2, Key::Key3, Key::Key4],
+            [Key::Apostrophe, Key::Comma, Key::Period, Key::P],
+            [Key::A, Key::O, Key::E, Key::U],
+            [Key::Semicolon, Key::Q, Key::J, Key::K],
+        ],
+    ),
+    (
+        "numpad",
+        [
+            [Key::NumPad7, Key::NumPad8, Key::NumPad9, Key::NumPadMinus],
+            [Key::NumPad4, Key::NumPad5, Key::NumPad6, Key::NumPadPlus],
+            [Key::NumPad1, Key::NumPad2, Key::NumPad3, Key::NumPadEnter],
+            // The numpad's bottom row only has room for 0 and F, on 0 and
+            // the dot, so A and B go on / and * in the row above the digits,
+            // over 8 and 9, rather than either side of the 0.
+            [
+                Key::NumPadSlash,
+                Key::NumPad0,
+                Key::NumPadAsterisk,
+                Key::NumPadDot,
+            ],
+        ],
+    ),
+];
+
+// Every layout also puts the hex keypad's arrows on the D-pad, with 5 in the
+// middle on the main face button, which is what most games steer with.
+const GAMEPAD_BINDINGS: [(Button, u8); 5] = [
+    (Button::Up, 0x2),
+    (Button::Down, 0x8),
+    (Button::Left, 0x4),
+    (Button::Right, 0x6),
+    (Button::South, 0x5),
+];
+
+// Something that can hold down a CHIP-8 key: a keyboard key or a gamepad
+// button.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Binding {
+    Key(Key),
+    Button(Button),
+}
+
+impl Binding {
+    pub fn parse(name: &str) -> Option<Binding> {
+        Button::parse(name)
+            .map(Binding::Button)
+            .or_else(|| parse_key(name).map(Binding::Key))
+    }
+}
+
+impl fmt::Display for Binding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Binding::Key(key) => write!(f, "{}", key_name(*key)),
+            Binding::Button(button) => write!(f, "{}", button),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Keymap {
+    bindings: Vec<Vec<Binding>>,
+}
+
+impl Keymap {
+    pub fn layout(name: &str) -> Option<Keymap> {
+        let (_, layout) = LAYOUTS
+            .iter()
+            .find(|(layout, _)| layout.eq_ignore_ascii_case(name))?;
+
+        let mut keymap = Keymap {
+            bindings: vec![Vec::new(); KEYPAD_SIZE],
+        };
+
+        for (row, keys) in KEYPAD_ROWS.iter().zip(layout.iter()) {
+            for (&chip8_key, &key) in row.iter().zip(keys.iter()) {
+                keymap.bind(chip8_key, vec![Binding::Key(key)]);
+            }
+        }
+
+        for &(button, chip8_key) in GAMEPAD_BINDINGS.iter() {
+            keymap.bindings[chip8_key as usize].push(Binding::Button(button));
+        }
+
+        Some(keymap)
+    }
+
+    pub fn layout_names() -> Vec<&'static str> {
+        LAYOUTS.iter().map(|(name, _)| *name).collect()
+    }
+
+    pub fn bind(&mut self, chip8_key: u8, bindings: Vec<Binding>) {
+        self.bindings[chip8_key as usize] = bindings;
+    }
+
+    pub fn bindings(&self, chip8_key: u8) -> &[Binding] {
+        &self.bindings[chip8_key as usize]
+    }
+
+    // Which CHIP-8 keys are held, given a way to ask whether a key or button
+    // is down. Any of the bindings of a CHIP-8 key will do.
+    pub fn pressed<F: Fn(Binding) -> bool>(&self, is_down: F) -> [bool; KEYPAD_SIZE] {
+        let mut pressed = [false; KEYPAD_SIZE];
+
+        for (chip8_key, keys) in self.bindings.iter().enumerate() {
+            pressed[chip8_key] = keys.iter().any(|&key| is_down(key));
+        }
+
+        pressed
+    }
+
+    // Draws the hex keypad in its original shape, each key labelled with the
+    // keys and buttons bound to it and highlighted while held.
+    pub fn draw(
+        &self,
+        buffer: &mut [u32],
+        width: usize,
+        pressed: &[bool; KEYPAD_SIZE],
+        palette: Palette,
+    ) {
+        let height = buffer.len() / width;
+        let cell_width = width / 4;
+        let cell_height = height / 4;
+        let columns = cell_width / overlay::CELL_WIDTH - 1;
+
+        for (row, keys) in KEYPAD_ROWS.iter().enumerate() {
+            for (column, &chip8_key) in keys.iter().enumerate() {
+                let x = column * cell_width;
+                let y = row * cell_height;
+
+                let (background, foreground) = if pressed[chip8_key as usize] {
+                    (palette.on(), palette.off())
+                } else {
+                    (palette.off(), palette.on())
+                };
+
+                overlay::fill_rect(
+                    buffer,
+                    width,
+                    (x, y),
+                    (cell_width, cell_height),
+                    palette.on(),
+                );
+                overlay::fill_rect(
+                    buffer,
+                    width,
+                    (x + 1, y + 1),
+                    (cell_width - 2, cell_height - 2),
+                    background,
+                );
+
+                let names: Vec<String> = self
+                    .bindings(chip8_key)
+                    .iter()
+                    .map(|b| b.to_string())
+                    .collect();
+                let label = format!("{:X}: {}", chip8_key, names.join(" "));
+
+                for (line, text) in overlay::wrap(&label, columns)
+                    .iter()
+                    .take((cell_height - 2) / overlay::CELL_HEIGHT)
+                    .enumerate()
+                {
+                    overlay::draw_text(
+                        buffer,
+                        width,
+                        (x + 3, y + 3 + line * overlay::CELL_HEIGHT),
+                        text,
+                        foreground,
+                    );
+                }
+            }
+        }
+    }
+}
+
+impl Default for Keymap {
+    fn default() -> Keymap {
+        Keymap::layout(LAYOUTS[0].0).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The keys on the corners of the hex keypad, 1, C, A and F, for every
+    // layout.
+    const CORNERS: [(&str, [Key; 4]); 4] = [
+        ("qwerty", [Key::Key1, Key::Key4, Key::Z, Key::V]),
+        ("azerty", [Key::Key1, Key::Key4, Key::W, Key::V]),
+        ("dvorak", [Key::Key1, Key::Key4, Key::Semicolon, Key::K]),
+        (
+            "numpad",
+            [
+                Key::NumPad7,
+                Key::NumPadMinus,
+                Key::NumPadSlash,
+                Key::NumPadDot,
+            ],
+        ),
+    ];
+
+    #[test]
+    fn layouts_keep_the_keypad_the_right_way_up() {
+        assert_eq!(Keymap::layout_names().len(), CORNERS.len());
+
+        for (name, keys) in CORNERS.iter() {
+            let keymap = Keymap::layout(name).unwrap();
+
+            for (&chip8_key, &key) in [0x1, 0xC, 0xA, 0xF].iter().zip(keys.iter()) {
+                assert_eq!(
+                    keymap.bindings(chip8_key)[0],
+                    Binding::Key(key),
+                    "key {:X} of {}",
+                    chip8_key,
+                    name
+                );
+            }
+        }
+    }
+}
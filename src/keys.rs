@@ -0,0 +1,133 @@
+use minifb::Key;
+
+use Key::*;
+
+const ALL_KEYS: [Key; 106] = [
+    Key0,
+    Key1,
+    Key2,
+    Key3,
+    Key4,
+    Key5,
+    Key6,
+    Key7,
+    Key8,
+    Key9,
+    A,
+    B,
+    C,
+    D,
+    E,
+    F,
+    G,
+    H,
+    I,
+    J,
+    K,
+    L,
+    M,
+    N,
+    O,
+    P,
+    Q,
+    R,
+    S,
+    T,
+    U,
+    V,
+    W,
+    X,
+    Y,
+    Z,
+    F1,
+    F2,
+    F3,
+    F4,
+    F5,
+    F6,
+    F7,
+    F8,
+    F9,
+    F10,
+    F11,
+    F12,
+    F13,
+    F14,
+    F15,
+    Down,
+    Left,
+    Right,
+    Up,
+    Apostrophe,
+    Backquote,
+    Backslash,
+    Comma,
+    Equal,
+    LeftBracket,
+    Minus,
+    Period,
+    RightBracket,
+    Semicolon,
+    Slash,
+    Backspace,
+    Delete,
+    End,
+    Enter,
+    Escape,
+    Home,
+    Insert,
+    Menu,
+    PageDown,
+    PageUp,
+    Pause,
+    Space,
+    Tab,
+    NumLock,
+    CapsLock,
+    ScrollLock,
+    LeftShift,
+    RightShift,
+    LeftCtrl,
+    RightCtrl,
+    NumPad0,
+    NumPad1,
+    NumPad2,
+    NumPad3,
+    NumPad4,
+    NumPad5,
+    NumPad6,
+    NumPad7,
+    NumPad8,
+    NumPad9,
+    NumPadDot,
+    NumPadSlash,
+    NumPadAsterisk,
+    NumPadMinus,
+    NumPadPlus,
+    NumPadEnter,
+    LeftAlt,
+    RightAlt,
+    LeftSuper,
+    RightSuper,
+];
+
+// Key names are minifb's, compared case-insensitively. Digits may be given
+// without the `Key` prefix, so `1` and `Key1` are the same key.
+pub fn parse_key(name: &str) -> Option<Key> {
+    let name = name.trim();
+    let name = if name.len() == 1 && name.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("Key{}", name)
+    } else {
+        name.to_string()
+    };
+
+    ALL_KEYS
+        .iter()
+        .find(|key| format!("{:?}", key).eq_ignore_ascii_case(&name))
+        .copied()
+}
+
+pub fn key_name(key: Key) -> String {
+    let name = format!("{:?}", key);
+    name.strip_prefix("Key").map(String::from).unwrap_or(name)
+}
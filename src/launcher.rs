@@ -0,0 +1,298 @@
+use crate::cli::Options;
+use crate::config::Config;
+use crate::metadata::RomInfo;
+use crate::{overlay, rom_names, settings_for};
+use chip8_rs::cpu::*;
+use chip8_rs::host::Host;
+use chip8_rs::palette::Palette;
+
+use std::fs;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+
+pub const DEFAULT_ROM_DIRECTORY: &str = "roms";
+
+const ROM_EXTENSION: &str = "ch8";
+const PREVIEW_SCALE: usize = 2;
+
+pub struct RomEntry {
+    pub path: PathBuf,
+    pub info: RomInfo,
+}
+
+pub struct RomGroup {
+    pub name: String,
+    pub entries: Vec<RomEntry>,
+}
+
+enum Row {
+    Group(usize),
+    Rom(usize, usize),
+}
+
+// The selected ROM, running with the settings it would be played with.
+struct Preview {
+    cpu: Cpu,
+    speed: u8,
+}
+
+pub struct Launcher {
+    groups: Vec<RomGroup>,
+    rows: Vec<Row>,
+    selected: usize,
+    scroll: usize,
+    preview: Option<Preview>,
+    // The row the preview was started for, so it only starts once per
+    // selection.
+    previewed: Option<usize>,
+}
+
+impl Launcher {
+    pub fn new(directory: &str) -> Launcher {
+        let groups = scan(Path::new(directory));
+
+        let mut rows = Vec::new();
+        for (g, group) in groups.iter().enumerate() {
+            rows.push(Row::Group(g));
+            for e in 0..group.entries.len() {
+                rows.push(Row::Rom(g, e));
+            }
+        }
+
+        let mut launcher = Launcher {
+            groups,
+            rows,
+            selected: 0,
+            scroll: 0,
+            preview: None,
+            previewed: None,
+        };
+
+        launcher.select(0);
+        launcher
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.groups.is_empty()
+    }
+
+    pub fn selected(&self) -> Option<&RomEntry> {
+        match self.rows.get(self.selected) {
+            Some(Row::Rom(g, e)) => Some(&self.groups[*g].entries[*e]),
+            _ => None,
+        }
+    }
+
+    pub fn previous(&mut self) {
+        if let Some(row) = (0..self.selected).rev().find(|&r| self.is_rom(r)) {
+            self.select(row);
+        }
+    }
+
+    pub fn next(&mut self) {
+        if let Some(row) = (self.selected + 1..self.rows.len()).find(|&r| self.is_rom(r)) {
+            self.select(row);
+        }
+    }
+
+    fn is_rom(&self, row: usize) -> bool {
+        matches!(self.rows[row], Row::Rom(_, _))
+    }
+
+    fn select(&mut self, row: usize) {
+        self.selected = (row..self.rows.len())
+            .find(|&r| self.is_rom(r))
+            .unwrap_or(row);
+    }
+
+    // Runs the preview the same way the main loop runs a game, minus input,
+    // with the speed and quirks the config and options give the ROM. ROMs
+    // that crash just lose their preview.
+    pub fn tick(&mut self, config: &Config, options: &Options) {
+        if self.previewed != Some(self.selected) {
+            self.previewed = Some(self.selected);
+            self.preview = self
+                .selected()
+                .and_then(|entry| start_preview(entry, config, options));
+        }
+
+        if let Some(Preview { cpu, speed }) = &mut self.preview {
+            let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                Host {
+                    display: &mut (),
+                    audio: &mut (),
+                    keypad: &mut [false; KEYPAD_SIZE],
+                    clock: &mut (),
+                }
+                .run_frame(cpu, *speed)
+            }));
+
+            if result.is_err() {
+                self.preview = None;
+            }
+        }
+    }
+
+    pub fn draw(&mut self, buffer: &mut [u32], width: usize, palette: Palette) {
+        let (on, off) = (palette.on(), palette.off());
+        let height = buffer.len() / width;
+        overlay::fill_rect(buffer, width, (0, 0), (width, height), off);
+
+        let preview_width = GRAPHICS_WIDTH * PREVIEW_SCALE;
+        let preview_height = GRAPHICS_HEIGHT * PREVIEW_SCALE;
+        let preview_x = width - preview_width - overlay::CELL_WIDTH;
+        let preview_y = overlay::CELL_HEIGHT;
+
+        let list_columns = preview_x / overlay::CELL_WIDTH - 2;
+        let list_rows = height / overlay::CELL_HEIGHT - 1;
+
+        if self.selected < self.scroll {
+            self.scroll = self.selected.saturating_sub(1);
+        } else if self.selected >= self.scroll + list_rows {
+            self.scroll = self.selected + 1 - list_rows;
+        }
+
+        for (i, row) in self
+            .rows
+            .iter()
+            .enumerate()
+            .skip(self.scroll)
+            .take(list_rows)
+        {
+            let y = 2 + (i - self.scroll) * overlay::CELL_HEIGHT;
+
+            let text = match row {
+                Row::Group(g) => format!("{}/", self.groups[*g].name),
+                Row::Rom(g, e) => format!(" {}", self.groups[*g].entries[*e].info.title),
+            };
+            let text: String = text.chars().take(list_columns).collect();
+
+            if i == self.selected {
+                overlay::fill_rect(
+                    buffer,
+                    width,
+                    (0, y - 1),
+                    (preview_x - overlay::CELL_WIDTH, overlay::CELL_HEIGHT),
+                    on,
+                );
+                overlay::draw_text(buffer, width, (2, y), &text, off);
+            } else {
+                overlay::draw_text(buffer, width, (2, y), &text, on);
+            }
+        }
+
+        overlay::fill_rect(
+            buffer,
+            width,
+            (preview_x - 1, preview_y - 1),
+            (preview_width + 2, preview_height + 2),
+            on,
+        );
+
+        match &self.preview {
+            Some(Preview { cpu, .. }) => {
+                for y in 0..preview_height {
+                    for x in 0..preview_width {
+                        buffer[(preview_y + y) * width + preview_x + x] =
+                            if cpu.graphics()[y / PREVIEW_SCALE][x / PREVIEW_SCALE] {
+                                on
+                            } else {
+                                off
+                            };
+                    }
+                }
+            }
+            None => overlay::draw_text(
+                buffer,
+                width,
+                (preview_x + 2, preview_y + 2),
+                "NO PREVIEW",
+                off,
+            ),
+        }
+
+        if let Some(entry) = self.selected() {
+            let details_columns = preview_width / overlay::CELL_WIDTH;
+            let details = [
+                entry.info.title.clone(),
+                entry.info.author.clone().unwrap_or_default(),
+                entry.info.year.clone().unwrap_or_default(),
+            ];
+
+            for (i, line) in details
+                .iter()
+                .flat_map(|d| overlay::wrap(d, details_columns))
+                .enumerate()
+            {
+                overlay::draw_text(
+                    buffer,
+                    width,
+                    (
+                        preview_x,
+                        preview_y + preview_height + 3 + i * overlay::CELL_HEIGHT,
+                    ),
+                    &line,
+                    on,
+                );
+            }
+        }
+    }
+}
+
+// Each directory containing ROMs becomes a group, named after its path
+// relative to the scanned directory.
+fn start_preview(entry: &RomEntry, config: &Config, options: &Options) -> Option<Preview> {
+    let filename = entry.path.to_string_lossy();
+    let names = rom_names(&filename, &entry.info);
+    let names: Vec<&str> = names.iter().map(String::as_str).collect();
+    let settings = settings_for(config, options, &names).ok()?;
+
+    let mut cpu = Cpu::new();
+    cpu.set_quirks(settings.quirks);
+    cpu.set_load_address(options.load_address);
+    cpu.load_program(&filename).ok()?;
+
+    Some(Preview {
+        cpu,
+        speed: settings.speed,
+    })
+}
+
+fn scan(root: &Path) -> Vec<RomGroup> {
+    let mut groups = Vec::new();
+    scan_directory(root, root, &mut groups);
+    groups.sort_by(|a, b| a.name.cmp(&b.name));
+    groups
+}
+
+fn scan_directory(root: &Path, directory: &Path, groups: &mut Vec<RomGroup>) {
+    let mut paths: Vec<PathBuf> = match fs::read_dir(directory) {
+        Ok(entries) => entries.filter_map(|e| e.ok()).map(|e| e.path()).collect(),
+        Err(_) => return,
+    };
+    paths.sort();
+
+    let mut entries = Vec::new();
+
+    for path in paths {
+        if path.is_dir() {
+            scan_directory(root, &path, groups);
+        } else if path.extension().and_then(|e| e.to_str()) == Some(ROM_EXTENSION) {
+            entries.push(RomEntry {
+                info: RomInfo::from_path(&path.to_string_lossy()),
+                path,
+            });
+        }
+    }
+
+    if !entries.is_empty() {
+        let name = directory
+            .strip_prefix(root)
+            .ok()
+            .map(|p| p.to_string_lossy().into_owned())
+            .filter(|p| !p.is_empty())
+            .unwrap_or_else(|| String::from("."));
+
+        groups.push(RomGroup { name, entries });
+    }
+}
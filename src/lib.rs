@@ -0,0 +1,15 @@
+// The emulator core, free of any windowing or audio library so that other
+// frontends can be built around it. The chip8-rs binary is one of them.
+pub mod cheat;
+pub mod cpu;
+pub mod environment;
+pub mod filter;
+pub mod host;
+pub mod movie;
+mod opcode;
+pub mod palette;
+pub mod phosphor;
+pub mod quirks;
+pub mod reward;
+pub mod screenshot;
+mod timing;
@@ -1,124 +1,748 @@
-mod cpu;
-mod opcode;
+mod beeper;
+mod cheat_store;
+mod cli;
+mod config;
+mod export;
+mod gamepad;
+mod keymap;
+mod keys;
+mod launcher;
+mod menu;
+mod metadata;
+mod osd;
+mod overlay;
+mod recorder;
+mod script;
+mod session;
+#[cfg(unix)]
+mod terminal;
 
-use cpu::*;
+use beeper::Beeper;
+use cheat_store::{Cheats, Console};
+use chip8_rs::cheat::rom_crc32;
+use chip8_rs::cpu::*;
+use chip8_rs::filter::Filter;
+use chip8_rs::host::{Audio, Clock, FrameClock, Host, Pacer};
+use chip8_rs::movie::Movie;
+use chip8_rs::palette::Palette;
+use chip8_rs::phosphor::{Intensities, Phosphor};
+use chip8_rs::screenshot;
+use cli::{Command, Options};
+use config::{Config, Settings};
+use export::{AudioExport, VideoExport};
+use gamepad::GamepadBackend;
+use keymap::Binding;
+use launcher::Launcher;
+use menu::Menu;
+use metadata::RomInfo;
+use recorder::Recorder;
+use script::Script;
+use session::Session;
 
 extern crate minifb;
-use minifb::{Key, Scale, ScaleMode, Window, WindowOptions};
+use minifb::{Key, KeyRepeat, Scale, ScaleMode, Window, WindowOptions};
 
 use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
 
-const INITIAL_SPEED: u8 = 10;
-const MIN_SPEED: u8 = 1;
-const MAX_SPEED: u8 = 40;
+pub const INITIAL_SPEED: u8 = 10;
+pub const MIN_SPEED: u8 = 1;
+pub const MAX_SPEED: u8 = 40;
 
 const KEY_DEBOUNCE: u8 = 5;
 
+const BUFFER_SCALE: usize = 4;
+const BUFFER_WIDTH: usize = GRAPHICS_WIDTH * BUFFER_SCALE;
+const BUFFER_HEIGHT: usize = GRAPHICS_HEIGHT * BUFFER_SCALE;
+
+const HELP_COLUMNS: usize = BUFFER_WIDTH / overlay::CELL_WIDTH - 1;
+const HELP_ROWS: usize = BUFFER_HEIGHT / overlay::CELL_HEIGHT - 1;
+
 fn main() {
-    let mut cpu = Cpu::new();
+    let options = match cli::parse(env::args().skip(1)) {
+        Ok(Command::Help) => {
+            println!("{}", cli::USAGE);
+            return;
+        }
+        Ok(Command::Info(filename)) => {
+            print!("{}", RomInfo::from_path(&filename));
+            return;
+        }
+        Ok(Command::Run(options)) => *options,
+        Err(message) => {
+            eprintln!("error: {}\nRun with --help for usage.", message);
+            process::exit(2);
+        }
+    };
 
-    let filename = env::args()
-        .nth(1)
-        .expect("Pass a filename as first argument.");
+    let mut config = Config::load(options.config.as_deref()).unwrap_or_else(|e| fail(&e));
 
-    cpu.load_program(&filename);
-    cpu.dump_program();
+    if options.headless {
+        run_headless(&config, &options);
+        return;
+    }
+
+    if options.terminal {
+        run_terminal(&mut config, &options);
+        return;
+    }
 
-    let mut buffer: Vec<u32> = vec![0; GRAPHICS_WIDTH * GRAPHICS_HEIGHT];
+    let target = options
+        .target
+        .clone()
+        .unwrap_or_else(|| String::from(launcher::DEFAULT_ROM_DIRECTORY));
 
-    let mut speed = INITIAL_SPEED;
-    let mut key_debounce = 0;
+    let (width, height, scale) = match options.scale {
+        _ if options.fullscreen => (GRAPHICS_WIDTH, GRAPHICS_HEIGHT, Scale::FitScreen),
+        Some(scale) => (GRAPHICS_WIDTH * scale, GRAPHICS_HEIGHT * scale, Scale::X1),
+        None => (640, 320, Scale::FitScreen),
+    };
 
-    let mut window = Window::new(
-        &format!("CHIP-8 Emulator - speed {} - ESC to exit", speed),
-        640,
-        320,
+    let window = Window::new(
+        "CHIP-8 Emulator",
+        width,
+        height,
         WindowOptions {
             resize: true,
-            scale: Scale::FitScreen,
+            scale,
+            // The picture is drawn at the window's size, so this only shows
+            // for the frame a resize happens in.
             scale_mode: ScaleMode::AspectRatioStretch,
-            ..WindowOptions::default()
+            borderless: options.fullscreen,
+            title: !options.fullscreen,
         },
     )
-    .unwrap_or_else(|e| {
-        panic!("{}", e);
+    .unwrap_or_else(|e| fail(&e.to_string()));
+
+    let mut frontend = Frontend {
+        window,
+        buffer: vec![0; BUFFER_WIDTH * BUFFER_HEIGHT],
+        screen: Screen::default(),
+        gamepad: open_gamepad(&options),
+        audio: open_audio(&options),
+        clock: FrameClock::new(),
+        script: load_script(&options),
+        console: if options.cheat_console {
+            Some(Console::open())
+        } else {
+            None
+        },
+    };
+
+    let mut cpu = Cpu::new();
+    configure(&mut cpu, &options);
+
+    if !Path::new(&target).is_dir() {
+        play(&mut frontend, &mut cpu, &target, &mut config, &options);
+        return;
+    }
+
+    let mut launcher = Launcher::new(&target);
+    if launcher.is_empty() {
+        fail(&format!("no .ch8 files found in {}", target));
+    }
+
+    while let Some(filename) = browse(&mut frontend, &mut launcher, &config, &options) {
+        play(&mut frontend, &mut cpu, &filename, &mut config, &options);
+    }
+}
+
+// Everything the window frontend runs the emulator on.
+struct Frontend {
+    window: Window,
+    // What's shown, at 256x128, before it's scaled to the window.
+    buffer: Vec<u32>,
+    screen: Screen,
+    gamepad: Box<dyn GamepadBackend>,
+    audio: Box<dyn Audio>,
+    clock: FrameClock,
+    script: Option<Script>,
+    console: Option<Console>,
+}
+
+#[cfg(unix)]
+fn run_terminal(config: &mut Config, options: &Options) {
+    terminal::run(config, options).unwrap_or_else(|e| fail(&e.to_string()));
+}
+
+#[cfg(not(unix))]
+fn run_terminal(_: &mut Config, _: &Options) {
+    fail("--terminal is only supported on Unix");
+}
+
+fn open_audio(options: &Options) -> Box<dyn Audio> {
+    if options.mute {
+        return Box::new(());
+    }
+
+    match Beeper::new() {
+        Some(beeper) => Box::new(beeper),
+        None => {
+            eprintln!("No audio output device found, running without sound.");
+            Box::new(())
+        }
+    }
+}
+
+fn open_gamepad(options: &Options) -> Box<dyn GamepadBackend> {
+    if options.gamepad {
+        gamepad::default_backend()
+    } else {
+        Box::new(gamepad::VirtualGamepad::default())
+    }
+}
+
+fn load_script(options: &Options) -> Option<Script> {
+    options
+        .script
+        .as_ref()
+        .map(|path| Script::load(path).unwrap_or_else(|e| fail(&e)))
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("error: {}", message);
+    process::exit(1);
+}
+
+// Settings come from the built-in defaults, then the config file's defaults,
+// then its sections for this ROM, and finally the command line.
+fn settings_for(config: &Config, options: &Options, names: &[&str]) -> Result<Settings, String> {
+    let mut settings = config.settings(names)?;
+
+    if let Some(speed) = options.speed {
+        settings.speed = speed;
+    }
+
+    if let Some(quirks) = options.quirks {
+        settings.quirks = quirks;
+    }
+
+    if let Some(palette) = &options.palette {
+        settings.palette = settings.find_palette(palette)?;
+    }
+
+    if let Some(flicker) = options.flicker {
+        settings.flicker = flicker;
+    }
+
+    if let Some(filter) = options.filter {
+        settings.filter = filter;
+    }
+
+    if let Some(layout) = &options.layout {
+        settings.keymap = layout.clone();
+    }
+
+    Ok(settings)
+}
+
+fn rom_names(filename: &str, info: &RomInfo) -> Vec<String> {
+    let stem = PathBuf::from(filename)
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    vec![stem, info.title.clone()]
+}
+
+fn configure(cpu: &mut Cpu, options: &Options) {
+    cpu.set_load_address(options.load_address);
+
+    if let Some(seed) = options.seed {
+        cpu.seed(seed);
+    }
+
+    if options.debug {
+        cpu.toggle_debug();
+    }
+}
+
+fn load(cpu: &mut Cpu, filename: &str, options: &Options) {
+    if let Err(e) = cpu.load_program(filename) {
+        fail(&format!("can't load {}: {}", filename, e));
+    }
+
+    if options.dump {
+        cpu.dump_program();
+    }
+}
+
+// Runs the ROM at the configured speed without a window, until it either
+// reaches the requested number of frames or jumps to itself forever. With a
+// movie, it runs until the movie ends instead, as the movie was recorded.
+fn run_headless(config: &Config, options: &Options) {
+    let filename = options.target.as_ref().unwrap();
+    let names = rom_names(filename, &RomInfo::from_path(filename));
+    let names: Vec<&str> = names.iter().map(String::as_str).collect();
+    let mut settings = settings_for(config, options, &names).unwrap_or_else(|e| fail(&e));
+
+    let mut cpu = Cpu::new();
+    configure(&mut cpu, options);
+
+    let movie = options
+        .movie
+        .as_ref()
+        .map(|path| load_movie(path, filename).unwrap_or_else(|e| fail(&e)));
+    if let Some(movie) = &movie {
+        settings.speed = movie.speed;
+        settings.quirks = movie.quirks;
+        cpu.seed(movie.seed);
+        cpu.set_load_address(movie.load_address);
+    }
+    let mut movie_frames = movie.as_ref().map(Movie::frames);
+
+    cpu.set_quirks(settings.quirks);
+    load(&mut cpu, filename, options);
+    // Saved cheats weren't necessarily on when a movie was recorded, and
+    // would throw its playback off. Only those asked for here apply.
+    let cheats = if movie.is_some() {
+        Cheats::only(&options.cheats)
+    } else {
+        Cheats::load(filename, &options.cheats).unwrap_or_else(|e| fail(&e))
+    };
+
+    let mut script = load_script(options);
+    if let Some(script) = &mut script {
+        script
+            .start(&mut cpu, settings.palette)
+            .unwrap_or_else(|e| fail(&e));
+    }
+
+    let mut recorder = options
+        .record
+        .as_ref()
+        .map(|path| Recorder::start(path).unwrap_or_else(|e| fail(&e)));
+    // Recordings show the screen as a window would, flicker reduction
+    // included.
+    let mut phosphor = Phosphor::new(settings.flicker);
+
+    let mut video = options.video.as_ref().map(|path| {
+        VideoExport::create(path, settings.palette)
+            .unwrap_or_else(|e| fail(&format!("can't export to {}: {}", path.display(), e)))
+    });
+    let mut audio = options.audio.as_ref().map(|path| {
+        AudioExport::create(path)
+            .unwrap_or_else(|e| fail(&format!("can't export to {}: {}", path.display(), e)))
     });
+    let export_failed = |e: std::io::Error| fail(&format!("export failed: {}", e));
 
-    window.limit_update_rate(Some(std::time::Duration::from_micros(16600)));
+    let mut frames = 0;
+    while options.frames.is_none_or(|limit| frames < limit)
+        && (movie_frames.is_some() || !cpu.is_done())
+    {
+        let mut keys = [false; KEYPAD_SIZE];
+        if let Some(movie_frames) = &mut movie_frames {
+            let frame = match movie_frames.next() {
+                Some(frame) => frame,
+                None => break,
+            };
+            if frame.restart {
+                cpu.restart();
+            }
+            if let Some(speed) = frame.speed {
+                settings.speed = speed;
+            }
+            keys = frame.keys;
+        }
+        if let Some(script) = &script {
+            script.press_keys(&mut keys);
+        }
 
-    while window.is_open() && !window.is_key_down(Key::Escape) {
-        if key_debounce > 0 {
-            key_debounce -= 1;
+        cheats.apply(&mut cpu);
+        let mut host = Host {
+            display: &mut (),
+            audio: &mut (),
+            keypad: &mut keys,
+            clock: &mut (),
+        };
+        run_frame(&mut host, &mut cpu, settings.speed, &mut script);
+        frames += 1;
+
+        if let Some(recorder) = &mut recorder {
+            phosphor.update(cpu.graphics());
+            recorder
+                .capture(phosphor.intensities(), settings.palette)
+                .unwrap_or_else(|e| fail(&e));
+        }
+        if let Some(video) = &mut video {
+            video
+                .write_frame(cpu.graphics())
+                .unwrap_or_else(export_failed);
+        }
+        if let Some(audio) = &mut audio {
+            audio
+                .write_frame(cpu.is_beeping())
+                .unwrap_or_else(export_failed);
+        }
+    }
+
+    if let Some(recorder) = recorder {
+        recorder.finish().unwrap_or_else(|e| fail(&e));
+    }
+    if let Some(video) = video {
+        video.finish().unwrap_or_else(export_failed);
+    }
+    if let Some(audio) = audio {
+        audio.finish().unwrap_or_else(export_failed);
+    }
+    if let Some(path) = &options.screenshot {
+        let scale = options.scale.unwrap_or(1);
+        if settings.filter == Filter::default() {
+            screenshot::save(cpu.graphics(), settings.palette, scale, path)
+        } else {
+            screenshot::save_filtered(
+                cpu.graphics(),
+                settings.palette,
+                &settings.filter,
+                scale,
+                path,
+            )
+        }
+        .unwrap_or_else(|e| fail(&format!("can't save {}: {}", path.display(), e)));
+    }
+
+    println!("Ran {} frames", frames);
+}
+
+// Reads a movie, checking that it was recorded with this ROM.
+fn load_movie(path: &Path, filename: &str) -> Result<Movie, String> {
+    let movie = fs::read_to_string(path)
+        .map_err(|e| e.to_string())
+        .and_then(|text| Movie::parse(&text))
+        .map_err(|e| format!("{}: {}", path.display(), e))?;
+
+    let rom = fs::read(filename).map_err(|e| format!("can't read {}: {}", filename, e))?;
+    if rom_crc32(&rom) != movie.rom_crc32 {
+        return Err(format!(
+            "{} was recorded with a different ROM, CRC-32 {:08X}",
+            path.display(),
+            movie.rom_crc32
+        ));
+    }
+
+    Ok(movie)
+}
+
+// Shows the ROM list until one is picked with ENTER. Returns None when the
+// window is closed or ESC is pressed.
+fn browse(
+    frontend: &mut Frontend,
+    launcher: &mut Launcher,
+    config: &Config,
+    options: &Options,
+) -> Option<String> {
+    let Frontend {
+        window,
+        buffer,
+        screen,
+        clock,
+        ..
+    } = frontend;
+
+    let (palette, filter) = settings_for(config, options, &[])
+        .map(|settings| (settings.palette, settings.filter))
+        .unwrap_or_default();
+
+    window.set_title("CHIP-8 Emulator - UP/DOWN to select, ENTER to play, ESC to exit");
+
+    // ESC also leaves a running game, so ignore it until it has been let go.
+    let mut escape_released = !window.is_key_down(Key::Escape);
+
+    while window.is_open() {
+        if window.is_key_down(Key::Escape) {
+            if escape_released {
+                return None;
+            }
         } else {
-            if window.is_key_down(Key::F11) && speed > MIN_SPEED {
-                speed -= 1;
-                key_debounce = KEY_DEBOUNCE;
-                window.set_title(&format!("CHIP-8 Emulator - speed {} - ESC to exit", speed));
+            escape_released = true;
+        }
+
+        if window.is_key_pressed(Key::Up, KeyRepeat::Yes) {
+            launcher.previous();
+        }
+
+        if window.is_key_pressed(Key::Down, KeyRepeat::Yes) {
+            launcher.next();
+        }
+
+        if window.is_key_pressed(Key::Enter, KeyRepeat::No) {
+            if let Some(entry) = launcher.selected() {
+                return Some(entry.path.to_string_lossy().into_owned());
             }
+        }
+
+        launcher.tick(config, options);
+        launcher.draw(buffer, BUFFER_WIDTH, palette);
+        screen.show(window, buffer, &filter, true);
+        clock.wait();
+    }
+
+    None
+}
 
-            if window.is_key_down(Key::F12) && speed < MAX_SPEED {
-                speed += 1;
-                window.set_title(&format!("CHIP-8 Emulator - speed {} - ESC to exit", speed));
-                key_debounce = KEY_DEBOUNCE;
+fn play(
+    frontend: &mut Frontend,
+    cpu: &mut Cpu,
+    filename: &str,
+    config: &mut Config,
+    options: &Options,
+) {
+    let Frontend {
+        window,
+        buffer,
+        screen,
+        gamepad,
+        audio,
+        clock,
+        script,
+        console,
+    } = frontend;
+
+    let mut session = Session::start(cpu, filename, config, options);
+    let mut pacer = Pacer::default();
+    window.set_title(&session.title());
+
+    if let Some(script) = script {
+        script
+            .start(cpu, session.settings.palette)
+            .unwrap_or_else(|e| fail(&e));
+    }
+
+    while window.is_open() && !window.is_key_down(Key::Escape) {
+        let is_down = |key| window.is_key_down(key);
+        if session.handle_hotkeys(is_down, is_down, cpu, config, options) {
+            window.set_title(&session.title());
+        }
+
+        if window.is_key_pressed(session.settings.hotkeys.menu, KeyRepeat::No) {
+            session.menu = match session.menu {
+                Some(_) => None,
+                None => Some(Menu::default()),
+            };
+            cpu.redraw();
+            window.set_title(&session.title());
+        }
+
+        if let Some(mut menu) = session.menu.take() {
+            audio.set_beeping(false);
+            if menu.handle(
+                |key| window.is_key_pressed(key, KeyRepeat::Yes),
+                &mut session,
+                cpu,
+            ) {
+                session.menu = Some(menu);
+            } else {
+                cpu.redraw();
+                window.set_title(&session.title());
             }
 
-            if window.is_key_down(Key::F4) {
-                cpu.toggle_debug();
-                println!("Debug toggled");
-                key_debounce = KEY_DEBOUNCE;
+            let settings = &session.settings;
+            convert_graphics(session.phosphor.intensities(), buffer, settings.palette);
+            if let Some(menu) = &session.menu {
+                menu.draw(buffer, BUFFER_WIDTH, &session);
             }
+            session.osd.count_frame(0);
+            session.osd.needs_redraw(true);
+            session
+                .osd
+                .draw(buffer, BUFFER_WIDTH, session.settings.palette, true);
+            screen.show(window, buffer, &session.settings.filter, true);
+            clock.wait();
+            continue;
         }
 
-        if window.is_key_down(Key::F2) {
-            cpu.restart();
-        }
-
-        for _ in 0..speed {
-            cpu.reset_keys();
-            window.get_keys().map(|keys| {
-                for t in keys {
-                    match t {
-                        Key::X => cpu.set_key(0),
-                        Key::Key1 => cpu.set_key(1),
-                        Key::Key2 => cpu.set_key(2),
-                        Key::Key3 => cpu.set_key(3),
-                        Key::Q => cpu.set_key(4),
-                        Key::W => cpu.set_key(5),
-                        Key::E => cpu.set_key(6),
-                        Key::A => cpu.set_key(7),
-                        Key::S => cpu.set_key(8),
-                        Key::D => cpu.set_key(9),
-                        Key::Z => cpu.set_key(0xA),
-                        Key::C => cpu.set_key(0xB),
-                        Key::Key4 => cpu.set_key(0xC),
-                        Key::R => cpu.set_key(0xD),
-                        Key::F => cpu.set_key(0xE),
-                        Key::V => cpu.set_key(0xF),
-                        _ => (),
-                    }
-                }
-            });
-            cpu.steps(1);
-        }
-
-        if cpu.draw_flag {
-            convert_graphics(&mut cpu, &mut buffer);
-            cpu.draw_done();
-            window.update_with_buffer(&buffer, 64, 32).unwrap();
-        } else {
+        // Whatever the OSD showed last frame has to be drawn over too.
+        let osd_visible = session.osd.needs_redraw(session.paused);
+        if osd_visible {
+            cpu.redraw();
+        }
+
+        let settings = &session.settings;
+
+        if session.help_visible {
+            audio.set_beeping(false);
+            draw_help(
+                buffer,
+                &session.help[session.help_scroll..],
+                settings.palette,
+            );
+            screen.show(window, buffer, &settings.filter, true);
+            clock.wait();
+            continue;
+        }
+
+        for message in gamepad.poll() {
+            session.osd.show(message);
+        }
+        let mut pressed = settings.keymap.pressed(|binding| match binding {
+            Binding::Key(key) => window.is_key_down(key),
+            Binding::Button(button) => gamepad.is_down(button),
+        });
+
+        if let Some(console) = console {
+            console.poll(&mut session.cheats, cpu);
+        }
+
+        // Text a script showed last frame has to be drawn over.
+        if let Some(script) = script.as_mut() {
+            script.press_keys(&mut pressed);
+            script.set_palette(settings.palette);
+            if script.has_text() {
+                cpu.redraw();
+            }
+        }
+
+        // Paused, only a frame advance runs anything; otherwise the time
+        // scale decides how many frames go by before the screen is shown.
+        let (paused, advance) = (session.paused, session.take_advance());
+        let (speed, time_scale) = (session.speed, session.time_scale);
+        let (mut redrawn, instructions) = (false, cpu.instructions());
+        let mut emulate = || {
+            session.cheats.apply(cpu);
+            redrawn |= run_frame(
+                &mut Host {
+                    display: &mut (),
+                    audio: audio.as_mut(),
+                    keypad: &mut pressed,
+                    clock: &mut (),
+                },
+                cpu,
+                speed,
+                script,
+            );
+            // The screen may still be fading out when the game didn't draw.
+            redrawn |= session.phosphor.update(cpu.graphics());
+            session.capture();
+            session.record_input(&pressed);
+        };
+        match (paused, advance) {
+            (false, _) => {
+                pacer.run(time_scale, emulate);
+            }
+            (true, true) => emulate(),
+            (true, false) => {}
+        }
+        if paused {
+            audio.set_beeping(false);
+        }
+        session.osd.count_frame(cpu.instructions() - instructions);
+
+        // Overlays ask for a redraw to get drawn over, even when no frame ran.
+        let redrawn = cpu.take_draw_flag() || redrawn;
+        let settings = &session.settings;
+        if redrawn {
+            convert_graphics(session.phosphor.intensities(), buffer, settings.palette);
+        }
+
+        let scripted_text = match script.as_ref().filter(|script| script.has_text()) {
+            Some(script) => {
+                script.draw_text(buffer, BUFFER_WIDTH, settings.palette);
+                true
+            }
+            None => false,
+        };
+
+        if session.keypad_visible {
+            settings
+                .keymap
+                .draw(buffer, BUFFER_WIDTH, &pressed, settings.palette);
+        }
+
+        if osd_visible {
+            session
+                .osd
+                .draw(buffer, BUFFER_WIDTH, settings.palette, paused);
+        }
+
+        let changed = session.keypad_visible || redrawn || scripted_text || osd_visible;
+        screen.show(window, buffer, &settings.filter, changed);
+        clock.wait();
+    }
+
+    session.end(options);
+    audio.set_beeping(false);
+}
+
+// The buffer scaled up to the window's size, as it was last shown.
+#[derive(Default)]
+struct Screen {
+    pixels: Vec<u32>,
+    size: (usize, usize),
+    filter: Filter,
+}
+
+impl Screen {
+    // Shows the buffer in the window, drawing it again if it changed or the
+    // window has been resized.
+    fn show(&mut self, window: &mut Window, buffer: &[u32], filter: &Filter, changed: bool) {
+        let (width, height) = window.get_size();
+        if width == 0 || height == 0 {
+            window.update();
+            return;
+        }
+
+        if !changed && self.size == (width, height) && self.filter == *filter {
             window.update();
+            return;
         }
+
+        self.size = (width, height);
+        self.filter = *filter;
+        self.pixels.resize(width * height, 0);
+        filter.render(buffer, BUFFER_WIDTH, BUFFER_SCALE, &mut self.pixels, width);
+        window
+            .update_with_buffer(&self.pixels, width, height)
+            .unwrap();
     }
 }
 
-fn convert_graphics(cpu: &mut Cpu, buffer: &mut Vec<u32>) {
-    for y in 0..32 {
-        for x in 0..64 {
-            buffer[y * 64 + x] = if cpu.graphics[y as usize][x as usize] {
-                0x004e6563
-            } else {
-                0x00a0a293
-            }
+// Runs a frame with the script's callbacks, when there is a script.
+fn run_frame(host: &mut Host, cpu: &mut Cpu, speed: u8, script: &mut Option<Script>) -> bool {
+    let script = match script {
+        Some(script) => script,
+        None => return host.run_frame(cpu, speed),
+    };
+
+    let redrawn = if script.wants_instructions() {
+        host.run_frame_with(cpu, speed, |cpu| {
+            script.before_instruction(cpu).unwrap_or_else(|e| fail(&e))
+        })
+    } else {
+        host.run_frame(cpu, speed)
+    };
+
+    script.after_frame(cpu).unwrap_or_else(|e| fail(&e));
+    redrawn
+}
+
+fn draw_help(buffer: &mut [u32], lines: &[String], palette: Palette) {
+    overlay::fill_rect(
+        buffer,
+        BUFFER_WIDTH,
+        (0, 0),
+        (BUFFER_WIDTH, BUFFER_HEIGHT),
+        palette.on(),
+    );
+
+    for (row, line) in lines.iter().take(HELP_ROWS).enumerate() {
+        overlay::draw_text(
+            buffer,
+            BUFFER_WIDTH,
+            (overlay::CELL_WIDTH / 2, 2 + row * overlay::CELL_HEIGHT),
+            line,
+            palette.off(),
+        );
+    }
+}
+
+// Draws the screen into the window's buffer, for overlays to go on top.
+fn convert_graphics(intensities: &Intensities, buffer: &mut [u32], palette: Palette) {
+    for y in 0..BUFFER_HEIGHT {
+        for x in 0..BUFFER_WIDTH {
+            buffer[y * BUFFER_WIDTH + x] =
+                palette.blend(intensities[y / BUFFER_SCALE][x / BUFFER_SCALE]);
         }
     }
 }
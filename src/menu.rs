@@ -0,0 +1,210 @@
+use crate::overlay::{self, CELL_HEIGHT, CELL_WIDTH};
+use crate::session::Session;
+use chip8_rs::cpu::Cpu;
+use chip8_rs::filter::Filter;
+use chip8_rs::phosphor::Persistence;
+
+use minifb::Key;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Item {
+    Resume,
+    Speed,
+    Palette,
+    Filter,
+    Flicker,
+    Counters,
+    Keypad,
+    Restart,
+}
+
+const ITEMS: [Item; 8] = [
+    Item::Resume,
+    Item::Speed,
+    Item::Palette,
+    Item::Filter,
+    Item::Flicker,
+    Item::Counters,
+    Item::Keypad,
+    Item::Restart,
+];
+
+// What the filter and flicker items cycle through. Anything else set in the
+// config shows as custom until it's changed.
+const FILTERS: [&str; 5] = ["none", "smooth", "scanlines", "grid", "crt"];
+const FLICKER: [&str; 4] = ["off", "or", "decay", "max"];
+
+const TITLE: &str = "SETTINGS";
+const LABEL_COLUMNS: usize = 12;
+const VALUE_COLUMNS: usize = 16;
+const ROW_HEIGHT: usize = CELL_HEIGHT + 2;
+
+// A small settings menu over the paused game: UP/DOWN pick a setting,
+// LEFT/RIGHT change it and ENTER toggles it or picks the next value.
+#[derive(Default)]
+pub struct Menu {
+    selected: usize,
+}
+
+impl Menu {
+    // Acts on the keys pressed this frame. Returns whether the menu stays
+    // open.
+    pub fn handle<F: Fn(Key) -> bool>(
+        &mut self,
+        pressed: F,
+        session: &mut Session,
+        cpu: &mut Cpu,
+    ) -> bool {
+        if pressed(Key::Up) {
+            self.selected = cycle(ITEMS.len(), Some(self.selected), -1);
+        }
+
+        if pressed(Key::Down) {
+            self.selected = cycle(ITEMS.len(), Some(self.selected), 1);
+        }
+
+        let step = if pressed(Key::Left) {
+            -1
+        } else if pressed(Key::Right) || pressed(Key::Enter) {
+            1
+        } else {
+            return true;
+        };
+
+        match ITEMS[self.selected] {
+            Item::Resume => return !pressed(Key::Enter),
+            Item::Speed => {
+                session.step_speed(step);
+            }
+            Item::Palette => session.cycle_palette(step),
+            Item::Filter => {
+                let current = position(&FILTERS, |f| {
+                    Filter::parse(f) == Ok(session.settings.filter)
+                });
+                let next = FILTERS[cycle(FILTERS.len(), current, step)];
+                session.settings.filter = Filter::parse(next).expect("built-in filter");
+            }
+            Item::Flicker => {
+                let current = position(&FLICKER, |p| {
+                    Persistence::parse(p) == Ok(session.settings.flicker)
+                });
+                let next = FLICKER[cycle(FLICKER.len(), current, step)];
+                session.set_flicker(Persistence::parse(next).expect("built-in flicker reduction"));
+            }
+            Item::Counters => session.osd.counters_visible = !session.osd.counters_visible,
+            Item::Keypad => session.keypad_visible = !session.keypad_visible,
+            Item::Restart => {
+                if pressed(Key::Enter) {
+                    session.restart(cpu);
+                    return false;
+                }
+            }
+        }
+
+        true
+    }
+
+    // Draws the menu in the middle of the screen.
+    pub fn draw(&self, buffer: &mut [u32], width: usize, session: &Session) {
+        let palette = session.settings.palette;
+        let (on, off) = (palette.on(), palette.off());
+        let height = buffer.len() / width;
+
+        let box_width = (LABEL_COLUMNS + VALUE_COLUMNS) * CELL_WIDTH + 2 * CELL_WIDTH;
+        let box_height = (ITEMS.len() + 1) * ROW_HEIGHT + CELL_HEIGHT;
+        let (left, top) = (
+            width.saturating_sub(box_width) / 2,
+            height.saturating_sub(box_height) / 2,
+        );
+
+        overlay::fill_rect(buffer, width, (left, top), (box_width, box_height), on);
+        overlay::fill_rect(
+            buffer,
+            width,
+            (left + 1, top + 1),
+            (box_width - 2, box_height - 2),
+            off,
+        );
+
+        let x = left + CELL_WIDTH;
+        let title_x = left + (box_width - TITLE.len() * CELL_WIDTH) / 2;
+        overlay::draw_text(buffer, width, (title_x, top + CELL_HEIGHT / 2), TITLE, on);
+
+        for (i, &item) in ITEMS.iter().enumerate() {
+            let y = top + CELL_HEIGHT / 2 + (i + 1) * ROW_HEIGHT;
+            let text = format!(
+                "{:<label$}{}",
+                label(item),
+                value(item, session),
+                label = LABEL_COLUMNS
+            );
+
+            let color = if i == self.selected {
+                overlay::fill_rect(
+                    buffer,
+                    width,
+                    (x - 2, y - 1),
+                    (box_width - 2 * CELL_WIDTH + 4, ROW_HEIGHT - 1),
+                    on,
+                );
+                off
+            } else {
+                on
+            };
+            overlay::draw_text(buffer, width, (x, y), &text, color);
+        }
+    }
+}
+
+fn label(item: Item) -> &'static str {
+    match item {
+        Item::Resume => "Resume",
+        Item::Speed => "Speed",
+        Item::Palette => "Palette",
+        Item::Filter => "Filter",
+        Item::Flicker => "Flicker",
+        Item::Counters => "FPS counter",
+        Item::Keypad => "Keypad",
+        Item::Restart => "Restart",
+    }
+}
+
+fn value(item: Item, session: &Session) -> String {
+    let settings = &session.settings;
+    let on_off = |on: bool| String::from(if on { "on" } else { "off" });
+    let choice = |name: Option<&str>| format!("< {} >", name.unwrap_or("custom"));
+
+    match item {
+        Item::Resume | Item::Restart => String::new(),
+        Item::Speed => format!("< {} >", session.speed),
+        Item::Palette => choice(
+            settings
+                .palettes
+                .iter()
+                .find(|(_, palette)| *palette == settings.palette)
+                .map(|(name, _)| name.as_str()),
+        ),
+        Item::Filter => choice(
+            position(&FILTERS, |f| Filter::parse(f) == Ok(settings.filter)).map(|i| FILTERS[i]),
+        ),
+        Item::Flicker => choice(
+            position(&FLICKER, |p| Persistence::parse(p) == Ok(settings.flicker))
+                .map(|i| FLICKER[i]),
+        ),
+        Item::Counters => on_off(session.osd.counters_visible),
+        Item::Keypad => on_off(session.keypad_visible),
+    }
+}
+
+fn position<P: Fn(&str) -> bool>(names: &[&str], matches: P) -> Option<usize> {
+    names.iter().position(|name| matches(name))
+}
+
+// The index step places from current, wrapping around. Without a current
+// index, stepping either way starts from the first.
+fn cycle(len: usize, current: Option<usize>, step: isize) -> usize {
+    match current {
+        Some(current) => (current as isize + step).rem_euclid(len as isize) as usize,
+        None => 0,
+    }
+}
@@ -0,0 +1,275 @@
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const TAB_WIDTH: usize = 8;
+
+pub struct RomInfo {
+    pub title: String,
+    pub author: Option<String>,
+    pub year: Option<String>,
+    pub instructions: Option<String>,
+}
+
+impl RomInfo {
+    pub fn from_path(file_name: &str) -> RomInfo {
+        let path = Path::new(file_name);
+        let stem = path
+            .file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_else(|| String::from(file_name));
+
+        let mut info = RomInfo::from_file_name(&stem);
+
+        if let Ok(text) = fs::read_to_string(companion_path(path)) {
+            info.apply_companion(&text);
+        }
+
+        info
+    }
+
+    // Bundled ROMs follow the `Title (Year) [Author, Year]` naming convention,
+    // with every part except the title being optional and the year and
+    // credits in either order.
+    fn from_file_name(stem: &str) -> RomInfo {
+        let mut title = stem.trim();
+        let mut author = None;
+        let mut year = None;
+
+        loop {
+            // Only the last credits count.
+            let brackets = (title.rfind('['), title.ends_with(']'));
+            if let (Some(open), true, None) = (brackets.0, brackets.1, &author) {
+                let credits = &title[open + 1..title.len() - 1];
+                title = title[..open].trim_end();
+
+                let mut parts = credits.rsplitn(2, ',').map(str::trim);
+                match (parts.next(), parts.next()) {
+                    (Some(last), Some(first)) if is_year(last) => {
+                        author = Some(first.to_string());
+                        year = Some(last.to_string());
+                    }
+                    _ => author = Some(credits.trim().to_string()),
+                }
+                continue;
+            }
+
+            if let (Some(open), true) = (title.rfind('('), title.ends_with(')')) {
+                let inner = &title[open + 1..title.len() - 1];
+                if is_year(inner) {
+                    year.get_or_insert_with(|| inner.to_string());
+                    title = title[..open].trim_end();
+                    continue;
+                }
+            }
+
+            break;
+        }
+
+        RomInfo {
+            title: title.trim().to_string(),
+            author: author.filter(|a| !a.is_empty()),
+            year,
+            instructions: None,
+        }
+    }
+
+    // Companion files are free-form text, but some of them carry a
+    // `Key : Value` header block which is more precise than the file name.
+    fn apply_companion(&mut self, text: &str) {
+        for line in text.lines() {
+            let mut parts = line.splitn(2, ':');
+            let (key, value) = match (parts.next(), parts.next()) {
+                (Some(key), Some(value)) => (key.trim(), value.trim()),
+                _ => continue,
+            };
+
+            if value.is_empty() {
+                continue;
+            }
+
+            match key.to_lowercase().as_str() {
+                "title" => self.title = value.to_string(),
+                "author" => self.author = Some(value.to_string()),
+                "date" | "year" => {
+                    self.year = value.rsplit('/').next().map(|y| y.trim().to_string())
+                }
+                _ => (),
+            }
+        }
+
+        let instructions = text
+            .lines()
+            .map(|line| expand_tabs(line).trim_end().to_string())
+            .collect::<Vec<String>>()
+            .join("\n");
+
+        let instructions = instructions.trim_matches('\n');
+        if !instructions.trim().is_empty() {
+            self.instructions = Some(instructions.to_string());
+        }
+    }
+
+    pub fn window_title(&self) -> String {
+        let mut title = self.title.clone();
+
+        if let Some(author) = &self.author {
+            title.push_str(&format!(" by {}", author));
+        }
+
+        if let Some(year) = &self.year {
+            title.push_str(&format!(" ({})", year));
+        }
+
+        title
+    }
+}
+
+impl fmt::Display for RomInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Title:  {}", self.title)?;
+        writeln!(f, "Author: {}", self.author.as_deref().unwrap_or("unknown"))?;
+        writeln!(f, "Year:   {}", self.year.as_deref().unwrap_or("unknown"))?;
+
+        if let Some(instructions) = &self.instructions {
+            writeln!(f)?;
+            writeln!(f, "{}", instructions)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn companion_path(path: &Path) -> PathBuf {
+    path.with_extension("txt")
+}
+
+fn is_year(s: &str) -> bool {
+    s.len() == 4
+        && s.starts_with(|c: char| c.is_ascii_digit())
+        && s.chars().all(|c| c.is_ascii_digit() || c == 'x')
+}
+
+fn expand_tabs(line: &str) -> String {
+    let mut expanded = String::new();
+
+    for c in line.chars() {
+        if c == '\t' {
+            let pad = TAB_WIDTH - expanded.chars().count() % TAB_WIDTH;
+            expanded.push_str(&" ".repeat(pad));
+        } else {
+            expanded.push(c);
+        }
+    }
+
+    expanded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::env;
+
+    #[test]
+    fn file_names_give_title_author_and_year() {
+        let cases: [(&str, &str, Option<&str>, Option<&str>); 10] = [
+            ("Pong (1 player)", "Pong (1 player)", None, None),
+            (
+                "Animal Race [Brian Astle]",
+                "Animal Race",
+                Some("Brian Astle"),
+                None,
+            ),
+            (
+                "Hidden [David Winter, 1996]",
+                "Hidden",
+                Some("David Winter"),
+                Some("1996"),
+            ),
+            (
+                "Maze [David Winter, 199x]",
+                "Maze",
+                Some("David Winter"),
+                Some("199x"),
+            ),
+            (
+                "Trip8 Demo (2008) [Revival Studios]",
+                "Trip8 Demo",
+                Some("Revival Studios"),
+                Some("2008"),
+            ),
+            (
+                "Title [Author] (1990)",
+                "Title",
+                Some("Author"),
+                Some("1990"),
+            ),
+            (
+                "Title (1990) [Author, 1991]",
+                "Title",
+                Some("Author"),
+                Some("1991"),
+            ),
+            (
+                "Tetris [Fran Dachille, 1991] [alt]",
+                "Tetris [Fran Dachille, 1991]",
+                Some("alt"),
+                None,
+            ),
+            (
+                "Blinky [Hans Christian Egeberg, 1991",
+                "Blinky [Hans Christian Egeberg, 1991",
+                None,
+                None,
+            ),
+            ("  Spaced [] ", "Spaced", None, None),
+        ];
+
+        for (stem, title, author, year) in cases {
+            let info = RomInfo::from_file_name(stem);
+            assert_eq!(info.title, title, "{}", stem);
+            assert_eq!(info.author.as_deref(), author, "{}", stem);
+            assert_eq!(info.year.as_deref(), year, "{}", stem);
+        }
+    }
+
+    #[test]
+    fn companion_headers_override_the_file_name() {
+        let mut info = RomInfo::from_file_name("Maze [David Winter, 199x]");
+        info.apply_companion(
+            "Title : Maze\nAuthor: D. Winter\nDate  : 01/02/1994\nYear:\n\n\tPress 5.\n\n",
+        );
+
+        assert_eq!(info.author.as_deref(), Some("D. Winter"));
+        assert_eq!(info.year.as_deref(), Some("1994"));
+        assert_eq!(
+            info.instructions.as_deref(),
+            Some("Title : Maze\nAuthor: D. Winter\nDate  : 01/02/1994\nYear:\n\n        Press 5.")
+        );
+    }
+
+    #[test]
+    fn missing_or_unreadable_companions_leave_the_file_name() {
+        let directory = env::temp_dir().join(format!("chip8-rs-metadata-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let rom = |name: &str| directory.join(name).to_string_lossy().into_owned();
+
+        let info = RomInfo::from_path(&rom("Hidden [David Winter, 1996].ch8"));
+        assert_eq!(info.window_title(), "Hidden by David Winter (1996)");
+        assert!(info.instructions.is_none());
+
+        // Not UTF-8, so it can't be read as text.
+        fs::write(directory.join("Binary.txt"), [0xff, 0xfe, 0x00]).unwrap();
+        let info = RomInfo::from_path(&rom("Binary.ch8"));
+        assert_eq!(info.window_title(), "Binary");
+
+        // No headers and nothing but blank lines.
+        fs::write(directory.join("Blank.txt"), "\n  \n\t\n").unwrap();
+        let info = RomInfo::from_path(&rom("Blank [Someone].ch8"));
+        assert_eq!(info.author.as_deref(), Some("Someone"));
+        assert!(info.instructions.is_none());
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
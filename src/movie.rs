@@ -0,0 +1,222 @@
+// Input movies: the keys held in every frame of a run, with everything else
+// needed to play it back the same way, so that a run can be watched again or
+// exported long after it was played.
+//
+// Movies are text:
+//
+//     chip8-rs movie 1
+//     rom 841FDE23
+//     seed 8734651
+//     quirks none
+//     load-address 0x200
+//     speed 10
+//     120 0000
+//     14 0002
+//     speed 12
+//     restart
+//     ...
+//
+// The header names the ROM by CRC-32. After it, `<count> <keys>` lines hold
+// keys down for a number of frames, one bit per key with key 0 the lowest,
+// and `speed` and `restart` lines take effect before the next frame.
+use crate::cheat::rom_crc32;
+use crate::cpu::KEYPAD_SIZE;
+use crate::quirks::Quirks;
+
+use std::fmt::Write;
+
+const MAGIC: &str = "chip8-rs movie 1";
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Event {
+    Frames(u32, u16),
+    Speed(u8),
+    Restart,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Movie {
+    pub rom_crc32: u32,
+    pub seed: u64,
+    pub quirks: Quirks,
+    pub load_address: u16,
+    pub speed: u8,
+    events: Vec<Event>,
+}
+
+// What to do for one frame of a movie being played back.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct MovieFrame {
+    pub keys: [bool; KEYPAD_SIZE],
+    // The speed from this frame on, if it changed.
+    pub speed: Option<u8>,
+    // Whether the ROM restarts before this frame.
+    pub restart: bool,
+}
+
+impl Movie {
+    // An empty movie, to record a run of the ROM with these settings into.
+    // The CPU has to be seeded with the same seed.
+    pub fn new(rom: &[u8], seed: u64, quirks: Quirks, load_address: u16, speed: u8) -> Movie {
+        Movie {
+            rom_crc32: rom_crc32(rom),
+            seed,
+            quirks,
+            load_address,
+            speed,
+            events: Vec::new(),
+        }
+    }
+
+    pub fn record_frame(&mut self, keys: &[bool; KEYPAD_SIZE]) {
+        let keys = keys
+            .iter()
+            .enumerate()
+            .fold(0, |mask, (key, &down)| mask | (down as u16) << key);
+
+        match self.events.last_mut() {
+            Some(Event::Frames(count, last)) if *last == keys && *count < u32::MAX => *count += 1,
+            _ => self.events.push(Event::Frames(1, keys)),
+        }
+    }
+
+    pub fn record_speed(&mut self, speed: u8) {
+        self.events.push(Event::Speed(speed));
+    }
+
+    pub fn record_restart(&mut self) {
+        if self.events.last() != Some(&Event::Restart) {
+            self.events.push(Event::Restart);
+        }
+    }
+
+    pub fn len(&self) -> u64 {
+        self.events
+            .iter()
+            .map(|event| match event {
+                Event::Frames(count, _) => *count as u64,
+                _ => 0,
+            })
+            .sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // Every frame in order, for playing the movie back.
+    pub fn frames(&self) -> impl Iterator<Item = MovieFrame> + '_ {
+        let mut speed = None;
+        let mut restart = false;
+
+        self.events.iter().flat_map(move |&event| {
+            let (count, mask) = match event {
+                Event::Frames(count, mask) => (count, mask),
+                Event::Speed(new) => {
+                    speed = Some(new);
+                    (0, 0)
+                }
+                Event::Restart => {
+                    restart = true;
+                    (0, 0)
+                }
+            };
+
+            let mut keys = [false; KEYPAD_SIZE];
+            for (key, down) in keys.iter_mut().enumerate() {
+                *down = mask & (1 << key) != 0;
+            }
+
+            // Changes only apply to the first of the frames.
+            let first = MovieFrame {
+                keys,
+                speed: speed.take(),
+                restart: std::mem::take(&mut restart),
+            };
+            let rest = MovieFrame {
+                speed: None,
+                restart: false,
+                ..first
+            };
+
+            (0..count).map(move |i| if i == 0 { first } else { rest })
+        })
+    }
+
+    pub fn parse(text: &str) -> Result<Movie, String> {
+        let mut lines = text
+            .lines()
+            .enumerate()
+            .map(|(number, line)| (number + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty());
+
+        match lines.next() {
+            Some((_, MAGIC)) => {}
+            _ => return Err(format!("not a movie, expected '{}' first", MAGIC)),
+        }
+
+        let mut header = |name: &str| -> Result<String, String> {
+            match lines.next() {
+                Some((number, line)) => match line.split_once(' ') {
+                    Some((key, value)) if key == name => Ok(value.trim().to_string()),
+                    _ => Err(format!("line {}: expected {}", number, name)),
+                },
+                None => Err(format!("the movie ends before {}", name)),
+            }
+        };
+        let invalid = |name: &str, value: &str| format!("invalid {} '{}'", name, value);
+
+        let rom = header("rom")?;
+        let rom_crc32 = u32::from_str_radix(&rom, 16).map_err(|_| invalid("rom", &rom))?;
+        let seed = header("seed")?;
+        let seed = seed.parse().map_err(|_| invalid("seed", &seed))?;
+        let quirks = Quirks::parse(&header("quirks")?)?;
+        let load_address = header("load-address")?;
+        let load_address = u16::from_str_radix(load_address.trim_start_matches("0x"), 16)
+            .map_err(|_| invalid("load-address", &load_address))?;
+        let speed = header("speed")?;
+        let speed = speed.parse().map_err(|_| invalid("speed", &speed))?;
+
+        let mut events = Vec::new();
+        for (number, line) in lines {
+            let event = match line.split_once(' ') {
+                None if line == "restart" => Some(Event::Restart),
+                Some(("speed", speed)) => speed.parse().ok().map(Event::Speed),
+                Some((count, keys)) => count
+                    .parse()
+                    .ok()
+                    .zip(u16::from_str_radix(keys, 16).ok())
+                    .map(|(count, keys)| Event::Frames(count, keys)),
+                None => None,
+            };
+
+            events.push(event.ok_or_else(|| format!("line {}: can't read '{}'", number, line))?);
+        }
+
+        Ok(Movie {
+            rom_crc32,
+            seed,
+            quirks,
+            load_address,
+            speed,
+            events,
+        })
+    }
+
+    pub fn format(&self) -> String {
+        let mut text = format!(
+            "{}\nrom {:08X}\nseed {}\nquirks {}\nload-address {:#05x}\nspeed {}\n",
+            MAGIC, self.rom_crc32, self.seed, self.quirks, self.load_address, self.speed
+        );
+
+        for event in &self.events {
+            let _ = match event {
+                Event::Frames(count, keys) => writeln!(text, "{} {:04x}", count, keys),
+                Event::Speed(speed) => writeln!(text, "speed {}", speed),
+                Event::Restart => writeln!(text, "restart"),
+            };
+        }
+
+        text
+    }
+}
@@ -34,7 +34,7 @@ impl Opcode {
     }
 
     fn mnemonic(&self) -> String {
-        return match self.t() {
+        match self.t() {
             0x0000 => match self.opcode {
                 0x00e0 => String::from("CLR"),
                 0x00ee => String::from("RTS"),
@@ -82,7 +82,7 @@ impl Opcode {
                 _ => String::from("UNUS"),
             },
             _ => String::from("UNUS"),
-        };
+        }
     }
 }
 
@@ -0,0 +1,169 @@
+use crate::overlay::{self, CELL_HEIGHT, CELL_WIDTH};
+use chip8_rs::palette::Palette;
+
+use std::time::{Duration, Instant};
+
+// How long a message stays up.
+const MESSAGE_TIME: Duration = Duration::from_secs(2);
+// How often the counters are worked out again.
+const COUNTER_INTERVAL: Duration = Duration::from_secs(1);
+// Space between a label's text and its edges, and between labels and the
+// edges of the screen.
+const PADDING: usize = 1;
+const MARGIN: usize = 2;
+
+// The on-screen display: messages that go away after a while, counters of
+// frames and instructions per second, and the pause indicator, drawn over the
+// screen in the window.
+pub struct Osd {
+    message: Option<(String, Instant)>,
+    pub counters_visible: bool,
+    counters: Counters,
+    // Whether anything was drawn last frame, which has to be drawn over.
+    drawn: bool,
+}
+
+struct Counters {
+    since: Instant,
+    frames: u32,
+    instructions: u64,
+    fps: f64,
+    ips: u64,
+}
+
+impl Osd {
+    pub fn new() -> Osd {
+        Osd {
+            message: None,
+            counters_visible: false,
+            counters: Counters {
+                since: Instant::now(),
+                frames: 0,
+                instructions: 0,
+                fps: 0.0,
+                ips: 0,
+            },
+            drawn: false,
+        }
+    }
+
+    // Shows a message for a couple of seconds, in place of the last one.
+    pub fn show<S: Into<String>>(&mut self, message: S) {
+        self.message = Some((message.into(), Instant::now()));
+    }
+
+    // Counts a frame shown, which ran this many instructions.
+    pub fn count_frame(&mut self, instructions: u64) {
+        let counters = &mut self.counters;
+        counters.frames += 1;
+        counters.instructions += instructions;
+
+        let elapsed = counters.since.elapsed();
+        if elapsed >= COUNTER_INTERVAL {
+            counters.fps = counters.frames as f64 / elapsed.as_secs_f64();
+            counters.ips = (counters.instructions as f64 / elapsed.as_secs_f64()) as u64;
+            counters.since = Instant::now();
+            counters.frames = 0;
+            counters.instructions = 0;
+        }
+    }
+
+    // Whether the screen has to be drawn again this frame, to show the OSD
+    // or to get rid of what it showed last frame.
+    pub fn needs_redraw(&mut self, paused: bool) -> bool {
+        self.expire_message();
+        self.drawn || self.is_visible(paused)
+    }
+
+    // The OSD as a line of text, for the terminal.
+    pub fn status_line(&mut self, paused: bool) -> String {
+        self.expire_message();
+        let mut parts = Vec::new();
+
+        if paused {
+            parts.push(String::from("PAUSED"));
+        }
+        if let Some((message, _)) = &self.message {
+            parts.push(message.clone());
+        }
+        if self.counters_visible {
+            parts.push(self.counters_text());
+        }
+
+        parts.join("  ")
+    }
+
+    fn expire_message(&mut self) {
+        if self
+            .message
+            .as_ref()
+            .is_some_and(|(_, shown)| shown.elapsed() >= MESSAGE_TIME)
+        {
+            self.message = None;
+        }
+    }
+
+    fn counters_text(&self) -> String {
+        format!("{:.0} FPS {} IPS", self.counters.fps, self.counters.ips)
+    }
+
+    fn is_visible(&self, paused: bool) -> bool {
+        self.message.is_some() || self.counters_visible || paused
+    }
+
+    // Draws the pause indicator top left, the counters top right and the
+    // message along the bottom.
+    pub fn draw(&mut self, buffer: &mut [u32], width: usize, palette: Palette, paused: bool) {
+        self.drawn = self.is_visible(paused);
+        let height = buffer.len() / width;
+
+        if paused {
+            label(buffer, width, (MARGIN, MARGIN), "PAUSED", palette);
+        }
+
+        if self.counters_visible {
+            let text = self.counters_text();
+            let x = width.saturating_sub(label_width(&text) + MARGIN);
+            label(buffer, width, (x, MARGIN), &text, palette);
+        }
+
+        if let Some((message, _)) = &self.message {
+            let columns = (width - 2 * (MARGIN + PADDING)) / CELL_WIDTH;
+            let lines = overlay::wrap(message, columns);
+            let line_height = CELL_HEIGHT + 2 * PADDING;
+            let top = height.saturating_sub(lines.len() * line_height + MARGIN);
+
+            for (i, line) in lines.iter().enumerate() {
+                label(
+                    buffer,
+                    width,
+                    (MARGIN, top + i * line_height),
+                    line,
+                    palette,
+                );
+            }
+        }
+    }
+}
+
+fn label_width(text: &str) -> usize {
+    text.chars().count() * CELL_WIDTH - 1 + 2 * PADDING
+}
+
+// Text on a box of the on color, so that it stands out from the game.
+fn label(buffer: &mut [u32], width: usize, (x, y): (usize, usize), text: &str, palette: Palette) {
+    overlay::fill_rect(
+        buffer,
+        width,
+        (x, y),
+        (label_width(text), CELL_HEIGHT - 1 + 2 * PADDING),
+        palette.on(),
+    );
+    overlay::draw_text(
+        buffer,
+        width,
+        (x + PADDING, y + PADDING),
+        text,
+        palette.off(),
+    );
+}
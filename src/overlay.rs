@@ -0,0 +1,172 @@
+pub const GLYPH_WIDTH: usize = 3;
+pub const GLYPH_HEIGHT: usize = 5;
+pub const CELL_WIDTH: usize = GLYPH_WIDTH + 1;
+pub const CELL_HEIGHT: usize = GLYPH_HEIGHT + 1;
+
+// 3x5 glyphs for printable ASCII, one row per byte, most significant of the
+// three low bits being the leftmost pixel. Lowercase letters reuse uppercase.
+const FONT: [[u8; GLYPH_HEIGHT]; 95] = [
+    [0, 0, 0, 0, 0], // space
+    [2, 2, 2, 0, 2], // !
+    [5, 5, 0, 0, 0], // "
+    [5, 7, 5, 7, 5], // #
+    [3, 6, 2, 3, 6], // $
+    [5, 1, 2, 4, 5], // %
+    [2, 5, 2, 5, 3], // &
+    [2, 2, 0, 0, 0], // '
+    [1, 2, 2, 2, 1], // (
+    [4, 2, 2, 2, 4], // )
+    [0, 5, 2, 5, 0], // *
+    [0, 2, 7, 2, 0], // +
+    [0, 0, 0, 2, 4], // ,
+    [0, 0, 7, 0, 0], // -
+    [0, 0, 0, 0, 2], // .
+    [1, 1, 2, 4, 4], // /
+    [7, 5, 5, 5, 7], // 0
+    [2, 6, 2, 2, 7], // 1
+    [7, 1, 7, 4, 7], // 2
+    [7, 1, 3, 1, 7], // 3
+    [5, 5, 7, 1, 1], // 4
+    [7, 4, 7, 1, 7], // 5
+    [7, 4, 7, 5, 7], // 6
+    [7, 1, 1, 2, 2], // 7
+    [7, 5, 7, 5, 7], // 8
+    [7, 5, 7, 1, 7], // 9
+    [0, 2, 0, 2, 0], // :
+    [0, 2, 0, 2, 4], // ;
+    [1, 2, 4, 2, 1], // <
+    [0, 7, 0, 7, 0], // =
+    [4, 2, 1, 2, 4], // >
+    [7, 1, 3, 0, 2], // ?
+    [7, 5, 7, 4, 7], // @
+    [2, 5, 7, 5, 5], // A
+    [6, 5, 6, 5, 6], // B
+    [3, 4, 4, 4, 3], // C
+    [6, 5, 5, 5, 6], // D
+    [7, 4, 6, 4, 7], // E
+    [7, 4, 6, 4, 4], // F
+    [3, 4, 5, 5, 3], // G
+    [5, 5, 7, 5, 5], // H
+    [7, 2, 2, 2, 7], // I
+    [1, 1, 1, 5, 2], // J
+    [5, 5, 6, 5, 5], // K
+    [4, 4, 4, 4, 7], // L
+    [5, 7, 7, 5, 5], // M
+    [6, 5, 5, 5, 5], // N
+    [2, 5, 5, 5, 2], // O
+    [6, 5, 6, 4, 4], // P
+    [2, 5, 5, 6, 3], // Q
+    [6, 5, 6, 5, 5], // R
+    [3, 4, 2, 1, 6], // S
+    [7, 2, 2, 2, 2], // T
+    [5, 5, 5, 5, 7], // U
+    [5, 5, 5, 5, 2], // V
+    [5, 5, 7, 7, 5], // W
+    [5, 5, 2, 5, 5], // X
+    [5, 5, 2, 2, 2], // Y
+    [7, 1, 2, 4, 7], // Z
+    [6, 4, 4, 4, 6], // [
+    [4, 4, 2, 1, 1], // \
+    [3, 1, 1, 1, 3], // ]
+    [2, 5, 0, 0, 0], // ^
+    [0, 0, 0, 0, 7], // _
+    [4, 2, 0, 0, 0], // `
+    [2, 5, 7, 5, 5], // a
+    [6, 5, 6, 5, 6], // b
+    [3, 4, 4, 4, 3], // c
+    [6, 5, 5, 5, 6], // d
+    [7, 4, 6, 4, 7], // e
+    [7, 4, 6, 4, 4], // f
+    [3, 4, 5, 5, 3], // g
+    [5, 5, 7, 5, 5], // h
+    [7, 2, 2, 2, 7], // i
+    [1, 1, 1, 5, 2], // j
+    [5, 5, 6, 5, 5], // k
+    [4, 4, 4, 4, 7], // l
+    [5, 7, 7, 5, 5], // m
+    [6, 5, 5, 5, 5], // n
+    [2, 5, 5, 5, 2], // o
+    [6, 5, 6, 4, 4], // p
+    [2, 5, 5, 6, 3], // q
+    [6, 5, 6, 5, 5], // r
+    [3, 4, 2, 1, 6], // s
+    [7, 2, 2, 2, 2], // t
+    [5, 5, 5, 5, 7], // u
+    [5, 5, 5, 5, 2], // v
+    [5, 5, 7, 7, 5], // w
+    [5, 5, 2, 5, 5], // x
+    [5, 5, 2, 2, 2], // y
+    [7, 1, 2, 4, 7], // z
+    [3, 2, 6, 2, 3], // {
+    [2, 2, 2, 2, 2], // |
+    [6, 2, 3, 2, 6], // }
+    [0, 3, 6, 0, 0], // ~
+];
+
+pub fn fill_rect(
+    buffer: &mut [u32],
+    width: usize,
+    (x, y): (usize, usize),
+    (w, h): (usize, usize),
+    color: u32,
+) {
+    let height = buffer.len() / width;
+
+    for row in y..(y + h).min(height) {
+        for col in x..(x + w).min(width) {
+            buffer[row * width + col] = color;
+        }
+    }
+}
+
+pub fn draw_text(buffer: &mut [u32], width: usize, (x, y): (usize, usize), text: &str, color: u32) {
+    let height = buffer.len() / width;
+
+    for (i, c) in text.chars().enumerate() {
+        let glyph = match c {
+            ' '..='~' => &FONT[c as usize - ' ' as usize],
+            _ => &FONT['?' as usize - ' ' as usize],
+        };
+
+        for (row, bits) in glyph.iter().enumerate() {
+            for col in 0..GLYPH_WIDTH {
+                if (bits >> (GLYPH_WIDTH - 1 - col)) & 0x1 == 0 {
+                    continue;
+                }
+
+                let px = x + i * CELL_WIDTH + col;
+                let py = y + row;
+                if px < width && py < height {
+                    buffer[py * width + px] = color;
+                }
+            }
+        }
+    }
+}
+
+pub fn wrap(text: &str, columns: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+
+    for paragraph in text.lines() {
+        let mut line = String::new();
+        let mut fresh = true;
+
+        for word in paragraph.split(' ') {
+            if !fresh && line.chars().count() + 1 + word.chars().count() > columns {
+                lines.push(line);
+                line = String::new();
+                fresh = true;
+            }
+
+            if !fresh {
+                line.push(' ');
+            }
+            line.push_str(word);
+            fresh = false;
+        }
+
+        lines.push(line);
+    }
+
+    lines
+}
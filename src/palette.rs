@@ -0,0 +1,177 @@
+// Colors for the screen, looked up by a pixel's value: 0 is off and 1 is on.
+// XO-CHIP draws on two planes, which makes a pixel's value 0 to 3, and there's
+// room for the 16 values four planes would give. A pixel past the end of a
+// palette is drawn in its on color.
+pub const MAX_COLORS: usize = 16;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Palette {
+    colors: [u32; MAX_COLORS],
+    len: usize,
+}
+
+const PRESETS: [(&str, Palette); 8] = [
+    ("lcd", preset(&[0x00a0a293, 0x004e6563])),
+    ("mono", preset(&[0x00000000, 0x00ffffff])),
+    ("green", preset(&[0x00002200, 0x0033ff66])),
+    ("amber", preset(&[0x00201000, 0x00ffb000])),
+    (
+        "gameboy",
+        preset(&[0x009bbc0f, 0x000f380f, 0x008bac0f, 0x00306230]),
+    ),
+    (
+        "high-contrast",
+        preset(&[0x00000000, 0x00ffff00, 0x0000ffff, 0x00ffffff]),
+    ),
+    // Octo's colors, which most XO-CHIP games were made with.
+    (
+        "xo-chip",
+        preset(&[0x00996600, 0x00ffcc00, 0x00ff6600, 0x00662200]),
+    ),
+    (
+        "xo-chip-16",
+        preset(&[
+            0x00000000, 0x00ffffff, 0x00ff004d, 0x0029adff, 0x0000e436, 0x00ffec27, 0x00ab5236,
+            0x00ff77a8, 0x007e2553, 0x001d2b53, 0x00008751, 0x005f574f, 0x00c2c3c7, 0x00fff1e8,
+            0x00ffa300, 0x0083769c,
+        ]),
+    ),
+];
+
+const fn preset(colors: &[u32]) -> Palette {
+    let mut palette = Palette {
+        colors: [0; MAX_COLORS],
+        len: colors.len(),
+    };
+
+    let mut i = 0;
+    while i < colors.len() {
+        palette.colors[i] = colors[i];
+        i += 1;
+    }
+
+    palette
+}
+
+impl Palette {
+    // A palette of 2 to 16 colors, 0x00RRGGBB, off first.
+    pub fn new(colors: &[u32]) -> Result<Palette, String> {
+        if !(2..=MAX_COLORS).contains(&colors.len()) {
+            return Err(format!(
+                "a palette has 2 to {} colors, got {}",
+                MAX_COLORS,
+                colors.len()
+            ));
+        }
+
+        Ok(preset(colors))
+    }
+
+    pub fn named(name: &str) -> Option<Palette> {
+        PRESETS
+            .iter()
+            .find(|(preset, _)| *preset == name)
+            .map(|(_, palette)| *palette)
+    }
+
+    pub fn names() -> Vec<&'static str> {
+        PRESETS.iter().map(|(name, _)| *name).collect()
+    }
+
+    // Every preset, in the order they're cycled through.
+    pub fn presets() -> impl Iterator<Item = (&'static str, Palette)> {
+        PRESETS.iter().copied()
+    }
+
+    // Parses a preset's name or a list of colors.
+    pub fn parse(s: &str) -> Result<Palette, String> {
+        match Palette::named(s) {
+            Some(palette) => Ok(palette),
+            None if is_list(s) => Palette::parse_colors(s),
+            None => Err(format!(
+                "unknown palette '{}', expected one of: {}, or a list of colors",
+                s,
+                Palette::names().join(", ")
+            )),
+        }
+    }
+
+    // Parses colors separated by commas or spaces, off first, e.g.
+    // `9bbc0f, 0f380f, 8bac0f, 306230`.
+    pub fn parse_colors(s: &str) -> Result<Palette, String> {
+        let colors = s
+            .split(separator)
+            .filter(|color| !color.is_empty())
+            .map(parse_color)
+            .collect::<Result<Vec<u32>, String>>()?;
+
+        Palette::new(&colors)
+    }
+
+    pub fn off(&self) -> u32 {
+        self.colors[0]
+    }
+
+    pub fn on(&self) -> u32 {
+        self.colors[1]
+    }
+
+    // The color of a pixel with this value.
+    pub fn color(&self, value: usize) -> u32 {
+        if value < self.len {
+            self.colors[value]
+        } else {
+            self.on()
+        }
+    }
+
+    pub fn colors(&self) -> &[u32] {
+        &self.colors[..self.len]
+    }
+
+    // Replaces one of the palette's colors; values past its end are ignored.
+    pub fn set(&mut self, value: usize, color: u32) {
+        if value < self.len {
+            self.colors[value] = color;
+        }
+    }
+
+    // The color between off and on for a pixel of this brightness, from 0
+    // for off to 255 for on.
+    pub fn blend(&self, intensity: u8) -> u32 {
+        let (off, on) = (self.off().to_be_bytes(), self.on().to_be_bytes());
+        let mut color = [0; 4];
+
+        for i in 1..4 {
+            let (off, on) = (off[i] as u32, on[i] as u32);
+            color[i] = ((off * (255 - intensity as u32) + on * intensity as u32) / 255) as u8;
+        }
+
+        u32::from_be_bytes(color)
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Palette {
+        PRESETS[0].1
+    }
+}
+
+// A color written as RRGGBB, optionally after a `#` or `0x`.
+pub fn parse_color(value: &str) -> Result<u32, String> {
+    let hex = value.trim_start_matches('#').trim_start_matches("0x");
+
+    u32::from_str_radix(hex, 16)
+        .ok()
+        .filter(|_| hex.len() == 6)
+        .ok_or_else(|| format!("colors are written as RRGGBB, got '{}'", value))
+}
+
+// Whether a palette is written as its colors rather than a name.
+pub fn is_list(s: &str) -> bool {
+    s.contains(separator)
+}
+
+fn separator(c: char) -> bool {
+    c == ',' || c.is_whitespace()
+}
@@ -0,0 +1,126 @@
+// Flicker reduction. CHIP-8 games move sprites by XOR-ing them off and back
+// on, so a frame often catches a sprite half drawn or not at all. Keeping
+// pixels lit for a while after they go off, the way the phosphor of an old
+// screen does, hides most of that.
+use crate::cpu::{Graphics, GRAPHICS_HEIGHT, GRAPHICS_WIDTH};
+
+use std::collections::VecDeque;
+
+// How bright every pixel is, from 0 for off to 255 for fully lit.
+pub type Intensities = [[u8; GRAPHICS_WIDTH]; GRAPHICS_HEIGHT];
+
+pub const FULL: u8 = u8::MAX;
+
+const DEFAULT_DECAY: f32 = 0.5;
+const DEFAULT_MAX_FRAMES: usize = 3;
+// More than a second of frames is a smear, not a screen.
+const MAX_FRAMES: usize = 60;
+
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub enum Persistence {
+    // Every frame as it is.
+    #[default]
+    Off,
+    // Pixels fade out after they go off, keeping this fraction of their
+    // brightness every frame.
+    Decay(f32),
+    // Pixels lit in this frame or the one before.
+    Or,
+    // Pixels lit in any of this many frames.
+    Max(usize),
+}
+
+pub const PERSISTENCE_NAMES: [&str; 4] = ["off", "decay[:KEEP]", "or", "max[:FRAMES]"];
+
+impl Persistence {
+    // Parses `off`, `or`, `decay`, `decay:0.7`, `max` or `max:4`.
+    pub fn parse(s: &str) -> Result<Persistence, String> {
+        let (name, argument) = match s.split_once(':') {
+            Some((name, argument)) => (name, Some(argument)),
+            None => (s, None),
+        };
+
+        match (name, argument) {
+            ("off", None) => Ok(Persistence::Off),
+            ("or", None) => Ok(Persistence::Or),
+            ("decay", None) => Ok(Persistence::Decay(DEFAULT_DECAY)),
+            ("decay", Some(keep)) => match keep.parse::<f32>() {
+                Ok(keep) if (0.0..1.0).contains(&keep) => Ok(Persistence::Decay(keep)),
+                _ => Err(format!(
+                    "decay keeps a fraction from 0 up to 1 of a pixel's brightness, got '{}'",
+                    keep
+                )),
+            },
+            ("max", None) => Ok(Persistence::Max(DEFAULT_MAX_FRAMES)),
+            ("max", Some(frames)) => match frames.parse::<usize>() {
+                Ok(frames) if (1..=MAX_FRAMES).contains(&frames) => Ok(Persistence::Max(frames)),
+                _ => Err(format!(
+                    "max takes 1 to {} frames, got '{}'",
+                    MAX_FRAMES, frames
+                )),
+            },
+            _ => Err(format!(
+                "unknown flicker reduction '{}', expected one of: {}",
+                s,
+                PERSISTENCE_NAMES.join(", ")
+            )),
+        }
+    }
+
+    fn frames(self) -> usize {
+        match self {
+            Persistence::Or => 2,
+            Persistence::Max(frames) => frames,
+            _ => 1,
+        }
+    }
+}
+
+// What the screen looks like with persistence, updated once a frame.
+pub struct Phosphor {
+    persistence: Persistence,
+    history: VecDeque<Graphics>,
+    intensities: Intensities,
+}
+
+impl Phosphor {
+    pub fn new(persistence: Persistence) -> Phosphor {
+        Phosphor {
+            persistence,
+            history: VecDeque::new(),
+            intensities: [[0; GRAPHICS_WIDTH]; GRAPHICS_HEIGHT],
+        }
+    }
+
+    pub fn set_persistence(&mut self, persistence: Persistence) {
+        self.persistence = persistence;
+        self.history.clear();
+    }
+
+    // Adds the frame that just ran. Returns whether anything changed.
+    pub fn update(&mut self, graphics: &Graphics) -> bool {
+        self.history.push_front(*graphics);
+        self.history.truncate(self.persistence.frames());
+
+        let mut changed = false;
+        for y in 0..GRAPHICS_HEIGHT {
+            for x in 0..GRAPHICS_WIDTH {
+                let previous = self.intensities[y][x];
+                let intensity = match self.persistence {
+                    Persistence::Decay(keep) if !graphics[y][x] => (previous as f32 * keep) as u8,
+                    _ if self.history.iter().any(|frame| frame[y][x]) => FULL,
+                    _ => 0,
+                };
+
+                changed |= intensity != previous;
+                self.intensities[y][x] = intensity;
+            }
+        }
+
+        changed
+    }
+
+    pub fn intensities(&self) -> &Intensities {
+        &self.intensities
+    }
+}
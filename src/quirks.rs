@@ -0,0 +1,118 @@
+use std::fmt;
+
+// Behaviours that differ between CHIP-8 interpreters. The defaults match what
+// this emulator has always done; `vip` is the original COSMAC VIP interpreter.
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub struct Quirks {
+    // 8XY6 and 8XYE shift VY into VX instead of shifting VX in place.
+    pub shift_vy: bool,
+    // FX55 and FX65 leave I pointing past the last register stored or read.
+    pub memory_increment: bool,
+    // BNNN jumps to NNN + VX, where X is the high nibble of NNN.
+    pub jump_vx: bool,
+    // 8XY1, 8XY2 and 8XY3 reset VF to zero.
+    pub vf_reset: bool,
+    // Sprites are cut off at the screen edges instead of wrapping around.
+    pub clip: bool,
+    // FX0A waits for the key to be released, not just pressed.
+    pub key_release: bool,
+    // Instructions take as long as they did on the COSMAC VIP, and a frame
+    // runs as many as fit in 1/60 s there, whatever the speed. DXYN waits for
+    // the next frame before drawing.
+    pub vip_timing: bool,
+}
+
+pub const QUIRK_NAMES: [&str; 7] = [
+    "shift-vy",
+    "memory-increment",
+    "jump-vx",
+    "vf-reset",
+    "clip",
+    "key-release",
+    "vip-timing",
+];
+pub const PRESET_NAMES: [&str; 3] = ["none", "vip", "schip"];
+
+impl Quirks {
+    pub fn vip() -> Quirks {
+        Quirks {
+            shift_vy: true,
+            memory_increment: true,
+            jump_vx: false,
+            vf_reset: true,
+            clip: true,
+            key_release: true,
+            vip_timing: false,
+        }
+    }
+
+    pub fn schip() -> Quirks {
+        Quirks {
+            shift_vy: false,
+            memory_increment: false,
+            jump_vx: true,
+            vf_reset: false,
+            clip: true,
+            key_release: false,
+            vip_timing: false,
+        }
+    }
+
+    // Parses a comma separated list of quirk and preset names, e.g.
+    // `vip` or `shift-vy,clip`. Later entries add to earlier ones.
+    pub fn parse(list: &str) -> Result<Quirks, String> {
+        let mut quirks = Quirks::default();
+
+        for name in list.split(',').map(str::trim).filter(|n| !n.is_empty()) {
+            match name {
+                "none" => quirks = Quirks::default(),
+                "vip" => quirks = Quirks::vip(),
+                "schip" => quirks = Quirks::schip(),
+                "shift-vy" => quirks.shift_vy = true,
+                "memory-increment" => quirks.memory_increment = true,
+                "jump-vx" => quirks.jump_vx = true,
+                "vf-reset" => quirks.vf_reset = true,
+                "clip" => quirks.clip = true,
+                "key-release" => quirks.key_release = true,
+                "vip-timing" => quirks.vip_timing = true,
+                _ => {
+                    return Err(format!(
+                        "unknown quirk '{}', expected one of: {}, {}",
+                        name,
+                        PRESET_NAMES.join(", "),
+                        QUIRK_NAMES.join(", ")
+                    ))
+                }
+            }
+        }
+
+        Ok(quirks)
+    }
+}
+
+// The list parse() reads back: every quirk that's on, or `none`.
+impl fmt::Display for Quirks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let on = [
+            self.shift_vy,
+            self.memory_increment,
+            self.jump_vx,
+            self.vf_reset,
+            self.clip,
+            self.key_release,
+            self.vip_timing,
+        ];
+        let names: Vec<&str> = QUIRK_NAMES
+            .iter()
+            .zip(on.iter())
+            .filter(|(_, &on)| on)
+            .map(|(&name, _)| name)
+            .collect();
+
+        if names.is_empty() {
+            write!(f, "none")
+        } else {
+            write!(f, "{}", names.join(","))
+        }
+    }
+}
@@ -0,0 +1,255 @@
+use chip8_rs::cpu::{GRAPHICS_HEIGHT, GRAPHICS_WIDTH};
+use chip8_rs::palette::Palette;
+use chip8_rs::phosphor::Intensities;
+
+use std::fs::File;
+use std::io::BufWriter;
+use std::iter;
+use std::path::{Path, PathBuf};
+
+// Recordings are made at this multiple of 64x32, since viewers tend to show
+// small animations at their actual size.
+const RECORDING_SCALE: usize = 4;
+
+const FRAMES_PER_SECOND: u64 = 60;
+
+// GIF delays are in hundredths of a second, and most viewers play anything
+// shorter than two of them far slower than asked.
+const GIF_MIN_DELAY: u64 = 2;
+
+// Records every frame shown, at 60 frames per second, to an animated GIF or
+// APNG depending on the file's extension. Frames that don't change the
+// screen only make the previous one last longer.
+pub struct Recorder {
+    path: PathBuf,
+    writer: Writer,
+    frames: u64,
+    // The frame waiting for the screen to change, and when it was first shown.
+    pending: Option<(Frame, u64)>,
+}
+
+// A frame as it was shown: how bright every pixel was, in the palette then
+// in use.
+#[derive(Clone, Copy, PartialEq)]
+struct Frame {
+    intensities: Intensities,
+    palette: Palette,
+}
+
+enum Writer {
+    // GIF frames are written as they finish.
+    Gif(gif::Encoder<BufWriter<File>>),
+    // The number of APNG frames goes before the first one, so they're kept
+    // until the end, with their length in frames.
+    Apng(File, Vec<(Frame, u64)>),
+}
+
+impl Recorder {
+    pub fn start(path: &Path) -> Result<Recorder, String> {
+        let extension = path
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_lowercase());
+        let create = || File::create(path).map_err(|e| error(path, e));
+
+        let writer = match extension.as_deref() {
+            Some("gif") => {
+                // Every frame has its own colors, for fading pixels and
+                // palettes changed along the way.
+                let (width, height) = size();
+                let mut encoder =
+                    gif::Encoder::new(BufWriter::new(create()?), width as u16, height as u16, &[])
+                        .map_err(|e| error(path, e))?;
+                encoder
+                    .set_repeat(gif::Repeat::Infinite)
+                    .map_err(|e| error(path, e))?;
+                Writer::Gif(encoder)
+            }
+            Some("png") | Some("apng") => Writer::Apng(create()?, Vec::new()),
+            _ => {
+                return Err(format!(
+                    "can't record to {}, expected a .gif, .png or .apng file",
+                    path.display()
+                ))
+            }
+        };
+
+        Ok(Recorder {
+            path: path.to_path_buf(),
+            writer,
+            frames: 0,
+            pending: None,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    // Called once per frame with what's on screen: the screen with flicker
+    // reduction, in the palette it's drawn in.
+    pub fn capture(&mut self, intensities: &Intensities, palette: Palette) -> Result<(), String> {
+        let frame = Frame {
+            intensities: *intensities,
+            palette,
+        };
+
+        match self.pending {
+            Some((pending, _)) if pending == frame => {}
+            Some((pending, start)) => {
+                if self.write(pending, start, self.frames)? {
+                    self.pending = Some((frame, self.frames));
+                } else {
+                    // Too short to show: this frame takes its place.
+                    self.pending = Some((frame, start));
+                }
+            }
+            None => self.pending = Some((frame, self.frames)),
+        }
+
+        self.frames += 1;
+        Ok(())
+    }
+
+    // Writes out the last frame and closes the file.
+    pub fn finish(mut self) -> Result<(), String> {
+        if let Some((pending, start)) = self.pending.take() {
+            // Nothing follows the last frame, so it's shown however briefly.
+            let end = self.frames.max(start + GIF_MIN_DELAY);
+            self.write(pending, start, end)?;
+        }
+
+        let path = self.path;
+        match self.writer {
+            Writer::Gif(encoder) => encoder
+                .into_inner()
+                .map(|_| ())
+                .map_err(|e| error(&path, e)),
+            Writer::Apng(file, frames) => write_apng(file, &frames).map_err(|e| error(&path, e)),
+        }
+    }
+
+    // Writes a frame shown from frame number start until end. Returns false
+    // if it would be too short for the format.
+    fn write(&mut self, frame: Frame, start: u64, end: u64) -> Result<bool, String> {
+        let path = &self.path;
+        match &mut self.writer {
+            Writer::Gif(encoder) => {
+                let delay = centiseconds(end) - centiseconds(start);
+                if delay < GIF_MIN_DELAY {
+                    return Ok(false);
+                }
+
+                let (width, height) = size();
+                let (colors, indices) = indexed(&frame);
+                let mut gif_frame = gif::Frame {
+                    width: width as u16,
+                    height: height as u16,
+                    palette: Some(colors),
+                    buffer: indices.into(),
+                    ..gif::Frame::default()
+                };
+                // Longer still frames are split up.
+                let mut delay = delay;
+                while delay > 0 {
+                    gif_frame.delay = delay.min(u16::MAX as u64) as u16;
+                    delay -= gif_frame.delay as u64;
+                    encoder
+                        .write_frame(&gif_frame)
+                        .map_err(|e| error(path, e))?;
+                }
+            }
+            Writer::Apng(_, frames) => frames.push((frame, end - start)),
+        }
+
+        Ok(true)
+    }
+}
+
+fn write_apng(file: File, frames: &[(Frame, u64)]) -> Result<(), png::EncodingError> {
+    // APNG delays are fractions of a second, so frames are timed exactly, but
+    // only up to u16::MAX sixtieths of a second at a time.
+    let mut split = Vec::new();
+    for &(ref frame, mut length) in frames {
+        while length > 0 {
+            let part = length.min(u16::MAX as u64);
+            split.push((frame, part as u16));
+            length -= part;
+        }
+    }
+
+    let (width, height) = size();
+    let mut encoder = png::Encoder::new(BufWriter::new(file), width as u32, height as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_animated(split.len() as u32, 0)?;
+
+    let mut writer = encoder.write_header()?;
+    for (frame, length) in split {
+        writer.set_frame_delay(length, FRAMES_PER_SECOND as u16)?;
+        writer.write_image_data(&scale_up(frame, |color| {
+            let [_, red, green, blue] = color.to_be_bytes();
+            [red, green, blue]
+        }))?;
+    }
+    writer.finish()
+}
+
+fn size() -> (usize, usize) {
+    (
+        GRAPHICS_WIDTH * RECORDING_SCALE,
+        GRAPHICS_HEIGHT * RECORDING_SCALE,
+    )
+}
+
+// Frame numbers to hundredths of a second, rounded to the nearest.
+fn centiseconds(frames: u64) -> u64 {
+    (frames * 100 + FRAMES_PER_SECOND / 2) / FRAMES_PER_SECOND
+}
+
+// The frame scaled up, with the bytes `pixel` gives for each of its colors.
+fn scale_up<const N: usize>(frame: &Frame, mut pixel: impl FnMut(u32) -> [u8; N]) -> Vec<u8> {
+    let (width, height) = size();
+    let mut bytes = Vec::with_capacity(width * height * N);
+
+    for row in frame.intensities.iter() {
+        let line: Vec<u8> = row
+            .iter()
+            .flat_map(|&intensity| {
+                iter::repeat_n(pixel(frame.palette.blend(intensity)), RECORDING_SCALE)
+            })
+            .flatten()
+            .collect();
+        for _ in 0..RECORDING_SCALE {
+            bytes.extend_from_slice(&line);
+        }
+    }
+
+    bytes
+}
+
+// The frame's colors as RGB triples, and the frame scaled up with an index
+// into them for every pixel. A palette blends into at most 256 colors, so
+// they always fit.
+fn indexed(frame: &Frame) -> (Vec<u8>, Vec<u8>) {
+    let mut colors: Vec<u32> = Vec::new();
+    let indices = scale_up(frame, |color| {
+        let index = colors
+            .iter()
+            .position(|&known| known == color)
+            .unwrap_or_else(|| {
+                colors.push(color);
+                colors.len() - 1
+            });
+        [index as u8]
+    });
+
+    let colors = colors
+        .iter()
+        .flat_map(|color| color.to_be_bytes()[1..].to_vec())
+        .collect();
+    (colors, indices)
+}
+
+fn error<E: std::fmt::Display>(path: &Path, e: E) -> String {
+    format!("can't record to {}: {}", path.display(), e)
+}
@@ -0,0 +1,166 @@
+// Rewards for environment::Env, worked out from what a game keeps in memory.
+// Few ROMs keep score in the same place, so each game needs its own.
+use crate::cpu::MEMORY_SIZE;
+
+pub trait RewardExtractor: Send {
+    // Called at the start of every episode, once the ROM is loaded.
+    fn reset(&mut self, memory: &[u8]);
+
+    // The reward earned since the last call, or since reset.
+    fn reward(&mut self, memory: &[u8]) -> f32;
+
+    // Whether the game is over, as far as the game itself can tell.
+    fn is_over(&self, _memory: &[u8]) -> bool {
+        false
+    }
+}
+
+// For ROMs with nothing to extract: always zero.
+pub struct NoReward;
+
+impl RewardExtractor for NoReward {
+    fn reset(&mut self, _: &[u8]) {}
+
+    fn reward(&mut self, _: &[u8]) -> f32 {
+        0.0
+    }
+}
+
+// Rewards changes to single bytes of memory, each change multiplied by a
+// weight: a player's score counts positively, an opponent's negatively.
+pub struct ByteDelta {
+    terms: Vec<(u16, f32)>,
+    last: Vec<u8>,
+}
+
+impl ByteDelta {
+    pub fn new(terms: Vec<(u16, f32)>) -> Result<ByteDelta, String> {
+        if let Some(&(address, _)) = terms
+            .iter()
+            .find(|&&(address, _)| address as usize >= MEMORY_SIZE)
+        {
+            return Err(format!("address {:#x} is past the end of memory", address));
+        }
+
+        let last = vec![0; terms.len()];
+        Ok(ByteDelta { terms, last })
+    }
+
+    fn read(&self, memory: &[u8]) -> Vec<u8> {
+        self.terms
+            .iter()
+            .map(|&(address, _)| memory[address as usize])
+            .collect()
+    }
+}
+
+impl RewardExtractor for ByteDelta {
+    fn reset(&mut self, memory: &[u8]) {
+        self.last = self.read(memory);
+    }
+
+    fn reward(&mut self, memory: &[u8]) -> f32 {
+        let current = self.read(memory);
+        let reward = self
+            .terms
+            .iter()
+            .zip(current.iter().zip(&self.last))
+            .map(|(&(_, weight), (&now, &before))| weight * (now as f32 - before as f32))
+            .sum();
+
+        self.last = current;
+        reward
+    }
+}
+
+// Pong (1 player) keeps both scores in VE, adding 10 when the right paddle
+// misses and 1 when the left one does, and stores it as BCD at 0x2F2 to draw
+// it. The agent plays the left paddle, which keys 1 and 4 move.
+pub struct PongReward {
+    points_to_win: u32,
+    last: u32,
+    left: u32,
+    right: u32,
+}
+
+const PONG_SCORE_ADDRESS: usize = 0x2f2;
+const PONG_POINTS_TO_WIN: u32 = 7;
+
+impl PongReward {
+    pub fn new(points_to_win: u32) -> PongReward {
+        PongReward {
+            points_to_win,
+            last: 0,
+            left: 0,
+            right: 0,
+        }
+    }
+
+    fn score(memory: &[u8]) -> u32 {
+        memory[PONG_SCORE_ADDRESS..PONG_SCORE_ADDRESS + 3]
+            .iter()
+            .fold(0, |score, &digit| score * 10 + digit as u32)
+    }
+}
+
+impl RewardExtractor for PongReward {
+    fn reset(&mut self, memory: &[u8]) {
+        self.last = PongReward::score(memory);
+        self.left = 0;
+        self.right = 0;
+    }
+
+    fn reward(&mut self, memory: &[u8]) -> f32 {
+        let score = PongReward::score(memory);
+        // The score wraps around at 256, and the BCD copy with it.
+        let gained = (score + 256 - self.last) % 256;
+        self.last = score;
+
+        let left = gained / 10;
+        let right = gained % 10;
+        self.left += left;
+        self.right += right;
+
+        left as f32 - right as f32
+    }
+
+    fn is_over(&self, _: &[u8]) -> bool {
+        self.left >= self.points_to_win || self.right >= self.points_to_win
+    }
+}
+
+// Extractors for the ROMs in roms/, by file name without the extension.
+pub fn for_rom(name: &str) -> Option<Box<dyn RewardExtractor>> {
+    match name {
+        "Pong (1 player)" => Some(Box::new(PongReward::new(PONG_POINTS_TO_WIN))),
+        _ => None,
+    }
+}
+
+pub fn rom_names() -> Vec<&'static str> {
+    vec!["Pong (1 player)"]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn byte_deltas_are_weighted_and_summed() {
+        let mut reward = ByteDelta::new(vec![(0x300, 1.0), (0x301, -0.5)]).unwrap();
+        let mut memory = [0; MEMORY_SIZE];
+        memory[0x300] = 4;
+        reward.reset(&memory);
+
+        memory[0x300] = 6;
+        memory[0x301] = 2;
+        assert_eq!(reward.reward(&memory), 1.0);
+        assert_eq!(reward.reward(&memory), 0.0);
+    }
+
+    #[test]
+    fn byte_deltas_only_take_addresses_in_memory() {
+        assert!(ByteDelta::new(vec![(0xfff, 1.0)]).is_ok());
+        assert!(ByteDelta::new(vec![(0x200, 1.0), (0x1000, 1.0)]).is_err());
+    }
+}
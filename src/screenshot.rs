@@ -0,0 +1,181 @@
+// Saving the screen to disk: as a PNG in a palette's colors, as a plain PBM
+// bitmap, or as text for comparing screens in tests, and through a filter the
+// way the window shows it. PNG needs the png feature, which the frontend
+// feature turns on.
+use crate::cpu::{Graphics, GRAPHICS_HEIGHT, GRAPHICS_WIDTH};
+use crate::filter::Filter;
+use crate::palette::Palette;
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+pub const LIT: char = '#';
+pub const UNLIT: char = '.';
+
+// Saves the screen scaled up by a whole number, in a format picked by the
+// file's extension: .png, .pbm, or .txt for to_ascii.
+pub fn save(graphics: &Graphics, palette: Palette, scale: usize, path: &Path) -> io::Result<()> {
+    let extension = path
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase());
+    let write = match extension.as_deref() {
+        Some("png") => |graphics: &Graphics, palette, scale, out: &mut dyn Write| {
+            write_png(graphics, palette, scale, out)
+        },
+        Some("pbm") => {
+            |graphics: &Graphics, _, scale, out: &mut dyn Write| write_pbm(graphics, scale, out)
+        }
+        Some("txt") => |graphics: &Graphics, _, _, out: &mut dyn Write| {
+            out.write_all(to_ascii(graphics).as_bytes())
+        },
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "expected a .png, .pbm or .txt file",
+            ))
+        }
+    };
+
+    let mut out = BufWriter::new(File::create(path)?);
+    write(graphics, palette, scale, &mut out)?;
+    out.flush()
+}
+
+// Saves the screen through a filter, at scale times its size, as a .png or a
+// binary .ppm. This is what the window shows at that size.
+pub fn save_filtered(
+    graphics: &Graphics,
+    palette: Palette,
+    filter: &Filter,
+    scale: usize,
+    path: &Path,
+) -> io::Result<()> {
+    let source: Vec<u32> = graphics
+        .iter()
+        .flatten()
+        .map(|&lit| if lit { palette.on() } else { palette.off() })
+        .collect();
+
+    let width = GRAPHICS_WIDTH * scale.max(1);
+    let mut pixels = vec![0; width * GRAPHICS_HEIGHT * scale.max(1)];
+    filter.render(&source, GRAPHICS_WIDTH, 1, &mut pixels, width);
+
+    let extension = path
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase());
+    let mut out = BufWriter::new(match extension.as_deref() {
+        Some("png") | Some("ppm") => File::create(path)?,
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "filtered screenshots are saved as .png or .ppm",
+            ))
+        }
+    });
+
+    if extension.as_deref() == Some("png") {
+        write_rgb_png(&pixels, width, &mut out)?;
+    } else {
+        write_ppm(&pixels, width, &mut out)?;
+    }
+    out.flush()
+}
+
+// An RGB PNG, with every pixel a scale by scale square.
+pub fn write_png<W: Write>(
+    graphics: &Graphics,
+    palette: Palette,
+    scale: usize,
+    out: W,
+) -> io::Result<()> {
+    let scale = scale.max(1);
+    let mut pixels = Vec::with_capacity(GRAPHICS_WIDTH * GRAPHICS_HEIGHT * scale * scale);
+    for line in scaled(graphics, scale) {
+        pixels.extend(line.map(|lit| if lit { palette.on() } else { palette.off() }));
+    }
+
+    write_rgb_png(&pixels, GRAPHICS_WIDTH * scale, out)
+}
+
+// An RGB PNG of 0x00RRGGBB pixels, width to a line.
+#[cfg(feature = "png")]
+pub fn write_rgb_png<W: Write>(pixels: &[u32], width: usize, out: W) -> io::Result<()> {
+    let mut encoder = png::Encoder::new(out, width as u32, (pixels.len() / width) as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let data: Vec<u8> = pixels
+        .iter()
+        .flat_map(|color| color.to_be_bytes()[1..].to_vec())
+        .collect();
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&data)?;
+    Ok(())
+}
+
+#[cfg(not(feature = "png"))]
+pub fn write_rgb_png<W: Write>(_: &[u32], _: usize, _: W) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "chip8-rs was built without PNG support",
+    ))
+}
+
+// A binary (P6) PPM of 0x00RRGGBB pixels, width to a line.
+pub fn write_ppm<W: Write>(pixels: &[u32], width: usize, mut out: W) -> io::Result<()> {
+    writeln!(out, "P6\n{} {}\n255", width, pixels.len() / width)?;
+    for color in pixels {
+        out.write_all(&color.to_be_bytes()[1..])?;
+    }
+    Ok(())
+}
+
+// A plain (P1) PBM, black where pixels are lit, with every pixel a scale by
+// scale square.
+pub fn write_pbm<W: Write>(graphics: &Graphics, scale: usize, mut out: W) -> io::Result<()> {
+    let scale = scale.max(1);
+    writeln!(
+        out,
+        "P1\n{} {}",
+        GRAPHICS_WIDTH * scale,
+        GRAPHICS_HEIGHT * scale
+    )?;
+
+    for line in scaled(graphics, scale) {
+        let bits: Vec<&str> = line.map(|lit| if lit { "1" } else { "0" }).collect();
+        // PBM lines shouldn't be longer than 70 characters.
+        for chunk in bits.chunks(32) {
+            writeln!(out, "{}", chunk.join(" "))?;
+        }
+    }
+
+    Ok(())
+}
+
+// The screen as 32 lines of 64 characters, LIT or UNLIT, each ending in a
+// newline. Easier to read in a failed assertion than a grid of bools.
+pub fn to_ascii(graphics: &Graphics) -> String {
+    let mut text = String::with_capacity((GRAPHICS_WIDTH + 1) * GRAPHICS_HEIGHT);
+
+    for row in graphics.iter() {
+        text.extend(row.iter().map(|&lit| if lit { LIT } else { UNLIT }));
+        text.push('\n');
+    }
+
+    text
+}
+
+// Every line of the scaled up screen, as whether each pixel is lit.
+fn scaled(
+    graphics: &Graphics,
+    scale: usize,
+) -> impl Iterator<Item = impl Iterator<Item = bool> + '_> {
+    graphics
+        .iter()
+        .flat_map(move |row| (0..scale).map(move |_| row))
+        .map(move |row| {
+            row.iter()
+                .flat_map(move |&lit| (0..scale).map(move |_| lit))
+        })
+}
@@ -0,0 +1,349 @@
+use crate::overlay;
+use chip8_rs::cpu::{Cpu, Registers, KEYPAD_SIZE, MEMORY_SIZE, REGISTERS};
+use chip8_rs::palette::Palette;
+use chip8_rs::screenshot;
+
+use rhai::{CallFnOptions, Dynamic, Engine, EvalAltResult, Map, Scope, AST};
+
+use std::cell::RefCell;
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+type ScriptResult<T> = Result<T, Box<EvalAltResult>>;
+
+const ON_FRAME: &str = "on_frame";
+const ON_INSTRUCTION: &str = "on_instruction";
+
+// Anything further out is off screen anyway.
+const MAX_TEXT_POSITION: usize = 0x100;
+
+// Big enough for any screen there is.
+const MAX_SCREENSHOT_SCALE: usize = 100;
+
+// A line of text a script asked to show over the screen this frame, at a
+// position in overlay character cells.
+struct Text {
+    column: usize,
+    row: usize,
+    text: String,
+}
+
+// What the script's functions work on. The emulator's Cpu is swapped in for
+// the duration of every call into the script.
+struct State {
+    cpu: Cpu,
+    keys: [bool; KEYPAD_SIZE],
+    texts: Vec<Text>,
+    palette: Palette,
+    frame: i64,
+}
+
+// A Rhai script attached to the running ROM, see --script in the README.
+pub struct Script {
+    path: PathBuf,
+    engine: Engine,
+    ast: AST,
+    scope: Scope<'static>,
+    // `this` in the callbacks, where scripts keep values between calls.
+    this: Dynamic,
+    state: Rc<RefCell<State>>,
+    on_frame: bool,
+    on_instruction: bool,
+}
+
+impl Script {
+    pub fn load(path: &Path) -> Result<Script, String> {
+        let state = Rc::new(RefCell::new(State {
+            cpu: Cpu::new(),
+            keys: [false; KEYPAD_SIZE],
+            texts: Vec::new(),
+            palette: Palette::default(),
+            frame: 0,
+        }));
+
+        let mut engine = Engine::new();
+        register_api(&mut engine, &state);
+
+        let ast = engine
+            .compile_file(path.to_path_buf())
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        let defines = |name: &str| {
+            ast.iter_functions()
+                .any(|f| f.name == name && f.params.is_empty())
+        };
+
+        Ok(Script {
+            path: path.to_path_buf(),
+            on_frame: defines(ON_FRAME),
+            on_instruction: defines(ON_INSTRUCTION),
+            engine,
+            ast,
+            scope: Scope::new(),
+            this: Dynamic::from(Map::new()),
+            state,
+        })
+    }
+
+    // Runs the script's top level, once the ROM is loaded.
+    pub fn start(&mut self, cpu: &mut Cpu, palette: Palette) -> Result<(), String> {
+        {
+            let mut state = self.state.borrow_mut();
+            state.palette = palette;
+            state.frame = 0;
+            state.keys = [false; KEYPAD_SIZE];
+            state.texts.clear();
+        }
+        self.scope.clear();
+        self.this = Dynamic::from(Map::new());
+
+        let Script {
+            engine,
+            ast,
+            scope,
+            state,
+            ..
+        } = self;
+        with_cpu(state, cpu, || engine.run_ast_with_scope(scope, ast))
+            .map_err(|e| format!("{}: {}", self.path.display(), e))
+    }
+
+    pub fn set_palette(&mut self, palette: Palette) {
+        self.state.borrow_mut().palette = palette;
+    }
+
+    pub fn wants_instructions(&self) -> bool {
+        self.on_instruction
+    }
+
+    // Calls on_instruction(), before the CPU runs its next instruction.
+    pub fn before_instruction(&mut self, cpu: &mut Cpu) -> Result<(), String> {
+        if self.on_instruction {
+            self.call(cpu, ON_INSTRUCTION)?;
+        }
+        Ok(())
+    }
+
+    // Calls on_frame(), after the frame's instructions and timer tick. Text
+    // from the previous frame is cleared first.
+    pub fn after_frame(&mut self, cpu: &mut Cpu) -> Result<(), String> {
+        {
+            let mut state = self.state.borrow_mut();
+            state.frame += 1;
+            state.texts.clear();
+        }
+
+        if self.on_frame {
+            self.call(cpu, ON_FRAME)?;
+        }
+        Ok(())
+    }
+
+    // Adds the keys the script is holding down to the player's.
+    pub fn press_keys(&self, keys: &mut [bool; KEYPAD_SIZE]) {
+        for (key, &held) in keys.iter_mut().zip(&self.state.borrow().keys) {
+            *key |= held;
+        }
+    }
+
+    pub fn has_text(&self) -> bool {
+        !self.state.borrow().texts.is_empty()
+    }
+
+    pub fn draw_text(&self, buffer: &mut [u32], width: usize, palette: Palette) {
+        for text in &self.state.borrow().texts {
+            let position = (
+                text.column * overlay::CELL_WIDTH,
+                text.row * overlay::CELL_HEIGHT,
+            );
+            let size = (
+                text.text.chars().count() * overlay::CELL_WIDTH + 1,
+                overlay::CELL_HEIGHT + 1,
+            );
+
+            overlay::fill_rect(buffer, width, position, size, palette.off());
+            overlay::draw_text(
+                buffer,
+                width,
+                (position.0 + 1, position.1 + 1),
+                &text.text,
+                palette.on(),
+            );
+        }
+    }
+
+    fn call(&mut self, cpu: &mut Cpu, name: &str) -> Result<(), String> {
+        let Script {
+            engine,
+            ast,
+            scope,
+            this,
+            state,
+            ..
+        } = self;
+        let options = CallFnOptions::new().eval_ast(false).bind_this_ptr(this);
+
+        with_cpu(state, cpu, || {
+            engine
+                .call_fn_with_options::<Dynamic>(options, scope, ast, name, ())
+                .map(|_| ())
+        })
+        .map_err(|e| format!("{}: in {}: {}", self.path.display(), name, e))
+    }
+}
+
+fn with_cpu<T>(state: &Rc<RefCell<State>>, cpu: &mut Cpu, f: impl FnOnce() -> T) -> T {
+    mem::swap(cpu, &mut state.borrow_mut().cpu);
+    let result = f();
+    mem::swap(cpu, &mut state.borrow_mut().cpu);
+    result
+}
+
+fn register_api(engine: &mut Engine, state: &Rc<RefCell<State>>) {
+    let s = state.clone();
+    engine.register_fn("peek", move |address: i64| -> ScriptResult<i64> {
+        let address = check(address, MEMORY_SIZE, "address")?;
+        Ok(s.borrow().cpu.memory()[address] as i64)
+    });
+
+    let s = state.clone();
+    engine.register_fn(
+        "poke",
+        move |address: i64, value: i64| -> ScriptResult<()> {
+            let address = check(address, MEMORY_SIZE, "address")?;
+            let value = check(value, 0x100, "byte")?;
+            s.borrow_mut().cpu.memory_mut()[address] = value as u8;
+            Ok(())
+        },
+    );
+
+    let s = state.clone();
+    engine.register_fn("reg", move |x: i64| -> ScriptResult<i64> {
+        let x = check(x, REGISTERS, "register")?;
+        Ok(s.borrow().cpu.registers().v[x] as i64)
+    });
+
+    let s = state.clone();
+    engine.register_fn("set_reg", move |x: i64, value: i64| -> ScriptResult<()> {
+        let x = check(x, REGISTERS, "register")?;
+        let value = check(value, 0x100, "byte")?;
+        update_registers(&s, |registers| registers.v[x] = value as u8);
+        Ok(())
+    });
+
+    let s = state.clone();
+    engine.register_fn("reg_i", move || {
+        s.borrow().cpu.registers().index_register as i64
+    });
+
+    let s = state.clone();
+    engine.register_fn("set_reg_i", move |value: i64| -> ScriptResult<()> {
+        let value = check(value, 0x10000, "address")?;
+        update_registers(&s, |registers| registers.index_register = value as u16);
+        Ok(())
+    });
+
+    let s = state.clone();
+    engine.register_fn("pc", move || {
+        s.borrow().cpu.registers().program_counter as i64
+    });
+
+    let s = state.clone();
+    engine.register_fn("set_pc", move |value: i64| -> ScriptResult<()> {
+        // The whole opcode has to be in memory.
+        let value = check(value, MEMORY_SIZE - 1, "address")?;
+        update_registers(&s, |registers| registers.program_counter = value as u16);
+        Ok(())
+    });
+
+    let s = state.clone();
+    engine.register_fn("delay_timer", move || {
+        s.borrow().cpu.registers().delay_timer as i64
+    });
+
+    let s = state.clone();
+    engine.register_fn("set_delay_timer", move |value: i64| -> ScriptResult<()> {
+        let value = check(value, 0x100, "byte")?;
+        update_registers(&s, |registers| registers.delay_timer = value as u8);
+        Ok(())
+    });
+
+    let s = state.clone();
+    engine.register_fn("sound_timer", move || {
+        s.borrow().cpu.registers().sound_timer as i64
+    });
+
+    let s = state.clone();
+    engine.register_fn("set_sound_timer", move |value: i64| -> ScriptResult<()> {
+        let value = check(value, 0x100, "byte")?;
+        update_registers(&s, |registers| registers.sound_timer = value as u8);
+        Ok(())
+    });
+
+    let s = state.clone();
+    engine.register_fn("press", move |key: i64| -> ScriptResult<()> {
+        let key = check(key, KEYPAD_SIZE, "key")?;
+        s.borrow_mut().keys[key] = true;
+        Ok(())
+    });
+
+    let s = state.clone();
+    engine.register_fn("release", move |key: i64| -> ScriptResult<()> {
+        let key = check(key, KEYPAD_SIZE, "key")?;
+        s.borrow_mut().keys[key] = false;
+        Ok(())
+    });
+
+    let s = state.clone();
+    engine.register_fn("frame", move || s.borrow().frame);
+
+    let s = state.clone();
+    engine.register_fn(
+        "text",
+        move |column: i64, row: i64, text: &str| -> ScriptResult<()> {
+            let column = check(column, MAX_TEXT_POSITION, "column")?;
+            let row = check(row, MAX_TEXT_POSITION, "row")?;
+            s.borrow_mut().texts.push(Text {
+                column,
+                row,
+                text: text.to_string(),
+            });
+            Ok(())
+        },
+    );
+
+    let s = state.clone();
+    engine.register_fn("screenshot", move |path: &str| -> ScriptResult<()> {
+        save_screenshot(&s, path, 1)
+    });
+
+    let s = state.clone();
+    engine.register_fn(
+        "screenshot",
+        move |path: &str, scale: i64| -> ScriptResult<()> {
+            let scale = check(scale, MAX_SCREENSHOT_SCALE + 1, "scale")?.max(1);
+            save_screenshot(&s, path, scale)
+        },
+    );
+}
+
+fn save_screenshot(state: &Rc<RefCell<State>>, path: &str, scale: usize) -> ScriptResult<()> {
+    let state = state.borrow();
+    screenshot::save(state.cpu.graphics(), state.palette, scale, Path::new(path))
+        .map_err(|e| format!("can't save {}: {}", path, e).into())
+}
+
+fn update_registers<F: FnOnce(&mut Registers)>(state: &Rc<RefCell<State>>, update: F) {
+    let cpu = &mut state.borrow_mut().cpu;
+    let mut registers = cpu.registers();
+    update(&mut registers);
+    cpu.set_registers(&registers);
+}
+
+fn check(value: i64, limit: usize, what: &str) -> ScriptResult<usize> {
+    if value < 0 || value as u64 >= limit as u64 {
+        return Err(format!("{} {:#x} is out of range", what, value).into());
+    }
+
+    Ok(value as usize)
+}
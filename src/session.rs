@@ -0,0 +1,452 @@
+use crate::cheat_store::Cheats;
+use crate::cli::Options;
+use crate::config::{Config, Settings};
+use crate::keys::key_name;
+use crate::menu::Menu;
+use crate::metadata::RomInfo;
+use crate::osd::Osd;
+use crate::recorder::Recorder;
+use crate::{fail, load, overlay, rom_names, settings_for};
+use crate::{HELP_COLUMNS, HELP_ROWS, KEY_DEBOUNCE};
+use chip8_rs::cpu::{Cpu, KEYPAD_SIZE};
+use chip8_rs::host::TimeScale;
+use chip8_rs::movie::Movie;
+use chip8_rs::phosphor::{Persistence, Phosphor};
+use chip8_rs::screenshot;
+
+use minifb::Key;
+
+use std::fs;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const SCREENSHOT_SCALE: usize = 4;
+
+// A ROM being played and the state of the hotkeys around it, shared by the
+// window and terminal frontends.
+pub struct Session {
+    pub info: RomInfo,
+    names: Vec<String>,
+    pub settings: Settings,
+    pub speed: u8,
+    pub help: Vec<String>,
+    pub help_visible: bool,
+    pub help_scroll: usize,
+    pub keypad_visible: bool,
+    // The screen with the ROM's flicker reduction, for the frontend to show.
+    pub phosphor: Phosphor,
+    pub cheats: Cheats,
+    pub osd: Osd,
+    // The settings menu, while it's open. The game is paused meanwhile.
+    pub menu: Option<Menu>,
+    pub paused: bool,
+    // Whether to run a single frame while paused.
+    advance: bool,
+    // Normal, or fast-forward or slow motion while their hotkeys are held.
+    pub time_scale: TimeScale,
+    recorder: Option<Recorder>,
+    // The input movie being recorded with --record-input.
+    movie: Option<Movie>,
+    key_debounce: u8,
+}
+
+impl Session {
+    pub fn start(cpu: &mut Cpu, filename: &str, config: &Config, options: &Options) -> Session {
+        let info = RomInfo::from_path(filename);
+        let names = rom_names(filename, &info);
+        let name_refs: Vec<&str> = names.iter().map(String::as_str).collect();
+
+        let settings = settings_for(config, options, &name_refs).unwrap_or_else(|e| fail(&e));
+        cpu.set_quirks(settings.quirks);
+        load(cpu, filename, options);
+        let phosphor = Phosphor::new(settings.flicker);
+        let cheats = Cheats::load(filename, &options.cheats).unwrap_or_else(|e| fail(&e));
+        let recorder = options
+            .record
+            .as_ref()
+            .map(|path| Recorder::start(path).unwrap_or_else(|e| fail(&e)));
+
+        // Movies only play back the same with the same random numbers.
+        let movie = options.record_input.as_ref().map(|_| {
+            let seed = options.seed.unwrap_or_else(rand::random);
+            cpu.seed(seed);
+
+            let rom = fs::read(filename)
+                .unwrap_or_else(|e| fail(&format!("can't read {}: {}", filename, e)));
+            Movie::new(
+                &rom,
+                seed,
+                settings.quirks,
+                options.load_address,
+                settings.speed,
+            )
+        });
+
+        Session {
+            help: help_lines(&info, &settings, options),
+            speed: settings.speed,
+            info,
+            names,
+            settings,
+            help_visible: false,
+            help_scroll: 0,
+            keypad_visible: false,
+            phosphor,
+            cheats,
+            osd: Osd::new(),
+            menu: None,
+            paused: false,
+            advance: false,
+            time_scale: TimeScale::NORMAL,
+            recorder,
+            movie,
+            key_debounce: 0,
+        }
+    }
+
+    pub fn title(&self) -> String {
+        format!(
+            "CHIP-8 Emulator - {} - {}{}{} - {} for help, ESC to exit",
+            self.info.window_title(),
+            if self.settings.quirks.vip_timing {
+                String::from("VIP timing")
+            } else {
+                format!("speed {}", self.speed)
+            },
+            if self.paused { " - paused" } else { "" },
+            if self.recorder.is_some() {
+                " - recording"
+            } else {
+                ""
+            },
+            key_name(self.settings.hotkeys.help)
+        )
+    }
+
+    // Adds the screen to the recording, if there is one, after every frame,
+    // as it's drawn. Recording stops if the file can't be written.
+    pub fn capture(&mut self) {
+        if let Some(recorder) = &mut self.recorder {
+            if let Err(e) = recorder.capture(self.phosphor.intensities(), self.settings.palette) {
+                eprintln!("error: {}", e);
+                self.osd.show("Recording stopped, see the console");
+                self.recorder = None;
+            }
+        }
+    }
+
+    // Adds the frame's keys to the input movie, if one is being recorded.
+    pub fn record_input(&mut self, keys: &[bool; KEYPAD_SIZE]) {
+        if let Some(movie) = &mut self.movie {
+            movie.record_frame(keys);
+        }
+    }
+
+    // Called when the ROM stops running, to finish the recording and save
+    // the input movie.
+    pub fn end(&mut self, options: &Options) {
+        if let Some(message) = self.stop_recording() {
+            println!("{}", message);
+        }
+
+        if let (Some(movie), Some(path)) = (self.movie.take(), &options.record_input) {
+            match fs::write(path, movie.format()) {
+                Ok(()) => println!("Input movie saved to {}", path.display()),
+                Err(e) => eprintln!("error: can't save {}: {}", path.display(), e),
+            }
+        }
+    }
+
+    // Finishes the recording, if there is one. Returns what became of it.
+    fn stop_recording(&mut self) -> Option<String> {
+        let recorder = self.recorder.take()?;
+        let path = recorder.path().to_path_buf();
+
+        Some(match recorder.finish() {
+            Ok(()) => format!("Recording saved to {}", path.display()),
+            Err(e) => {
+                eprintln!("error: {}", e);
+                String::from("Can't save the recording, see the console")
+            }
+        })
+    }
+
+    fn save_screenshot(&mut self, cpu: &Cpu) {
+        let path = self.timestamped("png");
+        match screenshot::save(
+            cpu.graphics(),
+            self.settings.palette,
+            SCREENSHOT_SCALE,
+            &path,
+        ) {
+            Ok(()) => self
+                .osd
+                .show(format!("Screenshot saved to {}", path.display())),
+            Err(e) => {
+                eprintln!("error: can't save {}: {}", path.display(), e);
+                self.osd.show("Can't save the screenshot, see the console");
+            }
+        }
+    }
+
+    // A file in the current directory named after the ROM and the time.
+    fn timestamped(&self, extension: &str) -> PathBuf {
+        let seconds = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or_default();
+        PathBuf::from(format!("{}-{}.{}", self.names[0], seconds, extension))
+    }
+
+    fn record_speed(&mut self) {
+        if let Some(movie) = &mut self.movie {
+            movie.record_speed(self.speed);
+        }
+    }
+
+    fn toggle_recording(&mut self) {
+        if let Some(message) = self.stop_recording() {
+            self.osd.show(message);
+            return;
+        }
+
+        let path = self.timestamped("gif");
+        match Recorder::start(&path) {
+            Ok(recorder) => {
+                self.osd.show(format!("Recording to {}", path.display()));
+                self.recorder = Some(recorder);
+            }
+            Err(e) => {
+                eprintln!("error: {}", e);
+                self.osd.show("Can't record, see the console");
+            }
+        }
+    }
+
+    // Acts on whichever hotkeys are down. Fast-forward and slow motion last
+    // as long as their keys are held, which frontends that only see key
+    // presses tell apart with `is_held`. Returns whether the title changed.
+    pub fn handle_hotkeys<F: Fn(Key) -> bool, H: Fn(Key) -> bool>(
+        &mut self,
+        is_down: F,
+        is_held: H,
+        cpu: &mut Cpu,
+        config: &mut Config,
+        options: &Options,
+    ) -> bool {
+        let hotkeys = self.settings.hotkeys;
+        let mut title_changed = false;
+
+        if is_down(hotkeys.restart) {
+            self.restart(cpu);
+        }
+
+        let time_scale = if is_held(hotkeys.fast_forward) {
+            self.settings.fast_forward
+        } else if is_held(hotkeys.slow_motion) {
+            self.settings.slow_motion
+        } else {
+            TimeScale::NORMAL
+        };
+        if time_scale != self.time_scale {
+            self.time_scale = time_scale;
+            match time_scale {
+                TimeScale::Uncapped => self.osd.show("Fast forward"),
+                TimeScale::Times(times) if times > 1.0 => {
+                    self.osd.show(format!("Fast forward x{}", times))
+                }
+                TimeScale::Times(times) if times < 1.0 => {
+                    self.osd.show(format!("Slow motion x{}", times))
+                }
+                TimeScale::Times(_) => {}
+            }
+        }
+
+        if self.key_debounce > 0 {
+            self.key_debounce -= 1;
+            return false;
+        }
+
+        if is_down(hotkeys.help) {
+            self.help_visible = !self.help_visible;
+            self.help_scroll = 0;
+            cpu.redraw();
+            self.key_debounce = KEY_DEBOUNCE;
+        }
+
+        if is_down(hotkeys.keypad) {
+            self.keypad_visible = !self.keypad_visible;
+            cpu.redraw();
+            self.key_debounce = KEY_DEBOUNCE;
+        }
+
+        if self.help_visible && is_down(Key::Up) && self.help_scroll > 0 {
+            self.help_scroll -= 1;
+            self.key_debounce = KEY_DEBOUNCE;
+        }
+
+        if self.help_visible && is_down(Key::Down) && self.help_scroll + HELP_ROWS < self.help.len()
+        {
+            self.help_scroll += 1;
+            self.key_debounce = KEY_DEBOUNCE;
+        }
+
+        if is_down(hotkeys.speed_down) {
+            title_changed |= self.step_speed(-1);
+            self.key_debounce = KEY_DEBOUNCE;
+        }
+
+        if is_down(hotkeys.speed_up) {
+            title_changed |= self.step_speed(1);
+            self.key_debounce = KEY_DEBOUNCE;
+        }
+
+        if is_down(hotkeys.record) {
+            self.toggle_recording();
+            title_changed = true;
+            self.key_debounce = KEY_DEBOUNCE;
+        }
+
+        if is_down(hotkeys.screenshot) {
+            self.save_screenshot(cpu);
+            self.key_debounce = KEY_DEBOUNCE;
+        }
+
+        if is_down(hotkeys.pause) {
+            self.paused = !self.paused;
+            cpu.redraw();
+            title_changed = true;
+            self.key_debounce = KEY_DEBOUNCE;
+        }
+
+        // Advancing a running game pauses it first.
+        if is_down(hotkeys.frame_advance) {
+            if self.paused {
+                self.advance = true;
+            } else {
+                self.paused = true;
+                cpu.redraw();
+                title_changed = true;
+            }
+            self.key_debounce = KEY_DEBOUNCE;
+        }
+
+        if is_down(hotkeys.palette) {
+            self.cycle_palette(1);
+            cpu.redraw();
+            self.key_debounce = KEY_DEBOUNCE;
+        }
+
+        if is_down(hotkeys.counters) {
+            self.osd.counters_visible = !self.osd.counters_visible;
+            self.key_debounce = KEY_DEBOUNCE;
+        }
+
+        if is_down(hotkeys.debug) {
+            cpu.toggle_debug();
+            self.osd.show("Debug toggled, see the console");
+            self.key_debounce = KEY_DEBOUNCE;
+        }
+
+        if is_down(hotkeys.reload) {
+            let names: Vec<&str> = self.names.iter().map(String::as_str).collect();
+
+            match config
+                .reload()
+                .and_then(|c| settings_for(&c, options, &names).map(|s| (c, s)))
+            {
+                Ok((reloaded, settings)) => {
+                    *config = reloaded;
+                    self.settings = settings;
+                    self.set_speed(self.settings.speed);
+                    cpu.set_quirks(self.settings.quirks);
+                    self.phosphor.set_persistence(self.settings.flicker);
+                    cpu.redraw();
+                    self.help = help_lines(&self.info, &self.settings, options);
+                    self.help_scroll = 0;
+                    title_changed = true;
+                    self.osd.show("Configuration reloaded");
+                }
+                Err(e) => {
+                    eprintln!("error: {}", e);
+                    self.osd
+                        .show("Can't reload the configuration, see the console");
+                }
+            }
+            self.key_debounce = KEY_DEBOUNCE;
+        }
+
+        title_changed
+    }
+
+    // Whether a frame advance was asked for since the last call.
+    pub fn take_advance(&mut self) -> bool {
+        std::mem::take(&mut self.advance)
+    }
+
+    pub fn set_speed(&mut self, speed: u8) {
+        if speed != self.speed {
+            self.osd.show(format!("Speed {}", speed));
+        }
+        self.speed = speed;
+        self.record_speed();
+    }
+
+    // Moves the speed this far within min_speed and max_speed, or within the
+    // speed itself where it was set outside them, e.g. with --speed 100.
+    // Returns whether it changed.
+    pub fn step_speed(&mut self, step: isize) -> bool {
+        let min = self.settings.min_speed.min(self.speed) as isize;
+        let max = self.settings.max_speed.max(self.speed) as isize;
+        let speed = (self.speed as isize + step).clamp(min, max) as u8;
+
+        if speed == self.speed {
+            return false;
+        }
+        self.set_speed(speed);
+        true
+    }
+
+    pub fn set_flicker(&mut self, flicker: Persistence) {
+        self.settings.flicker = flicker;
+        self.phosphor.set_persistence(flicker);
+    }
+
+    pub fn restart(&mut self, cpu: &mut Cpu) {
+        cpu.restart();
+        if let Some(movie) = &mut self.movie {
+            movie.record_restart();
+        }
+    }
+
+    // Moves this many places along the list of palettes from the one in use,
+    // or to the first if the one in use isn't in the list.
+    pub fn cycle_palette(&mut self, step: isize) {
+        let palettes = &self.settings.palettes;
+        let next = palettes
+            .iter()
+            .position(|(_, palette)| *palette == self.settings.palette)
+            .map_or(0, |current| {
+                (current as isize + step).rem_euclid(palettes.len() as isize) as usize
+            });
+
+        let (name, palette) = &palettes[next];
+        self.osd.show(format!("Palette {}", name));
+        self.settings.palette = *palette;
+    }
+}
+
+fn help_lines(info: &RomInfo, settings: &Settings, options: &Options) -> Vec<String> {
+    let mut text = format!(
+        "{}\n\n{}",
+        info.window_title(),
+        settings.hotkeys.describe(!options.terminal)
+    );
+
+    if let Some(instructions) = &info.instructions {
+        text.push_str("\n\n");
+        text.push_str(instructions);
+    }
+
+    overlay::wrap(&text, HELP_COLUMNS)
+}
@@ -0,0 +1,589 @@
+use crate::cli::Options;
+use crate::config::Config;
+use crate::keymap::{Binding, Keymap, KEYPAD_ROWS};
+use crate::keys::parse_key;
+use crate::session::Session;
+use crate::{configure, open_audio, open_gamepad, HELP_ROWS};
+use chip8_rs::cpu::{Cpu, GRAPHICS_HEIGHT, GRAPHICS_WIDTH, KEYPAD_SIZE};
+use chip8_rs::host::{Clock, FrameClock, Host, Pacer};
+use chip8_rs::palette::Palette;
+use chip8_rs::phosphor::Intensities;
+
+use minifb::Key;
+
+use std::env;
+use std::fmt::Write as _;
+use std::io::{self, Write};
+use std::mem;
+use std::panic;
+use std::thread;
+use std::time::{Duration, Instant};
+
+// Terminals only send key presses, repeated while a key is held, and never
+// tell us a key was let go. A key counts as held until a few frames after its
+// last repeat, and a bit longer after the first press to bridge the delay
+// before the keyboard starts repeating.
+const FIRST_HOLD_FRAMES: u32 = 20;
+const REPEAT_HOLD_FRAMES: u32 = 4;
+
+// Each character cell shows two pixels stacked on top of each other.
+const UPPER_HALF_BLOCK: char = '\u{2580}';
+
+const KEYPAD_CELL_WIDTH: usize = GRAPHICS_WIDTH / 4;
+
+const CTRL_C: u8 = 0x03;
+const ESCAPE: u8 = 0x1b;
+
+// How long the rest of an escape sequence may take to arrive, e.g. over a
+// slow SSH connection, before the ESC counts as a key press on its own.
+const ESCAPE_TIMEOUT: Duration = Duration::from_millis(50);
+
+// Runs a ROM in the terminal until ESC or Ctrl-C, with the same hotkeys as
+// the window.
+pub fn run(config: &mut Config, options: &Options) -> io::Result<()> {
+    let filename = options.target.as_ref().unwrap();
+
+    let mut cpu = Cpu::new();
+    configure(&mut cpu, options);
+    let mut session = Session::start(&mut cpu, filename, config, options);
+
+    let mut audio = open_audio(options);
+    let mut gamepad = open_gamepad(options);
+    let mut clock = FrameClock::new();
+    let mut pacer = Pacer::default();
+
+    let mut terminal = Terminal::open()?;
+    let mut keys = HeldKeys::default();
+
+    terminal.set_title(&session.title())?;
+
+    loop {
+        keys.update(terminal.read_keys()?);
+
+        if keys.typed(Key::Escape) {
+            break;
+        }
+
+        if session.handle_hotkeys(
+            |key| keys.typed(key),
+            |key| keys.held(key),
+            &mut cpu,
+            config,
+            options,
+        ) {
+            terminal.set_title(&session.title())?;
+        }
+
+        let settings = &session.settings;
+        let mut frame = String::new();
+
+        if session.help_visible {
+            audio.set_beeping(false);
+            render_help(&mut frame, &session.help[session.help_scroll..]);
+            terminal.show(frame)?;
+            clock.wait();
+            continue;
+        }
+
+        for message in gamepad.poll() {
+            session.osd.show(message);
+        }
+        let mut pressed = settings.keymap.pressed(|binding| match binding {
+            Binding::Key(key) => keys.held(key),
+            Binding::Button(button) => gamepad.is_down(button),
+        });
+
+        let (paused, advance) = (session.paused, session.take_advance());
+        let (speed, time_scale) = (session.speed, session.time_scale);
+        let instructions = cpu.instructions();
+        let mut emulate = || {
+            session.cheats.apply(&mut cpu);
+            Host {
+                display: &mut (),
+                audio: audio.as_mut(),
+                keypad: &mut pressed,
+                clock: &mut (),
+            }
+            .run_frame(&mut cpu, speed);
+            session.phosphor.update(cpu.graphics());
+            session.capture();
+            session.record_input(&pressed);
+        };
+        match (paused, advance) {
+            (false, _) => {
+                pacer.run(time_scale, emulate);
+            }
+            (true, true) => emulate(),
+            (true, false) => audio.set_beeping(false),
+        }
+        session.osd.count_frame(cpu.instructions() - instructions);
+
+        let settings = &session.settings;
+        render_graphics(
+            &mut frame,
+            session.phosphor.intensities(),
+            settings.palette,
+            terminal.colors,
+        );
+        render_status(&mut frame, session.osd.status_line(paused));
+        if session.keypad_visible {
+            render_keypad(&mut frame, &settings.keymap, &pressed);
+        }
+        terminal.show(frame)?;
+        clock.wait();
+    }
+
+    session.end(options);
+    audio.set_beeping(false);
+    Ok(())
+}
+
+#[derive(Clone, Copy)]
+enum Colors {
+    TrueColor,
+    Ansi256,
+}
+
+impl Colors {
+    fn detect() -> Colors {
+        match env::var("COLORTERM") {
+            Ok(value) if value == "truecolor" || value == "24bit" => Colors::TrueColor,
+            _ => Colors::Ansi256,
+        }
+    }
+
+    // Sets the foreground and background color.
+    fn set(self, out: &mut String, foreground: u32, background: u32) {
+        match self {
+            Colors::TrueColor => {
+                let (fr, fg, fb) = channels(foreground);
+                let (br, bg, bb) = channels(background);
+                let _ = write!(
+                    out,
+                    "\x1b[38;2;{};{};{};48;2;{};{};{}m",
+                    fr, fg, fb, br, bg, bb
+                );
+            }
+            Colors::Ansi256 => {
+                let _ = write!(
+                    out,
+                    "\x1b[38;5;{};48;5;{}m",
+                    ansi256(foreground),
+                    ansi256(background)
+                );
+            }
+        }
+    }
+}
+
+fn channels(color: u32) -> (u8, u8, u8) {
+    ((color >> 16) as u8, (color >> 8) as u8, color as u8)
+}
+
+// The closest color in the 6x6x6 cube or the gray ramp of the 256 color
+// palette.
+fn ansi256(color: u32) -> u8 {
+    let (r, g, b) = channels(color);
+
+    if r == g && g == b {
+        return match r {
+            0..=7 => 16,
+            249..=255 => 231,
+            gray => 232 + (gray - 8) / 10,
+        };
+    }
+
+    let level = |c: u8| (c as u16 * 5 + 127) / 255;
+    (16 + 36 * level(r) + 6 * level(g) + level(b)) as u8
+}
+
+fn render_graphics(out: &mut String, intensities: &Intensities, palette: Palette, colors: Colors) {
+    for row in 0..GRAPHICS_HEIGHT / 2 {
+        let mut last = None;
+
+        for (&top, &bottom) in intensities[row * 2]
+            .iter()
+            .zip(intensities[row * 2 + 1].iter())
+        {
+            let cell = (palette.blend(top), palette.blend(bottom));
+
+            if last != Some(cell) {
+                colors.set(out, cell.0, cell.1);
+                last = Some(cell);
+            }
+            out.push(UPPER_HALF_BLOCK);
+        }
+
+        out.push_str("\x1b[0m\x1b[K\r\n");
+    }
+}
+
+// Messages, the pause indicator and the counters, on a line under the
+// screen.
+fn render_status(out: &mut String, status: String) {
+    out.extend(status.chars().take(GRAPHICS_WIDTH));
+    out.push_str("\x1b[K\r\n");
+}
+
+// The hex keypad as four rows of text under the screen, held keys in reverse.
+fn render_keypad(out: &mut String, keymap: &Keymap, pressed: &[bool; KEYPAD_SIZE]) {
+    for row in KEYPAD_ROWS.iter() {
+        for &chip8_key in row.iter() {
+            let names: Vec<String> = keymap
+                .bindings(chip8_key)
+                .iter()
+                .map(|b| b.to_string())
+                .collect();
+            let mut label = format!("{:X}: {}", chip8_key, names.join(" "));
+            label.truncate(KEYPAD_CELL_WIDTH - 1);
+
+            if pressed[chip8_key as usize] {
+                out.push_str("\x1b[7m");
+            }
+            let _ = write!(
+                out,
+                "{:width$}\x1b[0m ",
+                label,
+                width = KEYPAD_CELL_WIDTH - 1
+            );
+        }
+
+        out.push_str("\x1b[K\r\n");
+    }
+}
+
+fn render_help(out: &mut String, lines: &[String]) {
+    for line in lines.iter().take(HELP_ROWS) {
+        out.push_str(line);
+        out.push_str("\x1b[K\r\n");
+    }
+}
+
+// The terminal in raw mode, showing the alternate screen. Everything is put
+// back as it was when dropped.
+struct Terminal {
+    original: libc::termios,
+    colors: Colors,
+    last_frame: String,
+    // Input from the start of an unfinished escape sequence, and when it
+    // arrived.
+    pending: Vec<u8>,
+    pending_since: Option<Instant>,
+}
+
+impl Terminal {
+    fn open() -> io::Result<Terminal> {
+        if unsafe { libc::isatty(libc::STDIN_FILENO) } != 1 {
+            return Err(io::Error::other("--terminal needs stdin to be a terminal"));
+        }
+
+        let mut original: libc::termios = unsafe { mem::zeroed() };
+        if unsafe { libc::tcgetattr(libc::STDIN_FILENO, &mut original) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let mut raw = original;
+        raw.c_iflag &= !(libc::IXON | libc::ICRNL);
+        raw.c_lflag &= !(libc::ICANON | libc::ECHO | libc::ISIG | libc::IEXTEN);
+        // Reads return straight away, with or without input.
+        raw.c_cc[libc::VMIN] = 0;
+        raw.c_cc[libc::VTIME] = 0;
+
+        if unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &raw) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        // Alternate screen, cursor hidden.
+        print!("\x1b[?1049h\x1b[?25l\x1b[2J");
+        io::stdout().flush()?;
+
+        // Otherwise a panic's message goes to the alternate screen, which
+        // vanishes with it, and leaves the terminal in raw mode.
+        let default_hook = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            restore(&original);
+            default_hook(info);
+        }));
+
+        Ok(Terminal {
+            original,
+            colors: Colors::detect(),
+            last_frame: String::new(),
+            pending: Vec::new(),
+            pending_since: None,
+        })
+    }
+
+    fn set_title(&self, title: &str) -> io::Result<()> {
+        print!("\x1b]0;{}\x07", title);
+        io::stdout().flush()
+    }
+
+    // Draws a frame from the top left corner, unless it's the same as the
+    // last one, which saves a lot of bandwidth over SSH.
+    fn show(&mut self, frame: String) -> io::Result<()> {
+        if frame == self.last_frame {
+            return Ok(());
+        }
+
+        let mut stdout = io::stdout();
+        write!(stdout, "\x1b[H{}\x1b[J", frame)?;
+        stdout.flush()?;
+
+        self.last_frame = frame;
+        Ok(())
+    }
+
+    fn read_keys(&mut self) -> io::Result<Vec<Key>> {
+        let input = &mut self.pending;
+        let mut chunk = [0u8; 64];
+
+        loop {
+            let read = unsafe {
+                libc::read(
+                    libc::STDIN_FILENO,
+                    chunk.as_mut_ptr() as *mut libc::c_void,
+                    chunk.len(),
+                )
+            };
+
+            match read {
+                n if n > 0 => input.extend_from_slice(&chunk[..n as usize]),
+                0 => break,
+                _ => {
+                    let error = io::Error::last_os_error();
+                    match error.kind() {
+                        io::ErrorKind::WouldBlock | io::ErrorKind::Interrupted => break,
+                        _ => return Err(error),
+                    }
+                }
+            }
+        }
+
+        let timed_out = self
+            .pending_since
+            .is_some_and(|since| since.elapsed() >= ESCAPE_TIMEOUT);
+        let (keys, used) = parse_input(&self.pending, timed_out);
+
+        self.pending.drain(..used);
+        self.pending_since = match self.pending_since {
+            _ if self.pending.is_empty() => None,
+            Some(since) if used == 0 => Some(since),
+            _ => Some(Instant::now()),
+        };
+        Ok(keys)
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        // The hook has already restored the terminal when panicking, and
+        // can't be changed then.
+        if thread::panicking() {
+            return;
+        }
+        let _ = panic::take_hook();
+        restore(&self.original);
+    }
+}
+
+// Leaves the alternate screen and raw mode.
+fn restore(original: &libc::termios) {
+    print!("\x1b[0m\x1b[?25h\x1b[?1049l");
+    let _ = io::stdout().flush();
+    unsafe {
+        libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, original);
+    }
+}
+
+#[derive(Default)]
+struct HeldKeys {
+    held: Vec<(Key, u32)>,
+    typed: Vec<Key>,
+}
+
+impl HeldKeys {
+    fn update(&mut self, typed: Vec<Key>) {
+        for (_, frames) in &mut self.held {
+            *frames -= 1;
+        }
+        self.held.retain(|&(_, frames)| frames > 0);
+
+        for &key in &typed {
+            match self.held.iter_mut().find(|(k, _)| *k == key) {
+                Some((_, frames)) => *frames = (*frames).max(REPEAT_HOLD_FRAMES),
+                None => self.held.push((key, FIRST_HOLD_FRAMES)),
+            }
+        }
+
+        self.typed = typed;
+    }
+
+    // Whether the key came in this frame, for hotkeys.
+    fn typed(&self, key: Key) -> bool {
+        self.typed.contains(&key)
+    }
+
+    fn held(&self, key: Key) -> bool {
+        self.held.iter().any(|&(k, _)| k == key)
+    }
+}
+
+// Turns what the terminal sent into keys. Shifted characters count as the
+// key they are on only where that's unambiguous, i.e. for letters.
+//
+// Also returns how many bytes were used: an escape sequence cut off at the
+// end is left for the next read, unless it has timed out, in which case the
+// ESC is a key of its own.
+fn parse_input(input: &[u8], timed_out: bool) -> (Vec<Key>, usize) {
+    let mut keys = Vec::new();
+    let mut i = 0;
+
+    while i < input.len() {
+        let byte = input[i];
+        i += 1;
+
+        let key = match byte {
+            ESCAPE => match escape_sequence(&input[i..]) {
+                Escape::Sequence(key, length) => {
+                    i += length;
+                    key
+                }
+                Escape::Unfinished if !timed_out => return (keys, i - 1),
+                Escape::Unfinished | Escape::Alone => Some(Key::Escape),
+            },
+            CTRL_C => Some(Key::Escape),
+            b'\r' | b'\n' => Some(Key::Enter),
+            b'\t' => Some(Key::Tab),
+            b' ' => Some(Key::Space),
+            0x08 | 0x7f => Some(Key::Backspace),
+            _ => character_key(byte as char),
+        };
+
+        keys.extend(key);
+    }
+
+    (keys, input.len())
+}
+
+fn character_key(c: char) -> Option<Key> {
+    match c {
+        ',' => Some(Key::Comma),
+        '.' => Some(Key::Period),
+        ';' => Some(Key::Semicolon),
+        '\'' => Some(Key::Apostrophe),
+        '/' => Some(Key::Slash),
+        '\\' => Some(Key::Backslash),
+        '-' => Some(Key::Minus),
+        '=' => Some(Key::Equal),
+        '[' => Some(Key::LeftBracket),
+        ']' => Some(Key::RightBracket),
+        '`' => Some(Key::Backquote),
+        c if c.is_ascii_alphanumeric() => parse_key(&c.to_string()),
+        _ => None,
+    }
+}
+
+enum Escape {
+    // The key, if we know it, and how many bytes after the ESC it took up.
+    Sequence(Option<Key>, usize),
+    // The input ends before the sequence does, or right after the ESC.
+    Unfinished,
+    Alone,
+}
+
+// Decodes the CSI and SS3 sequences terminals send for arrows, function keys
+// and the like.
+fn escape_sequence(input: &[u8]) -> Escape {
+    match input.first() {
+        None => Escape::Unfinished,
+        Some(b'O') => match input.get(1) {
+            Some(&b) => Escape::Sequence(final_key(b), 2),
+            None => Escape::Unfinished,
+        },
+        Some(b'[') => {
+            // Parameters, then a final byte between @ and ~.
+            let Some(end) = input[1..].iter().position(|&b| (0x40..=0x7e).contains(&b)) else {
+                return Escape::Unfinished;
+            };
+            let end = end + 1;
+            let parameters = std::str::from_utf8(&input[1..end]).unwrap_or("");
+            let first = parameters.split(';').next().unwrap_or("");
+
+            let key = match input[end] {
+                b'~' => tilde_key(first),
+                b => final_key(b),
+            };
+            Escape::Sequence(key, end + 1)
+        }
+        Some(_) => Escape::Alone,
+    }
+}
+
+fn final_key(byte: u8) -> Option<Key> {
+    match byte {
+        b'A' => Some(Key::Up),
+        b'B' => Some(Key::Down),
+        b'C' => Some(Key::Right),
+        b'D' => Some(Key::Left),
+        b'H' => Some(Key::Home),
+        b'F' => Some(Key::End),
+        b'P' => Some(Key::F1),
+        b'Q' => Some(Key::F2),
+        b'R' => Some(Key::F3),
+        b'S' => Some(Key::F4),
+        _ => None,
+    }
+}
+
+fn tilde_key(code: &str) -> Option<Key> {
+    match code {
+        "1" | "7" => Some(Key::Home),
+        "2" => Some(Key::Insert),
+        "3" => Some(Key::Delete),
+        "4" | "8" => Some(Key::End),
+        "5" => Some(Key::PageUp),
+        "6" => Some(Key::PageDown),
+        "11" => Some(Key::F1),
+        "12" => Some(Key::F2),
+        "13" => Some(Key::F3),
+        "14" => Some(Key::F4),
+        "15" => Some(Key::F5),
+        "17" => Some(Key::F6),
+        "18" => Some(Key::F7),
+        "19" => Some(Key::F8),
+        "20" => Some(Key::F9),
+        "21" => Some(Key::F10),
+        "23" => Some(Key::F11),
+        "24" => Some(Key::F12),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escape_sequences_split_across_reads_wait_for_the_rest() {
+        assert_eq!(parse_input(b"a\x1b", false), (vec![Key::A], 1));
+        assert_eq!(parse_input(b"a\x1b[1;5", false), (vec![Key::A], 1));
+        assert_eq!(parse_input(b"\x1bO", false), (vec![], 0));
+
+        assert_eq!(
+            parse_input(b"\x1b[1;5A\x1bOP", false),
+            (vec![Key::Up, Key::F1], 9)
+        );
+        assert_eq!(parse_input(b"\x1b[15~", false), (vec![Key::F5], 5));
+    }
+
+    #[test]
+    fn escapes_stand_alone_when_followed_by_something_else_or_timed_out() {
+        assert_eq!(parse_input(b"\x1bq", false), (vec![Key::Escape, Key::Q], 2));
+        assert_eq!(parse_input(b"\x1b", true), (vec![Key::Escape], 1));
+        assert_eq!(
+            parse_input(b"\x1b[", true),
+            (vec![Key::Escape, Key::LeftBracket], 2)
+        );
+    }
+}
@@ -0,0 +1,71 @@
+// How long instructions took on the COSMAC VIP, in machine cycles of its
+// 1802: 8 clock cycles at 1.7609 MHz, about 4.5 µs each. The counts follow the
+// interpreter's own code, so they're what a game written on the VIP was timed
+// against.
+use crate::opcode::Opcode;
+
+// Machine cycles in a 60 Hz frame.
+pub const FRAME_CYCLES: i32 = 3668;
+// What the display takes out of every frame: a DMA cycle for each of the 8
+// bytes on each of 128 lines, and the interrupt routine that sets it up and
+// counts the timers down.
+const DISPLAY_CYCLES: i32 = 8 * 128 + 46;
+// What's left for the interpreter.
+pub const INTERPRETER_CYCLES: i32 = FRAME_CYCLES - DISPLAY_CYCLES;
+
+// Fetching an instruction and jumping to its routine, before it runs.
+const FETCH_CYCLES: u32 = 40;
+
+// The cycles an instruction took, fetch included. `vx` is VX before it ran
+// and `skipped` whether it skipped the next instruction. For DXYN that's the
+// drawing, which the VIP only started once the next frame began.
+pub fn cycles(opcode: &Opcode, vx: u8, skipped: bool) -> u32 {
+    let skip = |cycles: u32| if skipped { cycles + 4 } else { cycles };
+
+    let execute = match opcode.t() {
+        0x0000 => match opcode.kk() {
+            // A loop over the 256 bytes of the screen.
+            0xe0 => 3078,
+            _ => 10,
+        },
+        0x1000 => 12,
+        0x2000 => 26,
+        0x3000 | 0x4000 => skip(10),
+        0x5000 | 0x9000 => skip(14),
+        0x6000 => 6,
+        0x7000 => 10,
+        0x8000 => match opcode.n() {
+            0x0 => 12,
+            _ => 44,
+        },
+        0xA000 => 12,
+        0xB000 => 22,
+        0xC000 => 36,
+        0xD000 => draw(vx, opcode.n()),
+        0xE000 => skip(14),
+        0xF000 => match opcode.kk() {
+            0x0A => 19,
+            0x1E | 0x29 => 16,
+            // BCD counts each digit up one subtraction at a time.
+            0x33 => {
+                let digits = vx / 100 + vx / 10 % 10 + vx % 10;
+                84 + 16 * digits as u32
+            }
+            0x55 | 0x65 => 14 + 14 * (opcode.x() as u32 + 1),
+            _ => 10,
+        },
+        _ => 0,
+    };
+
+    FETCH_CYCLES + execute
+}
+
+// Sprites are drawn a byte per row, shifted right bit by bit to where they
+// go on the screen and then spread over two bytes of it unless they line up
+// with one.
+fn draw(x: u8, rows: u8) -> u32 {
+    let shift = (x % 8) as u32;
+    let row = if shift == 0 { 34 } else { 54 + 10 * shift };
+
+    26 + rows as u32 * row
+}
@@ -0,0 +1,185 @@
+// The emulator for web pages, built with wasm-bindgen. JavaScript owns the
+// loop: it calls `step_frame` from requestAnimationFrame, copies
+// `framebuffer` into an ImageData and plays a tone while `beeping` is true.
+use chip8_rs::cpu::{Cpu, Graphics, GRAPHICS_HEIGHT, GRAPHICS_WIDTH, KEYPAD_SIZE};
+use chip8_rs::host::{self, Host};
+use chip8_rs::palette::Palette;
+use chip8_rs::quirks::Quirks;
+
+use wasm_bindgen::prelude::*;
+
+const DEFAULT_SPEED: u8 = 10;
+const BYTES_PER_PIXEL: usize = 4;
+
+// The screen as RGBA, four bytes per CHIP-8 pixel, ready for an ImageData.
+struct Framebuffer {
+    pixels: Vec<u8>,
+    palette: Palette,
+}
+
+impl host::Display for Framebuffer {
+    fn draw(&mut self, graphics: &Graphics) {
+        let on = rgba(self.palette.on());
+        let off = rgba(self.palette.off());
+
+        for (pixel, &lit) in self
+            .pixels
+            .chunks_mut(BYTES_PER_PIXEL)
+            .zip(graphics.iter().flatten())
+        {
+            pixel.copy_from_slice(if lit { &on } else { &off });
+        }
+    }
+}
+
+// Palette colours are 0x00RRGGBB; the alpha channel is always opaque.
+fn rgba(color: u32) -> [u8; BYTES_PER_PIXEL] {
+    let [_, r, g, b] = color.to_be_bytes();
+    [r, g, b, 0xff]
+}
+
+struct Beeper {
+    beeping: bool,
+}
+
+impl host::Audio for Beeper {
+    fn set_beeping(&mut self, beeping: bool) {
+        self.beeping = beeping;
+    }
+}
+
+#[wasm_bindgen]
+pub struct Emulator {
+    cpu: Cpu,
+    rom: Vec<u8>,
+    speed: u8,
+    keys: [bool; KEYPAD_SIZE],
+    framebuffer: Framebuffer,
+    beeper: Beeper,
+}
+
+#[wasm_bindgen]
+impl Emulator {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Emulator {
+        let palette = Palette::default();
+        let mut framebuffer = Framebuffer {
+            pixels: vec![0; GRAPHICS_WIDTH * GRAPHICS_HEIGHT * BYTES_PER_PIXEL],
+            palette,
+        };
+        host::Display::draw(
+            &mut framebuffer,
+            &[[false; GRAPHICS_WIDTH]; GRAPHICS_HEIGHT],
+        );
+
+        Emulator {
+            cpu: Cpu::new(),
+            rom: Vec::new(),
+            speed: DEFAULT_SPEED,
+            keys: [false; KEYPAD_SIZE],
+            framebuffer,
+            beeper: Beeper { beeping: false },
+        }
+    }
+
+    // Starts the given ROM from scratch, keeping the speed, quirks and palette.
+    #[wasm_bindgen(js_name = loadRom)]
+    pub fn load_rom(&mut self, rom: &[u8]) -> Result<(), JsValue> {
+        self.cpu
+            .load_rom(rom)
+            .map_err(|e| JsValue::from_str(&e.to_string()))?;
+
+        self.rom = rom.to_vec();
+        self.beeper.beeping = false;
+        Ok(())
+    }
+
+    // Reloading clears memory the ROM may have written over.
+    pub fn reset(&mut self) -> Result<(), JsValue> {
+        self.cpu
+            .load_rom(&self.rom)
+            .map_err(|e| JsValue::from_str(&e.to_string()))?;
+
+        self.beeper.beeping = false;
+        Ok(())
+    }
+
+    // Instructions run per frame, 1 to 255.
+    #[wasm_bindgen(js_name = setSpeed)]
+    pub fn set_speed(&mut self, speed: u8) {
+        self.speed = speed.max(1);
+    }
+
+    // Takes the same list as --quirks, e.g. `vip` or `shift-vy,clip`.
+    #[wasm_bindgen(js_name = setQuirks)]
+    pub fn set_quirks(&mut self, list: &str) -> Result<(), JsValue> {
+        let quirks = Quirks::parse(list).map_err(|e| JsValue::from_str(&e))?;
+        self.cpu.set_quirks(quirks);
+        Ok(())
+    }
+
+    // Colours as 0xRRGGBB.
+    #[wasm_bindgen(js_name = setPalette)]
+    pub fn set_palette(&mut self, on: u32, off: u32) {
+        self.framebuffer.palette = Palette::new(&[off, on]).expect("two colors make a palette");
+        self.cpu.redraw();
+    }
+
+    // Takes a preset name or a list of colors, as accepted by --palette.
+    #[wasm_bindgen(js_name = setPaletteNamed)]
+    pub fn set_palette_named(&mut self, name: &str) -> Result<(), JsValue> {
+        self.framebuffer.palette = Palette::parse(name).map_err(|e| JsValue::from_str(&e))?;
+        self.cpu.redraw();
+        Ok(())
+    }
+
+    // Keys 0x0 to 0xF; anything else is ignored.
+    #[wasm_bindgen(js_name = setKey)]
+    pub fn set_key(&mut self, key: u8, down: bool) {
+        if let Some(state) = self.keys.get_mut(key as usize) {
+            *state = down;
+        }
+    }
+
+    // Runs one 60 Hz frame. Returns whether the framebuffer changed.
+    #[wasm_bindgen(js_name = stepFrame)]
+    pub fn step_frame(&mut self) -> bool {
+        let mut host = Host {
+            display: &mut self.framebuffer,
+            audio: &mut self.beeper,
+            keypad: &mut self.keys,
+            clock: &mut (),
+        };
+
+        host.run_frame(&mut self.cpu, self.speed)
+    }
+
+    // The screen as GRAPHICS_WIDTH * GRAPHICS_HEIGHT RGBA pixels, row by row.
+    pub fn framebuffer(&self) -> Vec<u8> {
+        self.framebuffer.pixels.clone()
+    }
+
+    // Whether the sound timer is running, as of the last frame.
+    pub fn beeping(&self) -> bool {
+        self.beeper.beeping
+    }
+
+    #[wasm_bindgen(js_name = isDone)]
+    pub fn is_done(&self) -> bool {
+        self.cpu.is_done()
+    }
+
+    pub fn width() -> usize {
+        GRAPHICS_WIDTH
+    }
+
+    pub fn height() -> usize {
+        GRAPHICS_HEIGHT
+    }
+}
+
+impl Default for Emulator {
+    fn default() -> Emulator {
+        Emulator::new()
+    }
+}
@@ -0,0 +1,103 @@
+use chip8_rs_wasm::Emulator;
+
+// The tests run in Node.js with --target wasm32-unknown-unknown, and natively
+// with the rest of the workspace.
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen_test::*;
+
+// Clears the screen, draws the font's 0 in the top left corner, starts the
+// sound timer and loops.
+const DRAW_ZERO: [u8; 12] = [
+    0x00, 0xe0, 0xa0, 0x00, 0xd0, 0x05, 0x6a, 0x0a, 0xfa, 0x18, 0x12, 0x0a,
+];
+
+// Waits for a key and draws its hex digit in the top left corner.
+const DRAW_KEY: [u8; 8] = [0xf0, 0x0a, 0xf0, 0x29, 0xd1, 0x15, 0x12, 0x06];
+
+const ON: u32 = 0xffffff;
+const OFF: u32 = 0x000000;
+
+fn pixel(emulator: &Emulator, x: usize, y: usize) -> [u8; 4] {
+    let framebuffer = emulator.framebuffer();
+    let i = (y * Emulator::width() + x) * 4;
+    [
+        framebuffer[i],
+        framebuffer[i + 1],
+        framebuffer[i + 2],
+        framebuffer[i + 3],
+    ]
+}
+
+fn emulator_with(rom: &[u8]) -> Emulator {
+    let mut emulator = Emulator::new();
+    emulator.set_palette(ON, OFF);
+    emulator.load_rom(rom).unwrap();
+    emulator
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn framebuffer_is_rgba_of_the_whole_screen() {
+    let emulator = Emulator::new();
+
+    assert_eq!(
+        emulator.framebuffer().len(),
+        Emulator::width() * Emulator::height() * 4
+    );
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn step_frame_draws_with_the_palette() {
+    let mut emulator = emulator_with(&DRAW_ZERO);
+
+    assert!(emulator.step_frame());
+    assert_eq!(pixel(&emulator, 0, 0), [0xff, 0xff, 0xff, 0xff]);
+    assert_eq!(pixel(&emulator, 4, 0), [0x00, 0x00, 0x00, 0xff]);
+    assert_eq!(pixel(&emulator, 1, 1), [0x00, 0x00, 0x00, 0xff]);
+
+    assert!(!emulator.step_frame());
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn beeping_follows_the_sound_timer() {
+    let mut emulator = emulator_with(&DRAW_ZERO);
+    assert!(!emulator.beeping());
+
+    emulator.step_frame();
+    assert!(emulator.beeping());
+
+    for _ in 0..10 {
+        emulator.step_frame();
+    }
+    assert!(!emulator.beeping());
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn set_key_is_seen_by_the_program() {
+    let mut emulator = emulator_with(&DRAW_KEY);
+
+    emulator.step_frame();
+    emulator.step_frame();
+    assert_eq!(pixel(&emulator, 0, 0), [0x00, 0x00, 0x00, 0xff]);
+
+    emulator.set_key(0x8, true);
+    emulator.step_frame();
+
+    // The 8 has its middle row lit, unlike the 0.
+    assert_eq!(pixel(&emulator, 1, 2), [0xff, 0xff, 0xff, 0xff]);
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn reset_starts_the_rom_over() {
+    let mut emulator = emulator_with(&DRAW_ZERO);
+    emulator.step_frame();
+
+    emulator.reset().unwrap();
+    assert!(!emulator.beeping());
+    assert!(emulator.step_frame());
+    assert!(emulator.beeping());
+}